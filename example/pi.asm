@@ -29,7 +29,8 @@ main:
 		li   $t3, -1
 		mul  $t2, $t3		# LO = numerator * -1
 		mflo $t2    		# t2 = LO
-		mtc1 $t1, $f1		# f1 holds k
+		mtc1 $t1, $f1		# f1 holds k (as int)
+		cvt.s.w $f1, $f1	# f1 holds k
 		add.s $f2, $f1, $f1	# f2 = 2k
 		add.s $f2, $f2, -1	# f2 = 2k - 1
 		div.s $f2, $t2, $f2	# f2 = numerator/(2k-1)
@@ -0,0 +1,209 @@
+use super::{Instruction, Operand};
+use super::encode::*;
+use super::super::token::InstructionKind;
+
+type Result<T> = std::result::Result<T, String>;
+
+/// Decode a MIPS32 machine code `word` at `address`.
+/// mfc1 and mov.s are decoded as `move`, lwc1 and swc1 as `lw` and `sw` (like the lexer).
+pub fn decode(word: u32, address: u32) -> Result<Instruction> {
+    use InstructionKind::*;
+    use Operand::{Register, Integer, Memory, Target};
+
+    let op     = word >> 26;
+    let rs     = (word >> 21 & 0x1f) as usize;
+    let rt     = (word >> 16 & 0x1f) as usize;
+    let rd     = (word >> 11 & 0x1f) as usize;
+    let shamt  = (word >> 6 & 0x1f) as i32;
+    let funct  = word & 0x3f;
+    let simm   = word as u16 as i16 as i32;
+    let zimm   = word as u16 as i32;
+    let branch = Target(address.wrapping_add(4).wrapping_add((simm << 2) as u32));
+    let jump   = Target((address.wrapping_add(4) & 0xf000_0000) | (word & 0x03ff_ffff) << 2);
+    // Floating point registers
+    let (ft, fs, fd) = (rt + 32, rd + 32, shamt as usize + 32);
+
+    let invalid = || Err(format!("invalid instruction: 0x{:08x}", word));
+    // Condition code operand of c.cond.fmt (fd field), bc1t/bc1f and movf/movt (rt field)
+    let cc = |cc: usize, mut operands: Vec<Operand>| {
+        if cc != 0 {
+            operands.insert(0, Integer(cc as i32));
+        }
+        operands
+    };
+
+    let (kind, operands) = match op {
+        SPECIAL => match funct {
+            0x00 if word == 0 => (NOP, vec![]),
+            0x00 => (SLL,  vec![Register(rd), Register(rt), Integer(shamt)]),
+            0x01 => {
+                let kind = if rt & 1 == 1 { MOVT } else { MOVF };
+                let mut operands = vec![Register(rd), Register(rs)];
+                if rt >> 2 != 0 {
+                    operands.push(Integer((rt >> 2) as i32));
+                }
+                (kind, operands)
+            },
+            0x02 if rs == 1 => (ROR, vec![Register(rd), Register(rt), Integer(shamt)]),  // rotr
+            0x02 => (SRL,  vec![Register(rd), Register(rt), Integer(shamt)]),
+            0x03 => (SRA,  vec![Register(rd), Register(rt), Integer(shamt)]),
+            0x04 => (SLLV, vec![Register(rd), Register(rt), Register(rs)]),
+            0x06 if shamt == 1 => (ROR, vec![Register(rd), Register(rt), Register(rs)]),  // rotrv
+            0x06 => (SRLV, vec![Register(rd), Register(rt), Register(rs)]),
+            0x07 => (SRAV, vec![Register(rd), Register(rt), Register(rs)]),
+            0x08 => (JR,   vec![Register(rs)]),
+            0x09 if rd == 31 => (JALR, vec![Register(rs)]),
+            0x09 => (JALR, vec![Register(rd), Register(rs)]),
+            0x0a => (MOVZ, vec![Register(rd), Register(rs), Register(rt)]),
+            0x0b => (MOVN, vec![Register(rd), Register(rs), Register(rt)]),
+            0x0c => (SYSCALL, vec![]),
+            0x0d if word >> 6 == 0 => (BREAK, vec![]),
+            0x0d => (BREAK, vec![Integer((word >> 6 & 0xfffff) as i32)]),
+            0x10 => (MFHI, vec![Register(rd)]),
+            0x11 => (MTHI, vec![Register(rs)]),
+            0x12 => (MFLO, vec![Register(rd)]),
+            0x13 => (MTLO, vec![Register(rs)]),
+            0x18 => (MULT,  vec![Register(rs), Register(rt)]),
+            0x19 => (MULTU, vec![Register(rs), Register(rt)]),
+            0x1a => (DIV,   vec![Register(rs), Register(rt)]),
+            0x1b => (DIVU,  vec![Register(rs), Register(rt)]),
+            0x20 => (ADD,  vec![Register(rd), Register(rs), Register(rt)]),
+            0x21 => (ADDU, vec![Register(rd), Register(rs), Register(rt)]),
+            0x22 => (SUB,  vec![Register(rd), Register(rs), Register(rt)]),
+            0x23 => (SUBU, vec![Register(rd), Register(rs), Register(rt)]),
+            0x24 => (AND,  vec![Register(rd), Register(rs), Register(rt)]),
+            0x25 => (OR,   vec![Register(rd), Register(rs), Register(rt)]),
+            0x26 => (XOR,  vec![Register(rd), Register(rs), Register(rt)]),
+            0x27 => (NOR,  vec![Register(rd), Register(rs), Register(rt)]),
+            0x2a => (SLT,  vec![Register(rd), Register(rs), Register(rt)]),
+            0x2b => (SLTU, vec![Register(rd), Register(rs), Register(rt)]),
+            0x30 => (TGE,  vec![Register(rs), Register(rt)]),
+            0x31 => (TGEU, vec![Register(rs), Register(rt)]),
+            0x32 => (TLT,  vec![Register(rs), Register(rt)]),
+            0x33 => (TLTU, vec![Register(rs), Register(rt)]),
+            0x34 => (TEQ,  vec![Register(rs), Register(rt)]),
+            0x36 => (TNE,  vec![Register(rs), Register(rt)]),
+            _ => return invalid(),
+        },
+        REGIMM => match rt {
+            0x00 => (BLTZ,   vec![Register(rs), branch]),
+            0x01 => (BGEZ,   vec![Register(rs), branch]),
+            0x08 => (TGEI,   vec![Register(rs), Integer(simm)]),
+            0x09 => (TGEIU,  vec![Register(rs), Integer(simm)]),
+            0x0a => (TLTI,   vec![Register(rs), Integer(simm)]),
+            0x0b => (TLTIU,  vec![Register(rs), Integer(simm)]),
+            0x0c => (TEQI,   vec![Register(rs), Integer(simm)]),
+            0x0e => (TNEI,   vec![Register(rs), Integer(simm)]),
+            0x10 => (BLTZAL, vec![Register(rs), branch]),
+            0x11 => (BGEZAL, vec![Register(rs), branch]),
+            _ => return invalid(),
+        },
+        0x02 => (J,     vec![jump]),
+        0x03 => (JAL,   vec![jump]),
+        0x04 => (BEQ,   vec![Register(rs), Register(rt), branch]),
+        0x05 => (BNE,   vec![Register(rs), Register(rt), branch]),
+        0x06 => (BLEZ,  vec![Register(rs), branch]),
+        0x07 => (BGTZ,  vec![Register(rs), branch]),
+        0x08 => (ADDI,  vec![Register(rt), Register(rs), Integer(simm)]),
+        0x09 => (ADDIU, vec![Register(rt), Register(rs), Integer(simm)]),
+        0x0a => (SLTI,  vec![Register(rt), Register(rs), Integer(simm)]),
+        0x0b => (SLTIU, vec![Register(rt), Register(rs), Integer(simm)]),
+        0x0c => (ANDI,  vec![Register(rt), Register(rs), Integer(zimm)]),
+        0x0d => (ORI,   vec![Register(rt), Register(rs), Integer(zimm)]),
+        0x0e => (XORI,  vec![Register(rt), Register(rs), Integer(zimm)]),
+        0x0f => (LUI,   vec![Register(rt), Integer(zimm)]),
+        COP0 => match (rs, funct) {
+            (0x00, _) => (MFC0, vec![Register(rt), Register(rd)]),
+            (0x04, _) => (MTC0, vec![Register(rt), Register(rd)]),
+            (0x10, 0x18) => (ERET, vec![]),
+            _ => return invalid(),
+        },
+        COP1 => match (rs, funct) {
+            (0x00, _) => (MOVE, vec![Register(rt), Register(fs)]),  // mfc1
+            (0x04, _) => (MTC1, vec![Register(rt), Register(fs)]),
+            (0x02, _) => (CFC1, vec![Register(rt), Register(rd)]),
+            (0x06, _) => (CTC1, vec![Register(rt), Register(rd)]),
+            (0x08, _) if rt & 1 == 1 => (BC1T, cc(rt >> 2, vec![branch])),
+            (0x08, _) => (BC1F, cc(rt >> 2, vec![branch])),
+            (0x10, 0x00) => (ADD_S, vec![Register(fd), Register(fs), Register(ft)]),
+            (0x10, 0x01) => (SUB_S, vec![Register(fd), Register(fs), Register(ft)]),
+            (0x10, 0x02) => (MUL_S, vec![Register(fd), Register(fs), Register(ft)]),
+            (0x10, 0x03) => (DIV_S, vec![Register(fd), Register(fs), Register(ft)]),
+            (0x10, 0x04) => (SQRT_S, vec![Register(fd), Register(fs)]),
+            (0x10, 0x05) => (ABS_S, vec![Register(fd), Register(fs)]),
+            (0x10, 0x06) => (MOVE,  vec![Register(fd), Register(fs)]),  // mov.s
+            (0x10, 0x07) => (NEG_S, vec![Register(fd), Register(fs)]),
+            (0x10, 0x0c) => (ROUND_W_S, vec![Register(fd), Register(fs)]),
+            (0x10, 0x0d) => (TRUNC_W_S, vec![Register(fd), Register(fs)]),
+            (0x10, 0x0e) => (CEIL_W_S,  vec![Register(fd), Register(fs)]),
+            (0x10, 0x0f) => (FLOOR_W_S, vec![Register(fd), Register(fs)]),
+            (0x10, 0x12) => (MOVZ_S, vec![Register(fd), Register(fs), Register(rt)]),
+            (0x10, 0x13) => (MOVN_S, vec![Register(fd), Register(fs), Register(rt)]),
+            (0x10, 0x15) => (RECIP_S, vec![Register(fd), Register(fs)]),
+            (0x10, 0x16) => (RSQRT_S, vec![Register(fd), Register(fs)]),
+            (0x10, 0x24) => (CVT_W_S, vec![Register(fd), Register(fs)]),
+            (0x10, 0x32) => (C_EQ_S, cc(shamt as usize >> 2, vec![Register(fs), Register(ft)])),
+            (0x10, 0x3c) => (C_LT_S, cc(shamt as usize >> 2, vec![Register(fs), Register(ft)])),
+            (0x10, 0x3e) => (C_LE_S, cc(shamt as usize >> 2, vec![Register(fs), Register(ft)])),
+            (0x14, 0x20) => (CVT_S_W, vec![Register(fd), Register(fs)]),
+            (0x10, 0x21) => (CVT_D_S, vec![Register(fd), Register(fs)]),
+            (0x11, 0x00) => (ADD_D,  vec![Register(fd), Register(fs), Register(ft)]),
+            (0x11, 0x01) => (SUB_D,  vec![Register(fd), Register(fs), Register(ft)]),
+            (0x11, 0x02) => (MUL_D,  vec![Register(fd), Register(fs), Register(ft)]),
+            (0x11, 0x03) => (DIV_D,  vec![Register(fd), Register(fs), Register(ft)]),
+            (0x11, 0x04) => (SQRT_D, vec![Register(fd), Register(fs)]),
+            (0x11, 0x05) => (ABS_D,  vec![Register(fd), Register(fs)]),
+            (0x11, 0x06) => (MOV_D,  vec![Register(fd), Register(fs)]),
+            (0x11, 0x07) => (NEG_D,  vec![Register(fd), Register(fs)]),
+            (0x11, 0x20) => (CVT_S_D, vec![Register(fd), Register(fs)]),
+            (0x11, 0x24) => (CVT_W_D, vec![Register(fd), Register(fs)]),
+            (0x11, 0x32) => (C_EQ_D, cc(shamt as usize >> 2, vec![Register(fs), Register(ft)])),
+            (0x11, 0x3c) => (C_LT_D, cc(shamt as usize >> 2, vec![Register(fs), Register(ft)])),
+            (0x11, 0x3e) => (C_LE_D, cc(shamt as usize >> 2, vec![Register(fs), Register(ft)])),
+            (0x14, 0x21) => (CVT_D_W, vec![Register(fd), Register(fs)]),
+            _ => return invalid(),
+        },
+        SPECIAL2 => match funct {
+            0x00 => (MADD,  vec![Register(rs), Register(rt)]),
+            0x01 => (MADDU, vec![Register(rs), Register(rt)]),
+            0x02 => (MUL,   vec![Register(rd), Register(rs), Register(rt)]),
+            0x04 => (MSUB,  vec![Register(rs), Register(rt)]),
+            0x05 => (MSUBU, vec![Register(rs), Register(rt)]),
+            0x20 => (CLZ,   vec![Register(rd), Register(rs)]),
+            0x21 => (CLO,   vec![Register(rd), Register(rs)]),
+            UDI_PRTN => (PRTN, vec![]),
+            UDI_RST  => (RST,  vec![]),
+            UDI_PRTI | UDI_PRTH | UDI_PRTX | UDI_PRTC | UDI_PRTS => {
+                let kind = match funct {
+                    UDI_PRTI => PRTI,
+                    UDI_PRTH => PRTH,
+                    UDI_PRTX => PRTX,
+                    UDI_PRTC => PRTC,
+                    _        => PRTS,
+                };
+                let operand = if shamt == 1 { Memory(rs, 0) } else { Register(rs) };
+                (kind, vec![operand])
+            },
+            _ => return invalid(),
+        },
+        COP1X => match funct {
+            0x20 => (MADD_S, vec![Register(fd), Register(rs + 32), Register(fs), Register(ft)]),
+            _ => return invalid(),
+        },
+        0x20 => (LB,  vec![Register(rt), Memory(rs, simm)]),
+        0x21 => (LH,  vec![Register(rt), Memory(rs, simm)]),
+        0x23 => (LW,  vec![Register(rt), Memory(rs, simm)]),
+        0x24 => (LBU, vec![Register(rt), Memory(rs, simm)]),
+        0x25 => (LHU, vec![Register(rt), Memory(rs, simm)]),
+        0x28 => (SB,  vec![Register(rt), Memory(rs, simm)]),
+        0x29 => (SH,  vec![Register(rt), Memory(rs, simm)]),
+        0x2b => (SW,  vec![Register(rt), Memory(rs, simm)]),
+        0x31 => (LW,  vec![Register(ft), Memory(rs, simm)]),  // lwc1
+        0x39 => (SW,  vec![Register(ft), Memory(rs, simm)]),  // swc1
+        0x35 => (LDC1, vec![Register(ft), Memory(rs, simm)]),
+        0x3d => (SDC1, vec![Register(ft), Memory(rs, simm)]),
+        _ => return invalid(),
+    };
+
+    Ok(Instruction { kind, operands, address, size: 4, line: 0, filename_idx: 0 })
+}
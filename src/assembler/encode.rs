@@ -0,0 +1,703 @@
+use super::{Instruction, Operand};
+use super::super::token::InstructionKind;
+use super::super::token::cp0::Exception;
+
+type Result<T> = std::result::Result<T, String>;
+
+const ZERO: u32 = 0;
+const AT:   u32 = 1;
+const RA:   u32 = 31;
+
+// Opcode
+pub(super) const SPECIAL:  u32 = 0x00;
+pub(super) const REGIMM:   u32 = 0x01;
+pub(super) const COP0:     u32 = 0x10;
+pub(super) const COP1:     u32 = 0x11;
+pub(super) const COP1X:    u32 = 0x13;
+pub(super) const SPECIAL2: u32 = 0x1c;
+
+// COP1 fmt
+const FMT_S: u32 = 0x10;
+const FMT_D: u32 = 0x11;
+const FMT_W: u32 = 0x14;
+
+/// My own instructions are encoded as SPECIAL2 UDI (User Defined Instruction).
+/// `prt? rs` prints the value of rs, `shamt = 1` prints the value at the address in rs.
+pub const UDI_PRTN: u32 = 0x10;
+pub const UDI_PRTI: u32 = 0x11;
+pub const UDI_PRTH: u32 = 0x12;
+pub const UDI_PRTX: u32 = 0x13;
+pub const UDI_PRTC: u32 = 0x14;
+pub const UDI_PRTS: u32 = 0x15;
+pub const UDI_RST:  u32 = 0x16;
+
+/// Encode `instruction` into MIPS32 machine code.
+/// Pseudo instructions are expanded using $at.
+pub fn encode(instruction: &Instruction) -> Result<Vec<u32>> {
+    let mut encoder = Encoder::new(instruction.address);
+    encoder.instruction(instruction.kind, &instruction.operands)
+        .map_err(|e| format!("cannot encode {:?}: {}", instruction.kind, e))?;
+    match encoder.out_of_range {
+        Some(e) => Err(e),
+        None => Ok(encoder.words),
+    }
+}
+
+/// Size of the encoding in bytes.
+/// Depends only on the shape of operands (not on label addresses).
+/// 1 word if `instruction` cannot be encoded.
+pub fn size(instruction: &Instruction) -> u32 {
+    let mut encoder = Encoder::new(instruction.address);
+    match encoder.instruction(instruction.kind, &instruction.operands) {
+        Ok(()) => encoder.words.len() as u32 * 4,
+        Err(_) => 4,
+    }
+}
+
+/// Index of the word of the expansion which raises `exception`.
+/// It is the last word except `break` of the checks by `mulo`, `div`, `rem`, ...
+pub fn faulting_word(instruction: &Instruction, exception: Exception) -> u32 {
+    let last = (instruction.size / 4).max(1) - 1;
+    let mut encoder = Encoder::new(instruction.address);
+    match (exception, encoder.instruction(instruction.kind, &instruction.operands)) {
+        (Exception::Breakpoint(_), Ok(())) => encoder.words.iter()
+            .position(|w| w >> 26 == SPECIAL && w & 0x3f == 0x0d)
+            .map_or(last, |i| i as u32),
+        _ => last,
+    }
+}
+
+fn r_type(op: u32, rs: u32, rt: u32, rd: u32, shamt: u32, funct: u32) -> u32 {
+    op << 26 | rs << 21 | rt << 16 | rd << 11 | (shamt & 0x1f) << 6 | funct
+}
+
+fn i_type(op: u32, rs: u32, rt: u32, imm: i32) -> u32 {
+    op << 26 | rs << 21 | rt << 16 | (imm as u32 & 0xffff)
+}
+
+fn fits_signed(imm: i32) -> bool {
+    (-0x8000..=0x7fff).contains(&imm)
+}
+
+fn fits_unsigned(imm: i32) -> bool {
+    (0..=0xffff).contains(&imm)
+}
+
+/// SPECIAL funct of the register form of trap instructions
+fn trap_funct(kind: InstructionKind) -> u32 {
+    use InstructionKind::*;
+    match kind {
+        TGE  | TGEI  => 0x30,
+        TGEU | TGEIU => 0x31,
+        TLT  | TLTI  => 0x32,
+        TLTU | TLTIU => 0x33,
+        TEQ  | TEQI  => 0x34,
+        _            => 0x36,  // TNE, TNEI
+    }
+}
+
+/// Condition code operand (default 0) and the other operands.  e.g. `c.eq.s 2, $f0, $f1`, `bc1t 2, label`
+pub fn condition_code(operands: &[Operand]) -> (usize, &[Operand]) {
+    match operands {
+        [Operand::Integer(cc @ 0..=7), rest @ ..] => (*cc as usize, rest),
+        _ => (0, operands),
+    }
+}
+
+/// Even register of the pair which holds a double
+fn dpr(operand: &Operand) -> Result<u32> {
+    match fpr(operand)? {
+        idx if idx % 2 == 0 => Ok(idx),
+        _ => Err(format!("expect even floating point register for double. but got: {:?}", operand)),
+    }
+}
+
+fn gpr(operand: &Operand) -> Result<u32> {
+    match *operand {
+        Operand::Register(idx @ 0..=31) => Ok(idx as u32),
+        _ => Err(format!("expect general purpose register. but got: {:?}", operand)),
+    }
+}
+
+fn fpr(operand: &Operand) -> Result<u32> {
+    match *operand {
+        Operand::Register(idx @ 32..=63) => Ok(idx as u32 - 32),
+        _ => Err(format!("expect floating point register. but got: {:?}", operand)),
+    }
+}
+
+struct Encoder {
+    address: u32,                  // Address of the first word
+    words: Vec<u32>,
+    out_of_range: Option<String>,  // Branch/jump target is too far (the size is still valid)
+}
+
+impl Encoder {
+    fn new(address: u32) -> Self {
+        Encoder { address, words: Vec::new(), out_of_range: None }
+    }
+
+    /// Address of the next word
+    fn pc(&self) -> u32 {
+        self.address.wrapping_add(self.words.len() as u32 * 4)
+    }
+
+    fn special(&mut self, rs: u32, rt: u32, rd: u32, shamt: u32, funct: u32) {
+        self.words.push(r_type(SPECIAL, rs, rt, rd, shamt, funct));
+    }
+
+    fn special2(&mut self, rs: u32, rt: u32, rd: u32, shamt: u32, funct: u32) {
+        self.words.push(r_type(SPECIAL2, rs, rt, rd, shamt, funct));
+    }
+
+    fn cop1(&mut self, fmt: u32, ft: u32, fs: u32, fd: u32, funct: u32) {
+        self.words.push(r_type(COP1, fmt, ft, fs, fd, funct));
+    }
+
+    fn immediate(&mut self, op: u32, rs: u32, rt: u32, imm: i32) {
+        self.words.push(i_type(op, rs, rt, imm));
+    }
+
+    /// Offset is relative to PC+4
+    fn branch(&mut self, op: u32, rs: u32, rt: u32, target: u32) {
+        let offset = (target as i64 - (self.pc() as i64 + 4)) >> 2;
+        if !(-0x8000..=0x7fff).contains(&offset) {
+            self.out_of_range.get_or_insert(format!("branch target out of range: 0x{:08x}", target));
+        }
+        self.immediate(op, rs, rt, offset as i32);
+    }
+
+    /// Target must be in the same 256MB region as PC+4
+    fn jump(&mut self, op: u32, target: u32) {
+        if (self.pc().wrapping_add(4) ^ target) & 0xf000_0000 != 0 || target & 3 != 0 {
+            self.out_of_range.get_or_insert(format!("jump target out of range: 0x{:08x}", target));
+        }
+        self.words.push(op << 26 | (target >> 2 & 0x03ff_ffff));
+    }
+
+    /// li rd, imm
+    fn load_immediate(&mut self, rd: u32, imm: i32) {
+        if fits_signed(imm) {
+            self.immediate(0x09, ZERO, rd, imm);  // addiu rd, $zero, imm
+        } else if fits_unsigned(imm) {
+            self.immediate(0x0d, ZERO, rd, imm);  // ori rd, $zero, imm
+        } else {
+            self.immediate(0x0f, ZERO, AT, imm >> 16);  // lui $at, imm[31:16]
+            self.immediate(0x0d, AT, rd, imm);          // ori rd, $at, imm[15:0]
+        }
+    }
+
+    /// Register index. Immediate is loaded into $at.
+    fn register_or_at(&mut self, operand: &Operand) -> Result<u32> {
+        match *operand {
+            Operand::Integer(imm) => {
+                self.load_immediate(AT, imm);
+                Ok(AT)
+            },
+            _ => gpr(operand),
+        }
+    }
+
+    /// (base register, offset) of the memory operand. Large offset is added to $at.
+    /// An integer is an absolute address.
+    fn memory(&mut self, operand: &Operand) -> Result<(u32, i32)> {
+        match *operand {
+            Operand::Memory(r_idx, offset) if fits_signed(offset) =>
+                Ok((gpr(&Operand::Register(r_idx))?, offset)),
+            Operand::Memory(r_idx, offset) => {
+                let base = gpr(&Operand::Register(r_idx))?;
+                let upper = (offset as u32).wrapping_add(0x8000) >> 16;
+                self.immediate(0x0f, ZERO, AT, upper as i32);  // lui $at, upper
+                if base != ZERO {
+                    self.special(AT, base, AT, 0, 0x21);        // addu $at, $at, base
+                }
+                Ok((AT, offset))
+            },
+            Operand::Integer(address) => self.memory(&Operand::Memory(0, address)),
+            Operand::Address(address) => {
+                let upper = address.wrapping_add(0x8000) >> 16;
+                self.immediate(0x0f, ZERO, AT, upper as i32);  // lui $at, upper
+                Ok((AT, address as i32))
+            },
+            _ => Err(format!("expect address. but got: {:?}", operand)),
+        }
+    }
+
+    fn instruction(&mut self, kind: InstructionKind, operands: &[Operand]) -> Result<()> {
+        use InstructionKind::*;
+
+        match (kind, operands) {
+            // Arithmetic, Logic
+            (ADD | ADDU | ADDI | ADDIU | SUB | SUBU | AND | ANDI | OR | ORI | XOR | XORI | NOR |
+             SLT | SLTI | SLTU | SLTIU, [rd, rs, rt]) => {
+                let (funct, immediate) = match kind {
+                    ADD  | ADDI  => (0x20, Some((0x08, true))),
+                    ADDU | ADDIU => (0x21, Some((0x09, true))),
+                    SUB          => (0x22, None),
+                    SUBU         => (0x23, None),
+                    AND  | ANDI  => (0x24, Some((0x0c, false))),
+                    OR   | ORI   => (0x25, Some((0x0d, false))),
+                    XOR  | XORI  => (0x26, Some((0x0e, false))),
+                    NOR          => (0x27, None),
+                    SLTU | SLTIU => (0x2b, Some((0x0b, true))),
+                    _            => (0x2a, Some((0x0a, true))),  // SLT, SLTI
+                };
+                let (rd, rs) = (gpr(rd)?, gpr(rs)?);
+                match (rt, immediate) {
+                    (&Operand::Integer(imm), Some((op, true)))  if fits_signed(imm)   => self.immediate(op, rs, rd, imm),
+                    (&Operand::Integer(imm), Some((op, false))) if fits_unsigned(imm) => self.immediate(op, rs, rd, imm),
+                    _ => {
+                        let rt = self.register_or_at(rt)?;
+                        self.special(rs, rt, rd, 0, funct);
+                    },
+                }
+            },
+            (MOVN | MOVZ, [rd, rs, rt]) => {
+                let funct = if kind == MOVZ { 0x0a } else { 0x0b };
+                self.special(gpr(rs)?, gpr(rt)?, gpr(rd)?, 0, funct);
+            },
+            (MUL, [rd, rs, rt]) => {
+                let (rd, rs) = (gpr(rd)?, gpr(rs)?);
+                let rt = self.register_or_at(rt)?;
+                self.special2(rs, rt, rd, 0, 0x02);
+            },
+            (MUL | MULT | MULTU | DIV | DIVU, [rs, rt]) => {
+                let funct = match kind {
+                    MULTU => 0x19,
+                    DIV   => 0x1a,
+                    DIVU  => 0x1b,
+                    _     => 0x18,  // MUL, MULT
+                };
+                self.special(gpr(rs)?, gpr(rt)?, 0, 0, funct);
+            },
+            (MADD | MADDU | MSUB | MSUBU, [rs, rt]) => {
+                let funct = match kind {
+                    MADD  => 0x00,
+                    MADDU => 0x01,
+                    MSUB  => 0x04,
+                    _     => 0x05,  // MSUBU
+                };
+                self.special2(gpr(rs)?, gpr(rt)?, 0, 0, funct);
+            },
+            // Break if overflow or divided by zero (like MARS)
+            (MULO | MULOU | DIV | DIVU | REM | REMU, [rd, rs, rt]) => {
+                let (rd, rs) = (gpr(rd)?, gpr(rs)?);
+                let rt = self.register_or_at(rt)?;
+                match kind {
+                    MULO => {
+                        self.special(rs, rt, 0, 0, 0x18);    // mult rs, rt
+                        self.special(0, 0, AT, 0, 0x10);     // mfhi $at
+                        self.special(0, 0, rd, 0, 0x12);     // mflo rd
+                        self.special(0, rd, rd, 31, 0x03);   // sra rd, rd, 31
+                        self.immediate(0x04, AT, rd, 1);     // beq $at, rd, 1
+                        self.special(0, 0, 0, 0, 0x0d);      // break
+                    },
+                    MULOU => {
+                        self.special(rs, rt, 0, 0, 0x19);    // multu rs, rt
+                        self.special(0, 0, AT, 0, 0x10);     // mfhi $at
+                        self.immediate(0x04, AT, ZERO, 1);   // beq $at, $zero, 1
+                        self.special(0, 0, 0, 0, 0x0d);      // break
+                    },
+                    _ => {
+                        let funct = if matches!(kind, DIV | REM) { 0x1a } else { 0x1b };
+                        self.immediate(0x05, rt, ZERO, 1);   // bne rt, $zero, 1
+                        self.special(0, 0, 0, 0, 0x0d);      // break
+                        self.special(rs, rt, 0, 0, funct);   // div/divu rs, rt
+                    },
+                }
+                let mf = if matches!(kind, REM | REMU) { 0x10 } else { 0x12 };
+                self.special(0, 0, rd, 0, mf);               // mfhi/mflo rd
+            },
+            (CLO | CLZ, [rd, rs]) => {
+                let funct = if kind == CLO { 0x21 } else { 0x20 };
+                let rd = gpr(rd)?;
+                self.special2(gpr(rs)?, rd, rd, 0, funct);
+            },
+            // rotr, rotrv (MIPS32 Release 2)
+            (ROR | ROL, [rd, rs, Operand::Integer(shamt)]) => {
+                let shamt = if kind == ROR { *shamt } else { 32 - *shamt };
+                self.special(1, gpr(rs)?, gpr(rd)?, shamt as u32, 0x02);
+            },
+            (ROR, [rd, rs, rt]) => {
+                self.special(gpr(rt)?, gpr(rs)?, gpr(rd)?, 1, 0x06);
+            },
+            (ROL, [rd, rs, rt]) => {
+                self.special(ZERO, gpr(rt)?, AT, 0, 0x23);  // subu $at, $zero, rt
+                self.special(AT, gpr(rs)?, gpr(rd)?, 1, 0x06);
+            },
+            (NOT, [rd, rs]) => {
+                self.special(gpr(rs)?, ZERO, gpr(rd)?, 0, 0x27);
+            },
+            (NEG | NEGU, [rd, rs]) => {
+                let funct = if kind == NEG { 0x22 } else { 0x23 };
+                self.special(ZERO, gpr(rs)?, gpr(rd)?, 0, funct);
+            },
+
+            // Shift
+            (SLL | SLLV | SRA | SRAV | SRL | SRLV, [rd, rt, shift]) => {
+                let funct = match kind {
+                    SLL | SLLV => 0x00,
+                    SRL | SRLV => 0x02,
+                    _          => 0x03,  // SRA, SRAV
+                };
+                let (rd, rt) = (gpr(rd)?, gpr(rt)?);
+                match *shift {
+                    Operand::Integer(shamt) => self.special(0, rt, rd, shamt as u32, funct),
+                    _ => self.special(gpr(shift)?, rt, rd, 0, funct | 0x04),
+                }
+            },
+
+            // Constant
+            (LI, [rd, Operand::Integer(imm)]) => {
+                self.load_constant(rd, *imm)?;
+            },
+            (LI, [rd, Operand::Floating(float)]) => {
+                self.load_constant(rd, float.to_bits() as i32)?;
+            },
+            (LUI, [rt, Operand::Integer(imm)]) => {
+                self.immediate(0x0f, ZERO, gpr(rt)?, *imm);
+            },
+
+            // Comparison
+            (SEQ | SNE | SGT | SGE | SLE | SGTU | SGEU | SLEU, [rd, rs, rt]) => {
+                let (rd, rs) = (gpr(rd)?, gpr(rs)?);
+                let rt = self.register_or_at(rt)?;
+                let slt = if matches!(kind, SGTU | SGEU | SLEU) { 0x2b } else { 0x2a };
+                match kind {
+                    SEQ => {
+                        self.special(rs, rt, rd, 0, 0x26);  // xor rd, rs, rt
+                        self.immediate(0x0b, rd, rd, 1);    // sltiu rd, rd, 1
+                    },
+                    SNE => {
+                        self.special(rs, rt, rd, 0, 0x26);  // xor rd, rs, rt
+                        self.special(ZERO, rd, rd, 0, 0x2b);  // sltu rd, $zero, rd
+                    },
+                    SGT | SGTU => {
+                        self.special(rt, rs, rd, 0, slt);   // slt[u] rd, rt, rs
+                    },
+                    SGE | SGEU => {
+                        self.special(rs, rt, rd, 0, slt);   // slt[u] rd, rs, rt
+                        self.immediate(0x0e, rd, rd, 1);    // xori rd, rd, 1
+                    },
+                    _ => {  // SLE, SLEU
+                        self.special(rt, rs, rd, 0, slt);   // slt[u] rd, rt, rs
+                        self.immediate(0x0e, rd, rd, 1);    // xori rd, rd, 1
+                    },
+                }
+            },
+
+            // Branch
+            (B, [Operand::Target(target)]) => {
+                self.branch(0x04, ZERO, ZERO, *target);
+            },
+            (BEQ | BNE, [rs, rt, Operand::Target(target)]) => {
+                let op = if kind == BEQ { 0x04 } else { 0x05 };
+                let rs = gpr(rs)?;
+                let rt = self.register_or_at(rt)?;
+                self.branch(op, rs, rt, *target);
+            },
+            (BEQZ | BNEZ, [rs, Operand::Target(target)]) => {
+                let op = if kind == BEQZ { 0x04 } else { 0x05 };
+                self.branch(op, gpr(rs)?, ZERO, *target);
+            },
+            (BGTZ | BLEZ, [rs, Operand::Target(target)]) => {
+                let op = if kind == BGTZ { 0x07 } else { 0x06 };
+                self.branch(op, gpr(rs)?, 0, *target);
+            },
+            (BLTZ | BGEZ | BLTZAL | BGEZAL, [rs, Operand::Target(target)]) => {
+                let rt = match kind {
+                    BLTZ   => 0x00,
+                    BGEZ   => 0x01,
+                    BLTZAL => 0x10,
+                    _      => 0x11,  // BGEZAL
+                };
+                self.branch(REGIMM, gpr(rs)?, rt, *target);
+            },
+            // slt[u] $at, rs, rt; bne/beq $at, $zero, target
+            (BLT | BGE | BLTU | BGEU, [rs, rt, Operand::Target(target)]) => {
+                let rs = gpr(rs)?;
+                let unsigned = matches!(kind, BLTU | BGEU);
+                match *rt {
+                    Operand::Integer(imm) if fits_signed(imm) =>
+                        self.immediate(if unsigned { 0x0b } else { 0x0a }, rs, AT, imm),
+                    _ => {
+                        let rt = self.register_or_at(rt)?;
+                        self.special(rs, rt, AT, 0, if unsigned { 0x2b } else { 0x2a });
+                    },
+                }
+                let op = if matches!(kind, BLT | BLTU) { 0x05 } else { 0x04 };
+                self.branch(op, AT, ZERO, *target);
+            },
+            // slt[u] $at, rt, rs; bne/beq $at, $zero, target
+            (BGT | BLE | BGTU | BLEU, [rs, rt, Operand::Target(target)]) => {
+                let rs = gpr(rs)?;
+                let rt = self.register_or_at(rt)?;
+                let slt = if matches!(kind, BGTU | BLEU) { 0x2b } else { 0x2a };
+                self.special(rt, rs, AT, 0, slt);
+                let op = if matches!(kind, BGT | BGTU) { 0x05 } else { 0x04 };
+                self.branch(op, AT, ZERO, *target);
+            },
+
+            // Jump
+            (J | JAL, [Operand::Target(target)]) => {
+                let op = if kind == J { 0x02 } else { 0x03 };
+                self.jump(op, *target);
+            },
+            (JR, [rs]) => {
+                self.special(gpr(rs)?, 0, 0, 0, 0x08);
+            },
+            (JALR, [rs]) => {
+                self.special(gpr(rs)?, 0, RA, 0, 0x09);
+            },
+            (JALR, [rd, rs]) => {
+                self.special(gpr(rs)?, 0, gpr(rd)?, 0, 0x09);
+            },
+
+            // Load, Store
+            (LA, [rd, Operand::Address(address)]) => {
+                self.immediate(0x0f, ZERO, AT, (address >> 16) as i32);  // lui $at, address[31:16]
+                self.immediate(0x0d, AT, gpr(rd)?, *address as i32);    // ori rd, $at, address[15:0]
+            },
+            (LA, [rd, Operand::Integer(address)]) |
+            (LA, [rd, Operand::Memory(0, address)]) => {
+                self.load_immediate(gpr(rd)?, *address);
+            },
+            (LA, [rd, Operand::Memory(r_idx, offset)]) => {
+                let (rd, base) = (gpr(rd)?, gpr(&Operand::Register(*r_idx))?);
+                if fits_signed(*offset) {
+                    self.immediate(0x09, base, rd, *offset);                   // addiu rd, base, offset
+                } else {
+                    self.immediate(0x0f, ZERO, AT, (*offset as u32 >> 16) as i32);  // lui $at, offset[31:16]
+                    self.immediate(0x0d, AT, AT, *offset);                     // ori $at, $at, offset[15:0]
+                    self.special(AT, base, rd, 0, 0x21);                       // addu rd, $at, base
+                }
+            },
+            (LB | LBU | LH | LHU | LW | SB | SH | SW, [rt, address]) => {
+                let (op, rt) = match (kind, fpr(rt)) {
+                    (LW, Ok(ft)) => (0x31, ft),  // lwc1
+                    (SW, Ok(ft)) => (0x39, ft),  // swc1
+                    (LB, _) => (0x20, gpr(rt)?),
+                    (LH, _) => (0x21, gpr(rt)?),
+                    (LW, _) => (0x23, gpr(rt)?),
+                    (LBU, _) => (0x24, gpr(rt)?),
+                    (LHU, _) => (0x25, gpr(rt)?),
+                    (SB, _) => (0x28, gpr(rt)?),
+                    (SH, _) => (0x29, gpr(rt)?),
+                    _ => (0x2b, gpr(rt)?),  // SW
+                };
+                let (base, offset) = self.memory(address)?;
+                self.immediate(op, base, rt, offset);
+            },
+
+            // Transfer
+            (MOVE, [rd, rs]) => {
+                match (gpr(rd), gpr(rs)) {
+                    (Ok(rd), Ok(rs)) => self.special(ZERO, rs, rd, 0, 0x21),  // addu rd, $zero, rs
+                    (Ok(rt), Err(_)) => self.cop1(0x00, rt, fpr(rs)?, 0, 0),   // mfc1 rt, fs
+                    (Err(_), Ok(rt)) => self.cop1(0x04, rt, fpr(rd)?, 0, 0),   // mtc1 rt, fd
+                    (Err(_), Err(_)) => self.cop1(FMT_S, 0, fpr(rs)?, fpr(rd)?, 0x06),  // mov.s
+                }
+            },
+            (MFHI | MFLO, [rd]) => {
+                let funct = if kind == MFHI { 0x10 } else { 0x12 };
+                self.special(0, 0, gpr(rd)?, 0, funct);
+            },
+            (MTHI | MTLO, [rs]) => {
+                let funct = if kind == MTHI { 0x11 } else { 0x13 };
+                self.special(gpr(rs)?, 0, 0, 0, funct);
+            },
+
+            // Exception, Interrupt
+            (SYSCALL, []) => self.special(0, 0, 0, 0, 0x0c),
+            (BREAK, []) => self.special(0, 0, 0, 0, 0x0d),
+            (BREAK, [Operand::Integer(code)]) => {
+                if !(0..=0xfffff).contains(code) {
+                    return Err(format!("break code out of range: {}", code));
+                }
+                self.words.push((*code as u32) << 6 | 0x0d);
+            },
+            (TEQ | TNE | TGE | TGEU | TLT | TLTU, [rs, rt]) => {
+                let rs = gpr(rs)?;
+                let rt = self.register_or_at(rt)?;
+                self.special(rs, rt, 0, 0, trap_funct(kind));
+            },
+            (TEQI | TNEI | TGEI | TGEIU | TLTI | TLTIU, [rs, Operand::Integer(imm)]) => {
+                let rs = gpr(rs)?;
+                if fits_signed(*imm) {
+                    let rt = match kind {
+                        TGEI  => 0x08,
+                        TGEIU => 0x09,
+                        TLTI  => 0x0a,
+                        TLTIU => 0x0b,
+                        TEQI  => 0x0c,
+                        _     => 0x0e,  // TNEI
+                    };
+                    self.immediate(REGIMM, rs, rt, *imm);
+                } else {
+                    // Same as the register form with $at
+                    self.load_immediate(AT, *imm);
+                    self.special(rs, AT, 0, 0, trap_funct(kind));
+                }
+            },
+            (ERET, []) => self.words.push(COP0 << 26 | 1 << 25 | 0x18),
+            (MFC0 | MTC0, [rt, rd]) => {
+                let rs = if kind == MFC0 { 0x00 } else { 0x04 };
+                self.words.push(r_type(COP0, rs, gpr(rt)?, gpr(rd)?, 0, 0));
+            },
+            (NOP, []) => self.words.push(0),
+
+            // My own
+            (PRTN, []) => self.special2(0, 0, 0, 0, UDI_PRTN),
+            (RST, []) => self.special2(0, 0, 0, 0, UDI_RST),
+            (PRTI | PRTH | PRTX | PRTC | PRTS, [operand]) => {
+                let funct = match kind {
+                    PRTI => UDI_PRTI,
+                    PRTH => UDI_PRTH,
+                    PRTX => UDI_PRTX,
+                    PRTC => UDI_PRTC,
+                    _    => UDI_PRTS,
+                };
+                let (rs, indirect) = match *operand {
+                    Operand::Register(_) | Operand::Integer(_) => match fpr(operand) {
+                        Ok(fs) => {
+                            self.cop1(0x00, AT, fs, 0, 0);  // mfc1 $at, fs
+                            (AT, 0)
+                        },
+                        Err(_) => (self.register_or_at(operand)?, 0),
+                    },
+                    _ => {
+                        let (base, offset) = self.memory(operand)?;
+                        self.immediate(0x09, base, AT, offset);  // addiu $at, base, offset
+                        (AT, 1)
+                    },
+                };
+                self.special2(rs, 0, 0, indirect, funct);
+            },
+
+            // FPU Instructions
+            (MTC1, [rt, fs]) => {
+                self.cop1(0x04, gpr(rt)?, fpr(fs)?, 0, 0);
+            },
+            (CFC1 | CTC1, [rt, fs]) => {
+                let rs = if kind == CFC1 { 0x02 } else { 0x06 };
+                self.cop1(rs, gpr(rt)?, gpr(fs)?, 0, 0);
+            },
+            (ADD_S | SUB_S | MUL_S | DIV_S, [fd, fs, ft]) => {
+                let funct = match kind {
+                    ADD_S => 0x00,
+                    SUB_S => 0x01,
+                    MUL_S => 0x02,
+                    _     => 0x03,  // DIV_S
+                };
+                self.cop1(FMT_S, fpr(ft)?, fpr(fs)?, fpr(fd)?, funct);
+            },
+            (SQRT_S | ABS_S | NEG_S | RECIP_S | RSQRT_S, [fd, fs]) => {
+                let funct = match kind {
+                    SQRT_S  => 0x04,
+                    ABS_S   => 0x05,
+                    NEG_S   => 0x07,
+                    RECIP_S => 0x15,
+                    _       => 0x16,  // RSQRT_S
+                };
+                self.cop1(FMT_S, 0, fpr(fs)?, fpr(fd)?, funct);
+            },
+            (MADD_S, [fd, fr, fs, ft]) => {
+                self.words.push(r_type(COP1X, fpr(fr)?, fpr(ft)?, fpr(fs)?, fpr(fd)?, 0x20));
+            },
+            (C_EQ_S | C_LT_S | C_LE_S | C_EQ_D | C_LT_D | C_LE_D, _) => {
+                let funct = match kind {
+                    C_EQ_S | C_EQ_D => 0x32,
+                    C_LT_S | C_LT_D => 0x3c,
+                    _               => 0x3e,  // C_LE_S, C_LE_D
+                };
+                let (cc, (fs, ft)) = match condition_code(operands) {
+                    (cc, [fs, ft]) => (cc as u32, (fs, ft)),
+                    _ => return Err(format!("invalid operands: {:?}", operands)),
+                };
+                match kind {
+                    C_EQ_S | C_LT_S | C_LE_S => self.cop1(FMT_S, fpr(ft)?, fpr(fs)?, cc << 2, funct),
+                    _                        => self.cop1(FMT_D, dpr(ft)?, dpr(fs)?, cc << 2, funct),
+                }
+            },
+            (BC1F | BC1T, _) => {
+                let tf = if kind == BC1T { 1 } else { 0 };
+                match condition_code(operands) {
+                    (cc, [Operand::Target(target)]) => self.branch(COP1, 0x08, (cc as u32) << 2 | tf, *target),
+                    _ => return Err(format!("invalid operands: {:?}", operands)),
+                }
+            },
+            (MOVF | MOVT, [rd, rs, ..]) => {
+                let tf = if kind == MOVT { 1 } else { 0 };
+                let cc = match operands[2..] {
+                    [] => 0,
+                    [Operand::Integer(cc @ 0..=7)] => cc as u32,
+                    _ => return Err(format!("invalid operands: {:?}", operands)),
+                };
+                self.special(gpr(rs)?, cc << 2 | tf, gpr(rd)?, 0, 0x01);
+            },
+            (MOVN_S | MOVZ_S, [fd, fs, rt]) => {
+                let funct = if kind == MOVZ_S { 0x12 } else { 0x13 };
+                self.cop1(FMT_S, gpr(rt)?, fpr(fs)?, fpr(fd)?, funct);
+            },
+            (CVT_S_W, [fd, fs]) => {
+                self.cop1(FMT_W, 0, fpr(fs)?, fpr(fd)?, 0x20);
+            },
+            (CVT_W_S | ROUND_W_S | TRUNC_W_S | CEIL_W_S | FLOOR_W_S, [fd, fs]) => {
+                let funct = match kind {
+                    ROUND_W_S => 0x0c,
+                    TRUNC_W_S => 0x0d,
+                    CEIL_W_S  => 0x0e,
+                    FLOOR_W_S => 0x0f,
+                    _         => 0x24,  // CVT_W_S
+                };
+                self.cop1(FMT_S, 0, fpr(fs)?, fpr(fd)?, funct);
+            },
+
+            // Double
+            (LDC1 | SDC1, [ft, address]) => {
+                let op = if kind == LDC1 { 0x35 } else { 0x3d };
+                let ft = dpr(ft)?;
+                let (base, offset) = self.memory(address)?;
+                self.immediate(op, base, ft, offset);
+            },
+            (ADD_D | SUB_D | MUL_D | DIV_D, [fd, fs, ft]) => {
+                let funct = match kind {
+                    ADD_D => 0x00,
+                    SUB_D => 0x01,
+                    MUL_D => 0x02,
+                    _     => 0x03,  // DIV_D
+                };
+                self.cop1(FMT_D, dpr(ft)?, dpr(fs)?, dpr(fd)?, funct);
+            },
+            (SQRT_D | ABS_D | MOV_D | NEG_D, [fd, fs]) => {
+                let funct = match kind {
+                    SQRT_D => 0x04,
+                    ABS_D  => 0x05,
+                    MOV_D  => 0x06,
+                    _      => 0x07,  // NEG_D
+                };
+                self.cop1(FMT_D, 0, dpr(fs)?, dpr(fd)?, funct);
+            },
+            (CVT_D_S, [fd, fs]) => self.cop1(FMT_S, 0, fpr(fs)?, dpr(fd)?, 0x21),
+            (CVT_D_W, [fd, fs]) => self.cop1(FMT_W, 0, fpr(fs)?, dpr(fd)?, 0x21),
+            (CVT_S_D, [fd, fs]) => self.cop1(FMT_D, 0, dpr(fs)?, fpr(fd)?, 0x20),
+            (CVT_W_D, [fd, fs]) => self.cop1(FMT_D, 0, dpr(fs)?, fpr(fd)?, 0x24),
+
+            _ => return Err(format!("invalid operands: {:?}", operands)),
+        }
+
+        Ok(())
+    }
+
+    /// li into a general purpose or floating point register
+    fn load_constant(&mut self, rd: &Operand, imm: i32) -> Result<()> {
+        match fpr(rd) {
+            Ok(fd) => {
+                self.load_immediate(AT, imm);
+                self.cop1(0x04, AT, fd, 0, 0);  // mtc1 $at, fd
+            },
+            Err(_) => self.load_immediate(gpr(rd)?, imm),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,494 @@
+pub mod decode;
+pub mod encode;
+pub mod symbol;
+mod test;
+use super::token::*;
+use super::token::memory::*;
+use super::token::register::RegisterKind;
+use symbol::{Segment, Symbol, SymbolTable};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Decoded operand
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operand {
+    Register(usize),     // Register index
+    Integer(i32),        // Immediate
+    Floating(f32),       // Floating point immediate
+    Memory(usize, i32),  // (Register index, Offset)  e.g. 4($sp), label($t0)
+    Address(u32),        // Address of label or string literal  e.g. la $a0, label
+    Target(u32),         // Address of branch/jump target
+}
+
+/// Decoded instruction
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instruction {
+    pub kind: InstructionKind,
+    pub operands: Vec<Operand>,
+    pub address: u32,         // Address in .text (or .ktext) segment
+    pub size: u32,            // Size of the machine code in bytes
+    pub line: u32,            // Number of lines
+    pub filename_idx: usize,  // File name index
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let register = |idx: usize| match RegisterKind::from_index(idx) {
+            Some(kind) => format!("${:?}", kind),
+            None => format!("${}", idx),
+        };
+        match *self {
+            Operand::Register(idx)        => write!(f, "{}", register(idx)),
+            Operand::Integer(i)           => write!(f, "{}", i),
+            Operand::Floating(x)          => write!(f, "{}", x),
+            Operand::Memory(idx, offset)  => write!(f, "{}({})", offset, register(idx)),
+            Operand::Address(address) |
+            Operand::Target(address)      => write!(f, "0x{:08x}", address),
+        }
+    }
+}
+
+/// e.g. `add.s $f0, $f1, $f2`
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mnemonic = format!("{:?}", self.kind).to_lowercase().replace('_', ".");
+        let operands: Vec<String> = self.operands.iter().enumerate().map(|(i, o)| match (self.kind, o) {
+            // Coprocessor 0 register, FPU control register
+            (InstructionKind::MFC0 | InstructionKind::MTC0 |
+             InstructionKind::CFC1 | InstructionKind::CTC1, Operand::Register(idx)) if i == 1 => format!("${}", idx),
+            _ => o.to_string(),
+        }).collect();
+        if operands.is_empty() {
+            write!(f, "{}", mnemonic)
+        } else {
+            write!(f, "{:<8}{}", mnemonic, operands.join(", "))
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Program {
+    pub instructions: Vec<Instruction>,  // In the order of the source
+    addresses: BTreeMap<u32, usize>,     // Address => index of `instructions`
+    symbols: SymbolTable,  // Labels
+    cursor: usize,         // Tokens before this index are already assembled
+    text_end: u32,         // Address of the next instruction in .text
+    ktext_end: u32,        // Address of the next instruction in .ktext
+}
+
+/// Lengths to roll back to (Use only in REPL)
+#[derive(Clone, Copy, Debug)]
+pub struct Checkpoint {
+    cursor: usize,
+    instructions: usize,
+    symbols: usize,
+    text_end: u32,
+    ktext_end: u32,
+}
+
+impl Default for Program {
+    fn default() -> Self {
+        Program {
+            instructions: Vec::new(),
+            addresses:    BTreeMap::new(),
+            symbols:      SymbolTable::default(),
+            cursor:       0,
+            text_end:     TEXT_SEGMENT,
+            ktext_end:    KTEXT_SEGMENT,
+        }
+    }
+}
+
+impl Program {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+
+    /// Tokens before this index are already assembled
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            cursor:       self.cursor,
+            instructions: self.instructions.len(),
+            symbols:      self.symbols.len(),
+            text_end:     self.text_end,
+            ktext_end:    self.ktext_end,
+        }
+    }
+
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        self.cursor = checkpoint.cursor.min(self.cursor);
+        self.instructions.truncate(checkpoint.instructions);
+        self.addresses.retain(|_, idx| *idx < checkpoint.instructions);
+        self.symbols.truncate(checkpoint.symbols);
+        self.text_end = checkpoint.text_end.min(self.text_end);
+        self.ktext_end = checkpoint.ktext_end.min(self.ktext_end);
+    }
+
+    /// Address next to the last instruction of .text
+    pub fn end_address(&self) -> u32 {
+        self.text_end
+    }
+
+    /// Address of the next instruction in .text or .ktext
+    fn next_address(&self, kernel: bool) -> u32 {
+        if kernel { self.ktext_end } else { self.text_end }
+    }
+
+    fn push(&mut self, instruction: Instruction, kernel: bool) {
+        let end = instruction.address + instruction.size;
+        if kernel { self.ktext_end = end } else { self.text_end = end }
+        self.addresses.insert(instruction.address, self.instructions.len());
+        self.instructions.push(instruction);
+    }
+
+    /// Index of the instruction at `address`
+    pub fn index_of(&self, address: u32) -> Option<usize> {
+        self.addresses.get(&address).copied()
+    }
+
+    /// Instruction whose machine code includes `address`
+    pub fn find(&self, address: u32) -> Option<&Instruction> {
+        let (_, &idx) = self.addresses.range(..=address).next_back()?;
+        Some(&self.instructions[idx]).filter(|i| address < i.address + i.size)
+    }
+
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    pub fn symbols_mut(&mut self) -> &mut SymbolTable {
+        &mut self.symbols
+    }
+
+    fn text_label(&self, name: &str) -> Option<u32> {
+        match self.symbols.get(name) {
+            Some(Symbol { segment: Segment::Text, address, .. }) => Some(*address),
+            _ => None,
+        }
+    }
+
+    fn data_label(&self, name: &str) -> Option<u32> {
+        match self.symbols.get(name) {
+            Some(Symbol { segment: Segment::Data, address, .. }) => Some(*address),
+            _ => None,
+        }
+    }
+}
+
+/// Lower the tokens after `program.cursor()` into `program.instructions`.
+/// Data of the .data segment is pushed to `memory`.
+pub fn assemble(tokens: &mut Tokens, program: &mut Program, memory: &mut Memory) -> Result<(), String> {
+    let (data_area_now, kernel_area_now) = (tokens.data_area_now, tokens.kernel_area_now);
+
+    // 1st pass: labels and data
+    data_analysis(tokens, program, memory)?;
+
+    // 2nd pass: instructions
+    tokens.data_area_now = data_area_now;
+    tokens.kernel_area_now = kernel_area_now;
+    let mut idx = program.cursor;
+    while idx < tokens.len() {
+        let eol = end_of_line(tokens, idx);
+
+        let mut i = idx;
+        while i < eol {
+            match &tokens.token[i].kind {
+                TokenKind::INDICATE(kind @ (IndicateKind::text | IndicateKind::data |
+                                            IndicateKind::ktext(_) | IndicateKind::kdata(_))) => {
+                    let kind = kind.clone();
+                    if let IndicateKind::ktext(Some(address)) = kind {
+                        program.ktext_end = address;
+                    }
+                    switch_area(tokens, kind);
+                },
+                TokenKind::LABEL(_, _, _) | TokenKind::INDICATE(_) => (),
+                TokenKind::INSTRUCTION(kind) => {
+                    // Ignore except .text and .ktext segment
+                    if !tokens.data_area_now {
+                        let kernel = tokens.kernel_area_now;
+                        let address = program.next_address(kernel);
+                        let instruction = lower(tokens, program, Some(memory), *kind, address, i+1, eol)?;
+                        write_text(memory, &instruction);
+                        program.push(instruction, kernel);
+                    }
+                    break;
+                },
+                _ => {
+                    let t = &tokens.token[i];
+                    return Err(format!("{}:{}: expect instruction. but got: {:?}",
+                        tokens.filename_of(t.filename_idx), t.line, t.kind));
+                },
+            }
+            i += 1;
+        }
+
+        idx = eol + 1;
+    }
+
+    program.cursor = tokens.len();
+    Ok(())
+}
+
+/// Enter the segment of `.text`, `.data`, `.ktext` or `.kdata`
+fn switch_area(tokens: &mut Tokens, kind: IndicateKind) {
+    tokens.data_area_now = matches!(kind, IndicateKind::data | IndicateKind::kdata(_));
+    tokens.kernel_area_now = matches!(kind, IndicateKind::ktext(_) | IndicateKind::kdata(_));
+}
+
+/// Put the machine code of `instruction` into .text (or .ktext) segment.
+/// Left zero (nop) if it cannot be encoded.
+fn write_text(memory: &mut Memory, instruction: &Instruction) {
+    let endian = memory.endian;
+    let bytes: Vec<u8> = match encode::encode(instruction) {
+        Ok(words) => words.iter().flat_map(|w| endian.u32_bytes(*w)).collect(),
+        Err(_) => vec![0; instruction.size as usize],
+    };
+    memory.map(instruction.address, &bytes).expect(".text is mapped");
+}
+
+/// Index of EOL of the line which includes `idx`
+fn end_of_line(tokens: &Tokens, idx: usize) -> usize {
+    (idx..tokens.len())
+        .find(|&i| tokens.token[i].kind == TokenKind::EOL)
+        .unwrap_or(tokens.len())
+}
+
+/// Instructions whose label operand is a branch/jump target
+fn takes_target(kind: InstructionKind) -> bool {
+    use InstructionKind::*;
+    matches!(kind,
+        B | BEQ | BNE | BGE | BGT | BLE | BLT | BGEU | BGTU | BLEU | BLTU |
+        BEQZ | BGEZ | BGTZ | BLEZ | BLTZ | BNEZ | BGEZAL | BLTZAL |
+        J | JAL | BC1T | BC1F)
+}
+
+/// Instructions whose immediate is zero-extended
+fn zero_extends(kind: InstructionKind) -> bool {
+    use InstructionKind::*;
+    matches!(kind, AND | ANDI | OR | ORI | XOR | XORI)
+}
+
+/// Lower operands tokens[start..eol] of `kind`.
+/// Without `memory` (1st pass), labels and literals are placeholders of the same size.
+fn lower(tokens: &Tokens, program: &Program, mut memory: Option<&mut Memory>,
+    kind: InstructionKind, address: u32, start: usize, eol: usize) -> Result<Instruction, String>
+{
+    let t = &tokens.token[start-1];
+    let (line, filename_idx) = (t.line, t.filename_idx);
+    let undefined = |label: &str| {
+        format!("{}:{}: undefined label: {}", tokens.filename_of(filename_idx), line, label)
+    };
+
+    let mut operands = Vec::new();
+    for t in &tokens.token[start..eol] {
+        let operand = match &t.kind {
+            TokenKind::REGISTER(_, i)   => Operand::Register(*i),
+            TokenKind::INTEGER(i)       => Operand::Integer(*i),
+            TokenKind::FLOATING(f)      => Operand::Floating(*f),
+            TokenKind::MEMORY(_, i, a)  => Operand::Memory(*i, *a),
+            // String literal is put into .data segment
+            TokenKind::LITERAL(s) => match memory.as_mut() {
+                Some(memory) => {
+                    let mut bytes = s.clone().into_bytes();
+                    bytes.push(0);
+                    Operand::Address(memory.push_data(false, &bytes)
+                        .map_err(|e| format!("{}:{}: {}", tokens.filename_of(filename_idx), line, e))?)
+                },
+                None => Operand::Address(0),
+            },
+            TokenKind::DATA(_, i, _, _) if memory.is_none() => Operand::Memory(*i, STATIC_DATA as i32),
+            TokenKind::ADDRESS(_) if memory.is_none() => {
+                if takes_target(kind) { Operand::Target(address) } else { Operand::Address(0) }
+            },
+            TokenKind::DATA(_, i, s, offset) => {
+                let address = program.data_label(s).ok_or_else(|| undefined(s))?;
+                Operand::Memory(*i, (address as i32).wrapping_add(*offset))
+            },
+            // %hi(label) or %lo(label) fills the 16-bit immediate field (zero-extended by logical instructions),
+            // so the size does not depend on the address (a placeholder in the 1st pass)
+            TokenKind::RELOCATION(r, s, offset, i) => {
+                let address = match memory {
+                    None => 0,
+                    Some(_) => program.data_label(s).or_else(|| program.text_label(s)).ok_or_else(|| undefined(s))?,
+                };
+                let field = r.apply(address.wrapping_add(*offset as u32));
+                match i {
+                    Some(i) => Operand::Memory(*i, field),
+                    None if zero_extends(kind) => Operand::Integer(field as u16 as i32),
+                    None => Operand::Integer(field as i16 as i32),
+                }
+            },
+            TokenKind::ADDRESS(s) => {
+                if takes_target(kind) {
+                    Operand::Target(program.text_label(s).ok_or_else(|| undefined(s))?)
+                } else if let Some(address) = program.data_label(s) {
+                    Operand::Address(address)
+                } else {
+                    Operand::Address(program.text_label(s).ok_or_else(|| undefined(s))?)
+                }
+            },
+            _ => return Err(format!("{}:{}: invalid operand: {:?}",
+                    tokens.filename_of(filename_idx), line, t.kind)),
+        };
+        operands.push(operand);
+    }
+
+    // Coprocessor 0 has 32 registers
+    if let (InstructionKind::MFC0 | InstructionKind::MTC0, [_, rd]) = (kind, &operands[..]) {
+        if !matches!(rd, Operand::Register(0..=31)) {
+            return Err(format!("{}:{}: expect coprocessor 0 register. but got: {:?}",
+                tokens.filename_of(filename_idx), line, rd));
+        }
+    }
+
+    let mut instruction = Instruction { kind, operands, address, size: 0, line, filename_idx };
+    instruction.size = encode::size(&instruction);
+    Ok(instruction)
+}
+
+/// Labels and data of the tokens after `program.cursor()`
+fn data_analysis(tokens: &mut Tokens, program: &mut Program, memory: &mut Memory) -> Result<(), String> {
+    let mut text_address = program.next_address(false);
+    let mut ktext_address = program.next_address(true);
+    let mut idx = program.cursor;
+    let endian = memory.endian;
+
+    while idx < tokens.len() {
+        let eol = end_of_line(tokens, idx);
+        let has_data = tokens.token[idx..eol].iter().any(|t| is_data(&t.kind));
+        let kernel = tokens.kernel_area_now;
+
+        // Labels of the line point to the aligned data
+        if let Some(t) = tokens.token[idx..eol].iter().find(|t| is_data(&t.kind)) {
+            if let Some(n) = alignment_of(&t.kind) {
+                align_data(memory, kernel, n)
+                    .map_err(|e| format!("{}:{}: {}", tokens.filename_of(t.filename_idx), t.line, e))?;
+            }
+        }
+
+        for i in idx..eol {
+            let (line, filename_idx) = (tokens.token[i].line, tokens.token[i].filename_idx);
+            let kernel = tokens.kernel_area_now;
+            let address = if kernel { &mut ktext_address } else { &mut text_address };
+            let pushed = match &mut tokens.token[i].kind {
+                TokenKind::INDICATE(kind @ (IndicateKind::text | IndicateKind::data)) => {
+                    let kind = kind.clone();
+                    switch_area(tokens, kind);
+                    Ok(())
+                },
+                TokenKind::INDICATE(IndicateKind::ktext(start)) => {
+                    if let Some(start) = *start {
+                        if start < ktext_address || KDATA_SEGMENT <= start || start % 4 != 0 {
+                            return Err(format!("{}:{}: invalid .ktext address: 0x{:08x}",
+                                tokens.filename_of(filename_idx), line, start));
+                        }
+                        ktext_address = start;
+                    }
+                    switch_area(tokens, IndicateKind::ktext(None));
+                    Ok(())
+                },
+                TokenKind::INDICATE(IndicateKind::kdata(start)) => {
+                    if let Some(start) = *start {
+                        if start < memory.data_end(true) {
+                            return Err(format!("{}:{}: invalid .kdata address: 0x{:08x}",
+                                tokens.filename_of(filename_idx), line, start));
+                        }
+                        memory.reserve_data(true, start - memory.data_end(true))
+                            .map_err(|e| format!("{}:{}: {}", tokens.filename_of(filename_idx), line, e))?;
+                    }
+                    switch_area(tokens, IndicateKind::kdata(None));
+                    Ok(())
+                },
+                TokenKind::LABEL(name, _, index) => {
+                    let name = name.clone();
+                    let (segment, address) = if tokens.data_area_now || has_data {
+                        *index = Some(memory.data_end(kernel) as usize);
+                        (Segment::Data, memory.data_end(kernel))
+                    } else {
+                        (Segment::Text, *address)
+                    };
+                    let symbol = Symbol { segment, address, filename_idx, line };
+                    if let Err(first) = program.symbols.insert(&name, symbol) {
+                        return Err(format!("{}:{}: duplicate label: {} (first defined at {}:{})",
+                            tokens.filename_of(filename_idx), line, name,
+                            tokens.filename_of(first.filename_idx), first.line));
+                    }
+                    Ok(())
+                },
+                TokenKind::INSTRUCTION(kind) => {
+                    // Labels after a pseudo instruction depend on its size
+                    if !tokens.data_area_now {
+                        let kind = *kind;
+                        *address += lower(tokens, program, None, kind, *address, i+1, eol)
+                            .map_or(4, |instruction| instruction.size);
+                    }
+                    break;
+                },
+                // Align 2^n
+                TokenKind::INDICATE(IndicateKind::align(n)) => align_data(memory, kernel, 2u32.pow(*n as u32)),
+                TokenKind::INDICATE(IndicateKind::space(s))  => memory.reserve_data(kernel, *s).map(drop),
+                TokenKind::INDICATE(IndicateKind::ascii(s))  => push_data(memory, kernel, 1, s.as_bytes()),
+                TokenKind::INDICATE(IndicateKind::asciiz(s)) => push_data(memory, kernel, 1, &[s.as_bytes(), &[0]].concat()),
+                TokenKind::INDICATE(IndicateKind::word(w))   => push_data(memory, kernel, 4, &endian.u32_bytes(*w)),
+                TokenKind::INDICATE(IndicateKind::half(h))   => push_data(memory, kernel, 2, &endian.u16_bytes(*h)),
+                TokenKind::INDICATE(IndicateKind::byte(b))   => push_data(memory, kernel, 1, &[*b]),
+                TokenKind::INDICATE(IndicateKind::float(f))  => push_data(memory, kernel, 4, &endian.u32_bytes(f.to_bits())),
+                TokenKind::INDICATE(IndicateKind::double(d)) => push_data(memory, kernel, 8, &endian.u64_bytes(d.to_bits())),
+                _ => Ok(()),
+            };
+            pushed.map_err(|e| format!("{}:{}: {}", tokens.filename_of(filename_idx), line, e))?;
+        }
+
+        idx = eol + 1;
+    }
+
+    Ok(())
+}
+
+/// Pad .data (or .kdata) segment to a multiple of `n` bytes
+fn align_data(memory: &mut Memory, kernel: bool, n: u32) -> Result<(), String> {
+    let padding = (n - memory.data_end(kernel) % n) % n;
+    memory.reserve_data(kernel, padding).map(drop)
+}
+
+/// Append `bytes` aligned to `n` bytes to .data (or .kdata) segment
+fn push_data(memory: &mut Memory, kernel: bool, n: u32, bytes: &[u8]) -> Result<(), String> {
+    align_data(memory, kernel, n)?;
+    memory.push_data(kernel, bytes).map(drop)
+}
+
+/// Natural alignment of the data directive (like MARS)
+fn alignment_of(kind: &TokenKind) -> Option<u32> {
+    match kind {
+        TokenKind::INDICATE(IndicateKind::double(_)) => Some(8),
+        TokenKind::INDICATE(IndicateKind::word(_))   |
+        TokenKind::INDICATE(IndicateKind::float(_))  => Some(4),
+        TokenKind::INDICATE(IndicateKind::half(_))   => Some(2),
+        _ => None,
+    }
+}
+
+/// Directives which put data into .data (or .kdata) segment
+fn is_data(kind: &TokenKind) -> bool {
+    matches!(kind,
+        TokenKind::INDICATE(IndicateKind::word(_))   |
+        TokenKind::INDICATE(IndicateKind::half(_))   |
+        TokenKind::INDICATE(IndicateKind::byte(_))   |
+        TokenKind::INDICATE(IndicateKind::float(_))  |
+        TokenKind::INDICATE(IndicateKind::double(_)) |
+        TokenKind::INDICATE(IndicateKind::space(_))  |
+        TokenKind::INDICATE(IndicateKind::ascii(_))  |
+        TokenKind::INDICATE(IndicateKind::asciiz(_)))
+}
@@ -0,0 +1,80 @@
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Segment {
+    Text,  // .text
+    Data,  // .data
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+    pub segment: Segment,     // Segment of the label
+    pub address: u32,         // Address of the label
+    pub filename_idx: usize,  // File name index of the definition
+    pub line: u32,            // Number of lines of the definition
+}
+
+/// Labels of all files (Everyone global)
+#[derive(Clone, Debug, Default)]
+pub struct SymbolTable {
+    symbols: HashMap<String, Symbol>,
+    names: Vec<String>,  // Definition order (for rollback)
+    texts: BTreeMap<u32, Vec<usize>>,  // Address of .text labels => indices of `names`
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Return: Err(first definition) if `name` is already defined
+    pub fn insert(&mut self, name: &str, symbol: Symbol) -> Result<(), &Symbol> {
+        if self.symbols.contains_key(name) {
+            return Err(&self.symbols[name]);
+        }
+        if symbol.segment == Segment::Text {
+            self.texts.entry(symbol.address).or_default().push(self.names.len());
+        }
+        self.symbols.insert(name.to_string(), symbol);
+        self.names.push(name.to_string());
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.get(name)
+    }
+
+    /// Nearest .text label at or before `address` and the offset from it.
+    /// The last defined one if some labels have the same address.
+    pub fn label_of(&self, address: u32) -> Option<(&str, &Symbol, u32)> {
+        let (start, indices) = self.texts.range(..=address).next_back()?;
+        let name = &self.names[*indices.last()?];
+        Some((name, &self.symbols[name], address - start))
+    }
+
+    /// Remove labels defined after the first `len` labels
+    pub fn truncate(&mut self, len: usize) {
+        for name in self.names.drain(len.min(self.names.len())..) {
+            if let Some(Symbol { segment: Segment::Text, address, .. }) = self.symbols.remove(&name) {
+                let indices = self.texts.get_mut(&address).expect("inserted with the label");
+                indices.pop();
+                if indices.is_empty() {
+                    self.texts.remove(&address);
+                }
+            }
+        }
+    }
+
+    /// Iterate in definition order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Symbol)> {
+        self.names.iter().map(move |name| (name.as_str(), &self.symbols[name]))
+    }
+}
@@ -0,0 +1,206 @@
+#[test]
+#[cfg(test)]
+fn test_assemble() {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::token::memory::Memory;
+
+    let input = "\
+.data
+str:    .asciiz \"abc\"
+num:    .word 42
+
+.text
+main:
+    li      $t0, 1
+    la      $a0, str
+loop:
+    addi    $t0, $t0, 1
+    lw      $t1, num($t0)
+    sw      $t1, 4($sp)
+    blt     $t0, 10, loop
+    jal     func
+func:
+    jr      $ra
+";
+
+    let mut tokens = Tokens::new();
+    for (i, line) in input.lines().enumerate() {
+        tokenize(i as u32 + 1, 0, line, &mut tokens).unwrap();
+    }
+    let mut program = Program::new();
+    let mut memory = Memory::default();
+    assemble(&mut tokens, &mut program, &mut memory).unwrap();
+
+    assert_eq!(memory.data_end(false), STATIC_DATA + 8);
+    assert_eq!(memory.load_string(STATIC_DATA), Ok("abc".to_string()));
+    assert_eq!(memory.load_u32(STATIC_DATA + 4), Ok(42));
+    assert_eq!(program.cursor(), tokens.len());
+
+    let operands: Vec<(InstructionKind, Vec<Operand>)> = program.instructions
+        .iter()
+        .map(|i| (i.kind, i.operands.clone()))
+        .collect();
+    assert_eq!(operands, vec![
+        (InstructionKind::LI,   vec![Operand::Register(8), Operand::Integer(1)]),
+        (InstructionKind::LA,   vec![Operand::Register(4), Operand::Address(STATIC_DATA)]),
+        (InstructionKind::ADDI, vec![Operand::Register(8), Operand::Register(8), Operand::Integer(1)]),
+        (InstructionKind::LW,   vec![Operand::Register(9), Operand::Memory(8, (STATIC_DATA + 4) as i32)]),
+        (InstructionKind::SW,   vec![Operand::Register(9), Operand::Memory(29, 4)]),
+        (InstructionKind::BLT,  vec![Operand::Register(8), Operand::Integer(10), Operand::Target(TEXT_SEGMENT + 12)]),
+        (InstructionKind::JAL,  vec![Operand::Target(TEXT_SEGMENT + 44)]),
+        (InstructionKind::JR,   vec![Operand::Register(31)]),
+    ]);
+    assert_eq!(program.instructions[0].line, 7);
+    // la, lw label($t0) and blt are expanded
+    assert_eq!(program.instructions[7].address, TEXT_SEGMENT + 44);
+    assert_eq!(program.index_of(TEXT_SEGMENT + 44), Some(7));
+}
+
+#[test]
+#[cfg(test)]
+fn test_assemble_undefined_label() {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::token::memory::Memory;
+
+    let mut tokens = Tokens::new();
+    tokenize(1, 0, "j nowhere", &mut tokens).unwrap();
+    let mut program = Program::new();
+    let mut memory = Memory::default();
+
+    assert!(assemble(&mut tokens, &mut program, &mut memory).is_err());
+}
+
+#[test]
+#[cfg(test)]
+fn test_assemble_duplicate_label() {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::token::memory::Memory;
+
+    let mut tokens = Tokens::new();
+    for (i, line) in ["foo: li $t0, 1", "foo: li $t0, 2"].iter().enumerate() {
+        tokenize(i as u32 + 1, 0, line, &mut tokens).unwrap();
+    }
+    let mut program = Program::new();
+    let mut memory = Memory::default();
+
+    let err = assemble(&mut tokens, &mut program, &mut memory).unwrap_err();
+    assert!(err.contains("duplicate label: foo"));
+    assert_eq!(program.symbols().get("foo").unwrap().line, 1);
+}
+
+#[test]
+#[cfg(test)]
+fn test_assemble_cp0_register() {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::token::memory::Memory;
+
+    for line in ["mfc0 $t0, $f5", "mtc0 $t0, $fcsr"] {
+        let mut tokens = Tokens::new();
+        tokenize(1, 0, line, &mut tokens).unwrap();
+        let mut program = Program::new();
+        let mut memory = Memory::default();
+
+        let err = assemble(&mut tokens, &mut program, &mut memory).unwrap_err();
+        assert!(err.contains("expect coprocessor 0 register"), "{}", err);
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_encode() {
+    use super::*;
+    use crate::lexer::tokenize;
+    use crate::token::memory::Memory;
+
+    let input = "\
+main:
+    add     $t0, $t1, $t2
+    addi    $t0, $t0, -1
+    li      $t0, 0x12345678
+    lw      $t1, 4($sp)
+loop:
+    blt     $t0, $t1, loop
+    jal     main
+    jr      $ra
+    syscall
+    teq     $t0, $t1
+    tgei    $t0, -1
+";
+
+    let mut tokens = Tokens::new();
+    for (i, line) in input.lines().enumerate() {
+        tokenize(i as u32 + 1, 0, line, &mut tokens).unwrap();
+    }
+    let mut program = Program::new();
+    let mut memory = Memory::default();
+    assemble(&mut tokens, &mut program, &mut memory).unwrap();
+
+    let words: Vec<u32> = program.instructions
+        .iter()
+        .flat_map(|i| encode::encode(i).unwrap())
+        .collect();
+    assert_eq!(words, vec![
+        0x012a4020,  // add   $t0, $t1, $t2
+        0x2108ffff,  // addi  $t0, $t0, -1
+        0x3c011234,  // lui   $at, 0x1234
+        0x34285678,  // ori   $t0, $at, 0x5678
+        0x8fa90004,  // lw    $t1, 4($sp)
+        0x0109082a,  // slt   $at, $t0, $t1
+        0x1420fffe,  // bne   $at, $zero, loop
+        0x0c100000,  // jal   main
+        0x03e00008,  // jr    $ra
+        0x0000000c,  // syscall
+        0x01090034,  // teq   $t0, $t1
+        0x0508ffff,  // tgei  $t0, -1
+    ]);
+}
+
+#[test]
+#[cfg(test)]
+fn test_decode() {
+    use super::*;
+    use super::decode::decode;
+
+    let add = decode(0x012a4020, TEXT_SEGMENT).unwrap();
+    assert_eq!(add.kind, InstructionKind::ADD);
+    assert_eq!(add.operands, vec![Operand::Register(8), Operand::Register(9), Operand::Register(10)]);
+    assert_eq!(add.to_string(), "add     $t0, $t1, $t2");
+
+    let bne = decode(0x1420fffe, TEXT_SEGMENT + 24).unwrap();
+    assert_eq!(bne.operands, vec![Operand::Register(1), Operand::Register(0), Operand::Target(TEXT_SEGMENT + 20)]);
+
+    let lw = decode(0x8fa9fffc, TEXT_SEGMENT).unwrap();
+    assert_eq!(lw.to_string(), "lw      $t1, -4($sp)");
+
+    assert!(decode(0xfc000000, TEXT_SEGMENT).is_err());
+}
+
+#[test]
+#[cfg(test)]
+fn test_symbol_table() {
+    use super::symbol::*;
+    use crate::token::memory::TEXT_SEGMENT;
+
+    let symbol = |segment, address| Symbol { segment, address, filename_idx: 0, line: 0 };
+    let mut symbols = SymbolTable::new();
+    symbols.insert("main", symbol(Segment::Text, TEXT_SEGMENT)).unwrap();
+    symbols.insert("data", symbol(Segment::Data, TEXT_SEGMENT + 4)).unwrap();
+    symbols.insert("loop", symbol(Segment::Text, TEXT_SEGMENT + 8)).unwrap();
+    symbols.insert("again", symbol(Segment::Text, TEXT_SEGMENT + 8)).unwrap();
+
+    let label_of = |symbols: &SymbolTable, address| {
+        symbols.label_of(address).map(|(name, _, offset)| (name.to_string(), offset))
+    };
+    assert_eq!(label_of(&symbols, TEXT_SEGMENT - 4), None);
+    assert_eq!(label_of(&symbols, TEXT_SEGMENT + 4), Some(("main".to_string(), 4)));
+    assert_eq!(label_of(&symbols, TEXT_SEGMENT + 12), Some(("again".to_string(), 4)));
+
+    symbols.truncate(3);
+    assert_eq!(label_of(&symbols, TEXT_SEGMENT + 12), Some(("loop".to_string(), 4)));
+    symbols.truncate(1);
+    assert_eq!(label_of(&symbols, TEXT_SEGMENT + 12), Some(("main".to_string(), 12)));
+}
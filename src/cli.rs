@@ -1,37 +1,181 @@
-use std::io::{BufRead, BufReader};
-use std::fs::File;
-use std::path::PathBuf;
-
-use super::token::Tokens;
-use super::token::memory::Memory;
-use super::lexer::tokenize;
-use super::parser::parse;
-
-pub fn run(files: &[PathBuf]) {
-    let mut tokens: Tokens = Tokens::new();
-    let mut memory = Memory::default();
-
-    // Join files  =>  Everyone global
-    for (filename_idx, file) in files.iter().enumerate() {
-        let mut number_of_lines: u32 = 1;
-        let mut reader = BufReader::new(File::open(file).expect("Failed file open"));
-        tokens.add_file(file.to_str().unwrap());
-
-        let mut buf = String::new();
-        while 0 < reader.read_line(&mut buf).unwrap() {
-            if let Err(e) = tokenize(number_of_lines, filename_idx, &buf, &mut tokens) {
-                eprintln!("{}:{}: {}", file.display(), number_of_lines, e);
-                std::process::exit(1);
-            }
-            number_of_lines += 1;
-            buf.clear();
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use mipsi::{Simulator, Status, Execution, Endian};
+use mipsi::assembler::decode::decode;
+
+use super::Format;
+
+/// Join files  =>  Everyone global
+fn load(files: &[PathBuf], endian: Endian) -> Simulator {
+    let mut simulator = Simulator::new();
+    simulator.set_endian(endian);
+
+    for file in files {
+        if let Err(e) = simulator.load_file(file) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    simulator
+}
+
+pub fn run(files: &[PathBuf], machine: bool, endian: Endian, timer: Option<u32>, fs_root: Option<&Path>,
+           virtual_clock: bool, seed: Option<u64>) {
+    let mut simulator = load(files, endian);
+    if machine {
+        simulator.set_execution(Execution::Machine);
+    }
+    if let Some(n) = timer {
+        simulator.set_timer(n);
+    }
+    if virtual_clock {
+        simulator.set_virtual_clock(0);
+    }
+    if let Some(seed) = seed {
+        simulator.set_seed(seed);
+    }
+    if let Some(root) = fs_root {
+        if let Err(e) = simulator.set_fs_root(root) {
+            eprintln!("{}", e);
+            std::process::exit(1);
         }
     }
 
     // Execute
-    if let Err(e) = parse(&mut tokens, &mut memory) {
-        eprintln!("{}", e);
+    match simulator.run() {
+        Ok(Status::Exit(code)) => std::process::exit(code),
+        Ok(_) => (),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        },
+    }
+}
+
+pub fn asm(files: &[PathBuf], output: &Path, endian: Endian) {
+    let mut simulator = load(files, endian);
+
+    let words = match simulator.encode() {
+        Ok(words) => words,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        },
+    };
+
+    let bytes: Vec<u8> = words.iter().flat_map(|w| endian.u32_bytes(*w)).collect();
+    if let Err(e) = std::fs::write(output, bytes) {
+        eprintln!("{}: {}", output.display(), e);
         std::process::exit(1);
     }
 }
 
+pub fn disasm(file: &Path, format: Format, endian: Endian, base: u32) {
+    let bytes = match std::fs::read(file) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("{}: {}", file.display(), e);
+            std::process::exit(1);
+        },
+    };
+
+    let hex = match format {
+        Format::Auto => is_hex_text(&bytes),
+        Format::Bin  => false,
+        Format::Hex  => true,
+    };
+    let words = if hex {
+        match parse_hex(&String::from_utf8_lossy(&bytes), endian) {
+            Ok(words) => words,
+            Err(e) => {
+                eprintln!("{}: {}", file.display(), e);
+                std::process::exit(1);
+            },
+        }
+    } else {
+        if bytes.len() % 4 != 0 {
+            eprintln!("{}: ignored the last {} byte(s)", file.display(), bytes.len() % 4);
+        }
+        bytes.chunks_exact(4).map(|b| word(b, endian)).collect()
+    };
+
+    let mut stdout = std::io::stdout().lock();
+    for (i, &w) in words.iter().enumerate() {
+        let address = base.wrapping_add(i as u32 * 4);
+        let result = match decode(w, address) {
+            Ok(instruction) => writeln!(stdout, "0x{:08x}  0x{:08x}  {}", address, w, instruction),
+            Err(_)          => writeln!(stdout, "0x{:08x}  0x{:08x}  .word 0x{:08x}", address, w, w),
+        };
+        // e.g. Broken pipe
+        if result.is_err() {
+            return;
+        }
+    }
+}
+
+fn word(bytes: &[u8], endian: Endian) -> u32 {
+    endian.u32_from([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Only hex digits, whitespace, `0x`, `,`, `addr:` and comments after `#`
+fn is_hex_text(bytes: &[u8]) -> bool {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.lines()
+            .flat_map(|line| line.split('#').next().unwrap_or_default().split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|t| !t.is_empty() && !t.ends_with(':'))
+            .all(|t| {
+                let t = t.trim_start_matches("0x").trim_start_matches("0X");
+                !t.is_empty() && t.chars().all(|c| c.is_ascii_hexdigit())
+            }),
+        Err(_) => false,
+    }
+}
+
+/// 8 digits are a word (e.g. `0x012a4020`),
+/// others are bytes in memory order (e.g. `01 2a 40 20`, `012a 4020`).
+/// `addr:` and comments after `#` are ignored.
+fn parse_hex(text: &str, endian: Endian) -> Result<Vec<u32>, String> {
+    let mut words = Vec::new();
+    let mut bytes = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        for t in line.split(|c: char| c.is_whitespace() || c == ',') {
+            if t.is_empty() || t.ends_with(':') {
+                continue;
+            }
+            let digits = t.trim_start_matches("0x").trim_start_matches("0X");
+            let invalid = || format!("{}: invalid hex: {}", i+1, t);
+            if digits.len() == 8 && bytes.is_empty() {
+                words.push(u32::from_str_radix(digits, 16).map_err(|_| invalid())?);
+            } else if digits.len() % 2 == 0 && digits.is_ascii() {
+                for j in (0..digits.len()).step_by(2) {
+                    bytes.push(u8::from_str_radix(&digits[j..j+2], 16).map_err(|_| invalid())?);
+                }
+                if bytes.len() >= 4 {
+                    words.extend(bytes.chunks_exact(4).map(|b| word(b, endian)));
+                    bytes.drain(..bytes.len() / 4 * 4);
+                }
+            } else {
+                return Err(invalid());
+            }
+        }
+    }
+
+    if !bytes.is_empty() {
+        return Err(format!("ignored the last {} byte(s)", bytes.len()));
+    }
+    Ok(words)
+}
+
+#[test]
+#[cfg(test)]
+fn test_hex_text() {
+    let text = "# dump\n0x20080005 # li\n0x0000000c\n";
+    assert!(is_hex_text(text.as_bytes()));
+    assert_eq!(parse_hex(text, Endian::Big).unwrap(), [0x20080005, 0x0000000c]);
+    assert_eq!(parse_hex("00400000: 20 08 00 05\n", Endian::Little).unwrap(), [0x05000820]);
+    assert!(!is_hex_text(b"\x20\x08\x00\x05"));
+}
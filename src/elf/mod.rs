@@ -0,0 +1,211 @@
+//! ELF32 MIPS executables (statically linked)
+
+mod test;
+
+use super::token::memory::{mappable, Endian, USER_END};
+
+type Result<T> = std::result::Result<T, String>;
+
+// e_ident
+const MAGIC:       &[u8] = b"\x7fELF";
+const ELFCLASS32:  u8 = 1;
+const ELFDATA2LSB: u8 = 1;
+const ELFDATA2MSB: u8 = 2;
+
+const ET_EXEC:     u16 = 2;
+const EM_MIPS:     u16 = 8;
+const PT_LOAD:     u32 = 1;
+const PF_X:        u32 = 1;
+const SHT_SYMTAB:  u32 = 2;
+const STT_NOTYPE:  u8 = 0;
+const STT_FUNC:    u8 = 2;
+
+/// Memory image of a PT_LOAD segment (.bss is zero-filled)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub address: u32,
+    pub bytes: Vec<u8>,
+    pub executable: bool,
+}
+
+/// Named address in the symbol table
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub address: u32,
+    pub function: bool,  // STT_FUNC or STT_NOTYPE
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Elf {
+    pub endian: Endian,
+    pub entry: u32,
+    pub segments: Vec<Segment>,
+    pub symbols: Vec<Symbol>,
+}
+
+impl Elf {
+    pub fn is_elf(bytes: &[u8]) -> bool {
+        bytes.starts_with(MAGIC)
+    }
+
+    pub fn symbol(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|s| s.name == name)
+    }
+}
+
+/// Reads fields of the file in its byte order
+struct Reader<'a> {
+    bytes: &'a [u8],
+    endian: Endian,
+}
+
+impl<'a> Reader<'a> {
+    fn slice(&self, offset: u32, len: u32) -> Result<&'a [u8]> {
+        let start = offset as usize;
+        let end = start.checked_add(len as usize).filter(|&end| end <= self.bytes.len());
+        match end {
+            Some(end) => Ok(&self.bytes[start..end]),
+            None => Err(format!("truncated file: 0x{:x}-0x{:x} is out of {} bytes",
+                offset, offset as u64 + len as u64, self.bytes.len())),
+        }
+    }
+
+    fn u8(&self, offset: u32) -> Result<u8> {
+        Ok(self.slice(offset, 1)?[0])
+    }
+
+    fn u16(&self, offset: u32) -> Result<u16> {
+        let b = self.slice(offset, 2)?;
+        Ok(match self.endian {
+            Endian::Big    => u16::from_be_bytes([b[0], b[1]]),
+            Endian::Little => u16::from_le_bytes([b[0], b[1]]),
+        })
+    }
+
+    fn u32(&self, offset: u32) -> Result<u32> {
+        let b = self.slice(offset, 4)?;
+        Ok(match self.endian {
+            Endian::Big    => u32::from_be_bytes([b[0], b[1], b[2], b[3]]),
+            Endian::Little => u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        })
+    }
+
+    /// Reader of the `i`-th entry of `size` bytes in the table at `base`
+    fn entry(&self, base: u32, i: u32, size: u32) -> Result<Reader<'a>> {
+        let offset = i.checked_mul(size).and_then(|o| o.checked_add(base))
+            .ok_or_else(|| format!("truncated file: entry {} of the table at 0x{:x} is out of range", i, base))?;
+        Ok(Reader { bytes: self.slice(offset, size)?, endian: self.endian })
+    }
+
+    /// Null-terminated string
+    fn string(&self, offset: u32) -> Result<String> {
+        self.slice(offset, 0)?;
+        let bytes = &self.bytes[offset as usize..];
+        let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        Ok(String::from_utf8_lossy(&bytes[..len]).into_owned())
+    }
+}
+
+pub fn parse(bytes: &[u8]) -> Result<Elf> {
+    if !Elf::is_elf(bytes) {
+        return Err("not an ELF file".to_string());
+    }
+    let mut r = Reader { bytes, endian: Endian::Big };
+    if r.u8(4)? != ELFCLASS32 {
+        return Err("not a 32-bit ELF file".to_string());
+    }
+    r.endian = match r.u8(5)? {
+        ELFDATA2LSB => Endian::Little,
+        ELFDATA2MSB => Endian::Big,
+        data => return Err(format!("unknown data encoding: {}", data)),
+    };
+
+    let e_type = r.u16(0x10)?;
+    let e_machine = r.u16(0x12)?;
+    if e_machine != EM_MIPS {
+        return Err(format!("not a MIPS executable: e_machine is {}", e_machine));
+    }
+    if e_type != ET_EXEC {
+        return Err(format!("not a statically linked executable: e_type is {}", e_type));
+    }
+
+    let entry = r.u32(0x18)?;
+    let segments = segments(&r)?;
+    let symbols = symbols(&r, &segments)?;
+
+    Ok(Elf { endian: r.endian, entry, segments, symbols })
+}
+
+/// PT_LOAD program headers
+fn segments(r: &Reader) -> Result<Vec<Segment>> {
+    let phoff = r.u32(0x1c)?;
+    let phentsize = r.u16(0x2a)? as u32;
+    let phnum = r.u16(0x2c)? as u32;
+
+    let mut segments = Vec::new();
+    for i in 0..phnum {
+        let ph = r.entry(phoff, i, phentsize)?;
+        if ph.u32(0)? != PT_LOAD {
+            continue;
+        }
+        let offset  = ph.u32(0x04)?;
+        let address = ph.u32(0x08)?;
+        let filesz  = ph.u32(0x10)?;
+        let memsz   = ph.u32(0x14)?;
+        let flags   = ph.u32(0x18)?;
+        if memsz < filesz {
+            return Err(format!("segment 0x{:08x}: p_memsz is less than p_filesz", address));
+        }
+        // Before allocating the zero-filled bytes
+        if USER_END <= address || !mappable(address, memsz as u64) {
+            return Err(format!("segment 0x{:08x}: p_memsz 0x{:x} is out of the text and data segments", address, memsz));
+        }
+
+        let mut bytes = r.slice(offset, filesz)?.to_vec();
+        bytes.resize(memsz as usize, 0);
+        segments.push(Segment { address, bytes, executable: flags & PF_X != 0 });
+    }
+
+    if segments.is_empty() {
+        return Err("no PT_LOAD segment".to_string());
+    }
+    Ok(segments)
+}
+
+/// Named symbols of .symtab (empty if stripped)
+fn symbols(r: &Reader, segments: &[Segment]) -> Result<Vec<Symbol>> {
+    let shoff = r.u32(0x20)?;
+    let shentsize = r.u16(0x2e)? as u32;
+    let shnum = r.u16(0x30)? as u32;
+
+    let mut symbols = Vec::new();
+    for i in 0..shnum {
+        let sh = r.entry(shoff, i, shentsize)?;
+        if sh.u32(0x04)? != SHT_SYMTAB {
+            continue;
+        }
+        let offset  = sh.u32(0x10)?;
+        let size    = sh.u32(0x14)?;
+        let strtab  = r.entry(shoff, sh.u32(0x18)?, shentsize)?.u32(0x10)?;  // sh_link
+        let entsize = sh.u32(0x24)?.max(16);
+
+        for j in 0..size / entsize {
+            let st = r.entry(offset, j, entsize)?;
+            let name    = st.u32(0)?;
+            let address = st.u32(0x04)?;
+            let kind    = st.u8(0x0c)? & 0xf;
+            if name == 0 || address == 0 {
+                continue;
+            }
+            let name = r.string(strtab.checked_add(name)
+                .ok_or_else(|| format!("truncated file: name 0x{:x} of the string table at 0x{:x} is out of range", name, strtab))?)?;
+            // Labels in code (`$`-prefixed names are mapping symbols)
+            let function = (kind == STT_FUNC || kind == STT_NOTYPE) && !name.starts_with('$')
+                && segments.iter().any(|s| s.executable
+                    && (s.address..s.address.saturating_add(s.bytes.len() as u32)).contains(&address));
+            symbols.push(Symbol { name, address, function });
+        }
+    }
+    Ok(symbols)
+}
@@ -0,0 +1,138 @@
+
+#[cfg(test)]
+fn executable(endian: crate::token::memory::Endian) -> Vec<u8> {
+    use crate::token::memory::Endian;
+
+    let mut bytes: Vec<u8> = Vec::new();
+    let half = |bytes: &mut Vec<u8>, v: u16| bytes.extend(match endian {
+        Endian::Big    => v.to_be_bytes(),
+        Endian::Little => v.to_le_bytes(),
+    });
+    let word = |bytes: &mut Vec<u8>, v: u32| bytes.extend(match endian {
+        Endian::Big    => v.to_be_bytes(),
+        Endian::Little => v.to_le_bytes(),
+    });
+    let symbol = |bytes: &mut Vec<u8>, name: u32, value: u32, info: u8| {
+        word(bytes, name); word(bytes, value); word(bytes, 0);
+        bytes.extend([info, 0, 1, 0]);
+    };
+
+    // ELF header
+    bytes.extend(b"\x7fELF\x01");
+    bytes.push(if endian == Endian::Big { 2 } else { 1 });
+    bytes.extend([1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    half(&mut bytes, 2);  half(&mut bytes, 8);  word(&mut bytes, 1);
+    word(&mut bytes, 0x00400000);  // e_entry
+    word(&mut bytes, 0x34);        // e_phoff
+    word(&mut bytes, 0xe4);        // e_shoff
+    word(&mut bytes, 0);
+    half(&mut bytes, 52);  half(&mut bytes, 32);  half(&mut bytes, 2);
+    half(&mut bytes, 40);  half(&mut bytes, 3);   half(&mut bytes, 0);
+
+    // Program headers: .text (R+X), .data + .bss (R+W)
+    for &(offset, address, filesz, memsz, flags) in &[(0x80, 0x00400000, 16, 16, 5), (0x90, 0x00410000, 4, 8, 6)] {
+        for v in [1, offset, address, address, filesz, memsz, flags, 4] {
+            word(&mut bytes, v);
+        }
+    }
+
+    bytes.resize(0x80, 0);
+
+    // .text
+    for code in [
+        0x3c040041u32,  // lui   $a0, 0x0041
+        0x8c840000,     // lw    $a0, 0($a0)
+        0x24020011,     // addiu $v0, $zero, 17
+        0x0000000c,     // syscall
+    ] {
+        word(&mut bytes, code);
+    }
+    // .data
+    word(&mut bytes, 42);
+
+    // .strtab (0x94), .symtab (0xa4)
+    bytes.extend(b"\0main\0value\0_gp\0");
+    bytes.extend([0; 16]);
+    symbol(&mut bytes, 1, 0x00400000, 0x12);  // main:  FUNC
+    symbol(&mut bytes, 6, 0x00410000, 0x11);  // value: OBJECT
+    symbol(&mut bytes, 12, 0x00418000, 0x10); // _gp:   NOTYPE
+
+    // Section headers: null, .symtab, .strtab
+    bytes.extend([0; 40]);
+    for v in [0, 2, 0, 0, 0xa4, 64, 2, 0, 4, 16] {
+        word(&mut bytes, v);
+    }
+    for v in [0, 3, 0, 0, 0x94, 16, 0, 0, 1, 0] {
+        word(&mut bytes, v);
+    }
+
+    bytes
+}
+
+#[test]
+#[cfg(test)]
+fn test_parse() {
+    use super::*;
+
+    for endian in [Endian::Big, Endian::Little] {
+        let elf = parse(&executable(endian)).unwrap();
+
+        assert_eq!(elf.endian, endian);
+        assert_eq!(elf.entry, 0x00400000);
+        assert_eq!(elf.segments.len(), 2);
+        assert!(elf.segments[0].executable);
+        assert_eq!(elf.segments[1].bytes.len(), 8);
+        assert!(elf.symbol("main").unwrap().function);
+        assert!(!elf.symbol("value").unwrap().function);
+        assert_eq!(elf.symbol("_gp").unwrap().address, 0x00418000);
+    }
+
+    assert!(parse(b"\x7fELF").is_err());
+    assert!(parse(b"main:\n").is_err());
+}
+
+#[test]
+#[cfg(test)]
+fn test_parse_malformed() {
+    use super::*;
+
+    for (offset, value) in [
+        (0x20,  0xffffffff),  // e_shoff
+        (0x1c,  0xfffffff0),  // e_phoff
+        (0x48,  0xffffffff),  // p_memsz of .text
+        (0x48,  0x7fb00000),  // p_memsz of .text beyond the text segment (rejected before allocating)
+        (0x124, 0xffffffff),  // sh_link of .symtab
+        (0x120, 0xffffffff),  // sh_size of .symtab
+        (0xb4,  0xffffffff),  // st_name of main
+    ] {
+        let mut bytes = executable(Endian::Big);
+        bytes[offset..offset+4].copy_from_slice(&u32::to_be_bytes(value));
+        assert!(parse(&bytes).is_err(), "0x{:x}", offset);
+    }
+
+    let mut bytes = executable(Endian::Big);
+    bytes[0x124..0x128].copy_from_slice(&u32::to_be_bytes(0xffffffff));
+    assert!(parse(&bytes).unwrap_err().starts_with("truncated file"));
+
+    let mut bytes = executable(Endian::Big);
+    bytes[0x48..0x4c].copy_from_slice(&u32::to_be_bytes(0x7fb00000));
+    assert!(parse(&bytes).unwrap_err().ends_with("out of the text and data segments"));
+}
+
+#[test]
+#[cfg(test)]
+fn test_load_elf() {
+    use crate::{Simulator, Status, RegisterKind};
+    use crate::token::memory::Endian;
+
+    for endian in [Endian::Big, Endian::Little] {
+        let mut simulator = Simulator::new();
+        simulator.load_elf("a.out", &executable(endian)).unwrap();
+
+        assert_eq!(simulator.pc(), 0x00400000);
+        assert_eq!(simulator.register(RegisterKind::gp), 0x00418000);
+        assert_eq!(simulator.register(RegisterKind::sp) & 0xf, 0);
+        assert!(simulator.program().symbols().get("main").is_some());
+        assert_eq!(simulator.run().unwrap(), Status::Exit(42));
+    }
+}
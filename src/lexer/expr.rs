@@ -0,0 +1,150 @@
+/// Constant expression of 32-bit integers.  e.g. `SIZE*4+1`, `(1 << 16) | 0xff`, `~0x3`
+///
+/// | Precedence | Operator             |
+/// |------------|----------------------|
+/// | 1 (high)   | `-` `+` `~` (unary)  |
+/// | 2          | `*` `/`              |
+/// | 3          | `+` `-`              |
+/// | 4          | `<<` `>>`            |
+/// | 5          | `&`                  |
+/// | 6 (low)    | `\|`                 |
+///
+/// Evaluated in 64 bits (like GNU as) and truncated to 32 bits.
+/// e.g. `0xffffffff >> 4` is `0x0fffffff`, `-16 >> 2` is `-4`
+pub fn eval(expr: &str) -> Result<i32, String> {
+    let mut parser = Parser { expr: expr.as_bytes(), idx: 0 };
+    let value = parser.or()?;
+    if parser.peek().is_some() {
+        return Err(format!("invalid expression: {}", expr));
+    }
+    Ok(value as i32)
+}
+
+struct Parser<'a> {
+    expr: &'a [u8],
+    idx: usize,
+}
+
+impl Parser<'_> {
+    /// Next byte except white space
+    fn peek(&mut self) -> Option<u8> {
+        while matches!(self.expr.get(self.idx), Some(b' ' | b'\t')) {
+            self.idx += 1;
+        }
+        self.expr.get(self.idx).copied()
+    }
+
+    /// Consume `op` if it is next
+    fn eat(&mut self, op: &str) -> bool {
+        self.peek();
+        if self.expr[self.idx..].starts_with(op.as_bytes()) {
+            self.idx += op.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error(&self) -> String {
+        format!("invalid expression: {}", String::from_utf8_lossy(self.expr))
+    }
+
+    fn or(&mut self) -> Result<i64, String> {
+        let mut value = self.and()?;
+        while self.eat("|") {
+            value |= self.and()?;
+        }
+        Ok(value)
+    }
+
+    fn and(&mut self) -> Result<i64, String> {
+        let mut value = self.shift()?;
+        while self.eat("&") {
+            value &= self.shift()?;
+        }
+        Ok(value)
+    }
+
+    fn shift(&mut self) -> Result<i64, String> {
+        let mut value = self.additive()?;
+        loop {
+            let left = if self.eat("<<") {
+                true
+            } else if self.eat(">>") {
+                false
+            } else {
+                return Ok(value);
+            };
+            let shamt = self.additive()?;
+            if !(0..32).contains(&shamt) {
+                return Err(format!("shift amount out of range: {}", shamt));
+            }
+            value = if left { value << shamt } else { value >> shamt };
+        }
+    }
+
+    fn additive(&mut self) -> Result<i64, String> {
+        let mut value = self.multiplicative()?;
+        loop {
+            if self.eat("+") {
+                value = value.wrapping_add(self.multiplicative()?);
+            } else if self.eat("-") {
+                value = value.wrapping_sub(self.multiplicative()?);
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<i64, String> {
+        let mut value = self.unary()?;
+        loop {
+            if self.eat("*") {
+                value = value.wrapping_mul(self.unary()?);
+            } else if self.eat("/") {
+                let divisor = self.unary()?;
+                if divisor == 0 {
+                    return Err(format!("division by zero: {}", String::from_utf8_lossy(self.expr)));
+                }
+                value = value.wrapping_div(divisor);
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        if self.eat("-") {
+            Ok(self.unary()?.wrapping_neg())
+        } else if self.eat("+") {
+            self.unary()
+        } else if self.eat("~") {
+            Ok(!self.unary()?)
+        } else {
+            self.primary()
+        }
+    }
+
+    /// Decimal, hexadecimal or parenthesized expression
+    fn primary(&mut self) -> Result<i64, String> {
+        if self.eat("(") {
+            let value = self.or()?;
+            return if self.eat(")") { Ok(value) } else { Err(self.error()) };
+        }
+
+        let (radix, start) = if self.eat("0x") || self.eat("0X") {
+            (16, self.idx)
+        } else {
+            (10, self.idx)
+        };
+        while matches!(self.expr.get(self.idx), Some(b) if b.is_ascii_alphanumeric()) {
+            self.idx += 1;
+        }
+        let digits = std::str::from_utf8(&self.expr[start..self.idx]).map_err(|_| self.error())?;
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(self.error());
+        }
+        // Up to 32 bits (e.g. 0xffffffff, 4294967295)
+        u32::from_str_radix(digits, radix).map(i64::from).map_err(|_| self.error())
+    }
+}
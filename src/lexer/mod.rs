@@ -1,6 +1,8 @@
+mod expr;
 mod test;
 use super::token::*;
 use super::token::register::RegisterKind;
+use std::collections::BTreeMap;
 
 /// Recieve 1 line
 /// nol: number_of_lines
@@ -8,7 +10,13 @@ use super::token::register::RegisterKind;
 pub fn tokenize(nol: u32, fi: usize, line: &str, tokens: &mut Tokens)
     -> Result<(), String>
 {
-    let words: Vec<String> = split_words(line);
+    // .eqv NAME text | .set NAME, expression
+    if define(line, &mut tokens.constants)? {
+        return Ok(());
+    }
+
+    let line = substitute(line, &tokens.constants);
+    let words: Vec<String> = join_expressions(split_words(&line));
     let words: Vec<&str>   = words.iter().map(|s| &**s).collect();
 
     //println!("{:?}", words);
@@ -20,18 +28,19 @@ pub fn tokenize(nol: u32, fi: usize, line: &str, tokens: &mut Tokens)
 
     let mut words = words.iter();
     while let Some(word) = words.next() {
-        if let Ok(num) = word.parse::<i32>() {
+        let integer = expr::eval(word);
+        if let Ok(num) = integer {
             tokens.push(TokenKind::INTEGER(num), nol, fi);
         } else if let Ok(num) = word.parse::<f32>() {
             tokens.push(TokenKind::FLOATING(num), nol, fi);
-        } else if let Some(num) = is_hexadecimal(word) {
-            tokens.push(TokenKind::INTEGER(num), nol, fi);
         } else if let Ok((k, i)) = is_register(word) {
             tokens.push(TokenKind::REGISTER(k, i), nol, fi);
+        } else if let Ok(kind) = is_relocation(word) {
+            tokens.push(kind, nol, fi);
         } else if let Ok((k, i, a)) = is_memory(word) {
             tokens.push(TokenKind::MEMORY(k, i, a), nol, fi);
-        } else if let Ok((k, i, s)) = is_data_address(word) {
-            tokens.push(TokenKind::DATA(k, i, s), nol, fi);
+        } else if let Ok((k, i, s, a)) = is_data_address(word) {
+            tokens.push(TokenKind::DATA(k, i, s, a), nol, fi);
         } else {
             let token_kind = match &*word.to_ascii_uppercase() {
                 // CPU Instructions
@@ -86,31 +95,31 @@ pub fn tokenize(nol: u32, fi: usize, line: &str, tokens: &mut Tokens)
                 "LUI"     => TokenKind::INSTRUCTION(InstructionKind::LUI),
 
                 // Comparison
-                "SLTU" |
                 "SLT"     => TokenKind::INSTRUCTION(InstructionKind::SLT),
-                "SLTIU" |
                 "SLTI"    => TokenKind::INSTRUCTION(InstructionKind::SLTI),
+                "SLTU"    => TokenKind::INSTRUCTION(InstructionKind::SLTU),
+                "SLTIU"   => TokenKind::INSTRUCTION(InstructionKind::SLTIU),
                 "SEQ"     => TokenKind::INSTRUCTION(InstructionKind::SEQ),
-                "SGEU" |
                 "SGE"     => TokenKind::INSTRUCTION(InstructionKind::SGE),
-                "SGTU" |
+                "SGEU"    => TokenKind::INSTRUCTION(InstructionKind::SGEU),
                 "SGT"     => TokenKind::INSTRUCTION(InstructionKind::SGT),
-                "SLEU" |
+                "SGTU"    => TokenKind::INSTRUCTION(InstructionKind::SGTU),
                 "SLE"     => TokenKind::INSTRUCTION(InstructionKind::SLE),
+                "SLEU"    => TokenKind::INSTRUCTION(InstructionKind::SLEU),
                 "SNE"     => TokenKind::INSTRUCTION(InstructionKind::SNE),
 
                 // Branch
                 "B"       => TokenKind::INSTRUCTION(InstructionKind::B),
                 "BEQ"     => TokenKind::INSTRUCTION(InstructionKind::BEQ),
                 "BNE"     => TokenKind::INSTRUCTION(InstructionKind::BNE),
-                "BGE" |
-                "BGEU"    => TokenKind::INSTRUCTION(InstructionKind::BGE),
-                "BGT" |
-                "BGTU"    => TokenKind::INSTRUCTION(InstructionKind::BGT),
-                "BLE" |
-                "BLEU"    => TokenKind::INSTRUCTION(InstructionKind::BLE),
-                "BLT" |
-                "BLTU"    => TokenKind::INSTRUCTION(InstructionKind::BLT),
+                "BGE"     => TokenKind::INSTRUCTION(InstructionKind::BGE),
+                "BGEU"    => TokenKind::INSTRUCTION(InstructionKind::BGEU),
+                "BGT"     => TokenKind::INSTRUCTION(InstructionKind::BGT),
+                "BGTU"    => TokenKind::INSTRUCTION(InstructionKind::BGTU),
+                "BLE"     => TokenKind::INSTRUCTION(InstructionKind::BLE),
+                "BLEU"    => TokenKind::INSTRUCTION(InstructionKind::BLEU),
+                "BLT"     => TokenKind::INSTRUCTION(InstructionKind::BLT),
+                "BLTU"    => TokenKind::INSTRUCTION(InstructionKind::BLTU),
                 "BEQZ"    => TokenKind::INSTRUCTION(InstructionKind::BEQZ),
                 "BGEZ"    => TokenKind::INSTRUCTION(InstructionKind::BGEZ),
                 "BGTZ"    => TokenKind::INSTRUCTION(InstructionKind::BGTZ),
@@ -150,6 +159,22 @@ pub fn tokenize(nol: u32, fi: usize, line: &str, tokens: &mut Tokens)
 
                 // Exception, Interrupt
                 "SYSCALL" => TokenKind::INSTRUCTION(InstructionKind::SYSCALL),
+                "BREAK"   => TokenKind::INSTRUCTION(InstructionKind::BREAK),
+                "TEQ"     => TokenKind::INSTRUCTION(InstructionKind::TEQ),
+                "TNE"     => TokenKind::INSTRUCTION(InstructionKind::TNE),
+                "TGE"     => TokenKind::INSTRUCTION(InstructionKind::TGE),
+                "TGEU"    => TokenKind::INSTRUCTION(InstructionKind::TGEU),
+                "TLT"     => TokenKind::INSTRUCTION(InstructionKind::TLT),
+                "TLTU"    => TokenKind::INSTRUCTION(InstructionKind::TLTU),
+                "TEQI"    => TokenKind::INSTRUCTION(InstructionKind::TEQI),
+                "TNEI"    => TokenKind::INSTRUCTION(InstructionKind::TNEI),
+                "TGEI"    => TokenKind::INSTRUCTION(InstructionKind::TGEI),
+                "TGEIU"   => TokenKind::INSTRUCTION(InstructionKind::TGEIU),
+                "TLTI"    => TokenKind::INSTRUCTION(InstructionKind::TLTI),
+                "TLTIU"   => TokenKind::INSTRUCTION(InstructionKind::TLTIU),
+                "ERET"    => TokenKind::INSTRUCTION(InstructionKind::ERET),
+                "MFC0"    => TokenKind::INSTRUCTION(InstructionKind::MFC0),
+                "MTC0"    => TokenKind::INSTRUCTION(InstructionKind::MTC0),
                 "NOP"     => TokenKind::INSTRUCTION(InstructionKind::NOP),
 
                 // My own
@@ -167,12 +192,24 @@ pub fn tokenize(nol: u32, fi: usize, line: &str, tokens: &mut Tokens)
                 "LWC1"    => TokenKind::INSTRUCTION(InstructionKind::LW),
                 "SWC1"    => TokenKind::INSTRUCTION(InstructionKind::SW),
                 "MTC1"    => TokenKind::INSTRUCTION(InstructionKind::MTC1),
+                "CFC1"    => TokenKind::INSTRUCTION(InstructionKind::CFC1),
+                "CTC1"    => TokenKind::INSTRUCTION(InstructionKind::CTC1),
+                "MFC1"    => TokenKind::INSTRUCTION(InstructionKind::MOVE),
+                "MOV.S"   => TokenKind::INSTRUCTION(InstructionKind::MOVE),
+                "MOVF"    => TokenKind::INSTRUCTION(InstructionKind::MOVF),
+                "MOVT"    => TokenKind::INSTRUCTION(InstructionKind::MOVT),
+                "MOVN.S"  => TokenKind::INSTRUCTION(InstructionKind::MOVN_S),
+                "MOVZ.S"  => TokenKind::INSTRUCTION(InstructionKind::MOVZ_S),
                 "ABS.S"   => TokenKind::INSTRUCTION(InstructionKind::ABS_S),
                 "ADD.S"   => TokenKind::INSTRUCTION(InstructionKind::ADD_S),
                 "DIV.S"   => TokenKind::INSTRUCTION(InstructionKind::DIV_S),
                 "MUL.S"   => TokenKind::INSTRUCTION(InstructionKind::MUL_S),
                 "SUB.S"   => TokenKind::INSTRUCTION(InstructionKind::SUB_S),
                 "NEG.S"   => TokenKind::INSTRUCTION(InstructionKind::NEG_S),
+                "SQRT.S"  => TokenKind::INSTRUCTION(InstructionKind::SQRT_S),
+                "MADD.S"  => TokenKind::INSTRUCTION(InstructionKind::MADD_S),
+                "RECIP.S" => TokenKind::INSTRUCTION(InstructionKind::RECIP_S),
+                "RSQRT.S" => TokenKind::INSTRUCTION(InstructionKind::RSQRT_S),
                 "BC1T"    => TokenKind::INSTRUCTION(InstructionKind::BC1T),
                 "BC1F"    => TokenKind::INSTRUCTION(InstructionKind::BC1F),
                 "C.EQ.S"  => TokenKind::INSTRUCTION(InstructionKind::C_EQ_S),
@@ -180,12 +217,34 @@ pub fn tokenize(nol: u32, fi: usize, line: &str, tokens: &mut Tokens)
                 "C.LT.S"  => TokenKind::INSTRUCTION(InstructionKind::C_LT_S),
                 "CVT.S.W" => TokenKind::INSTRUCTION(InstructionKind::CVT_S_W),
                 "CVT.W.S" => TokenKind::INSTRUCTION(InstructionKind::CVT_W_S),
+                "ROUND.W.S" => TokenKind::INSTRUCTION(InstructionKind::ROUND_W_S),
+                "TRUNC.W.S" => TokenKind::INSTRUCTION(InstructionKind::TRUNC_W_S),
+                "CEIL.W.S"  => TokenKind::INSTRUCTION(InstructionKind::CEIL_W_S),
+                "FLOOR.W.S" => TokenKind::INSTRUCTION(InstructionKind::FLOOR_W_S),
+                "LDC1"    => TokenKind::INSTRUCTION(InstructionKind::LDC1),
+                "L.D"     => TokenKind::INSTRUCTION(InstructionKind::LDC1),
+                "SDC1"    => TokenKind::INSTRUCTION(InstructionKind::SDC1),
+                "S.D"     => TokenKind::INSTRUCTION(InstructionKind::SDC1),
+                "ABS.D"   => TokenKind::INSTRUCTION(InstructionKind::ABS_D),
+                "ADD.D"   => TokenKind::INSTRUCTION(InstructionKind::ADD_D),
+                "DIV.D"   => TokenKind::INSTRUCTION(InstructionKind::DIV_D),
+                "MOV.D"   => TokenKind::INSTRUCTION(InstructionKind::MOV_D),
+                "MUL.D"   => TokenKind::INSTRUCTION(InstructionKind::MUL_D),
+                "NEG.D"   => TokenKind::INSTRUCTION(InstructionKind::NEG_D),
+                "SQRT.D"  => TokenKind::INSTRUCTION(InstructionKind::SQRT_D),
+                "SUB.D"   => TokenKind::INSTRUCTION(InstructionKind::SUB_D),
+                "C.EQ.D"  => TokenKind::INSTRUCTION(InstructionKind::C_EQ_D),
+                "C.LE.D"  => TokenKind::INSTRUCTION(InstructionKind::C_LE_D),
+                "C.LT.D"  => TokenKind::INSTRUCTION(InstructionKind::C_LT_D),
+                "CVT.D.S" => TokenKind::INSTRUCTION(InstructionKind::CVT_D_S),
+                "CVT.D.W" => TokenKind::INSTRUCTION(InstructionKind::CVT_D_W),
+                "CVT.S.D" => TokenKind::INSTRUCTION(InstructionKind::CVT_S_D),
+                "CVT.W.D" => TokenKind::INSTRUCTION(InstructionKind::CVT_W_D),
 
                 _ =>
                     if is_label(word) {
                         let mut identifier = (*word).to_string();
                         identifier.pop();  // Delete ':'
-                        tokens.add_address(identifier.clone(), tokens.len());
                         TokenKind::LABEL(identifier, tokens.len(), None)
                     } else if is_indicate(word) {
                         match *word {
@@ -199,30 +258,50 @@ pub fn tokenize(nol: u32, fi: usize, line: &str, tokens: &mut Tokens)
                                 let _ = words.next();
                                 TokenKind::INDICATE(IndicateKind::data)
                             },
+                            ".ktext" | ".kdata" => {
+                                // Optional <Addr>
+                                let address = words.as_slice().first()
+                                    .and_then(|w| expr::eval(w).ok());
+                                if address.is_some() {
+                                    words.next();
+                                }
+                                let address = address.map(|a| a as u32);
+                                if *word == ".ktext" {
+                                    TokenKind::INDICATE(IndicateKind::ktext(address))
+                                } else {
+                                    TokenKind::INDICATE(IndicateKind::kdata(address))
+                                }
+                            },
                             ".globl" => {
                                 let label = (*words.next().unwrap()).to_string();
                                 TokenKind::INDICATE(IndicateKind::globl(label))
                             },
                             ".word" => {
-                                indicate_word(tokens, nol, fi, words);
+                                indicate_integer(tokens, nol, fi, words, |n| IndicateKind::word(n as u32))?;
                                 break;
                             },
                             ".half" => {
-                                indicate_half(tokens, nol, fi, words);
+                                indicate_integer(tokens, nol, fi, words, |n| IndicateKind::half(n as u16))?;
                                 break;
                             },
                             ".byte" => {
-                                indicate_byte(tokens, nol, fi, words);
+                                indicate_integer(tokens, nol, fi, words, |n| IndicateKind::byte(n as u8))?;
                                 break;
                             },
                             ".float" => {
-                                indicate_float(tokens, nol, fi, words);
+                                indicate_float(tokens, nol, fi, words)?;
+                                break;
+                            },
+                            ".double" => {
+                                indicate_double(tokens, nol, fi, words)?;
                                 break;
                             },
                             ".space" => {
-                                let word = words.next().unwrap();
-                                let length = indicate_space(word);
-                                TokenKind::INDICATE(IndicateKind::space(length))
+                                let word = words.next().ok_or(".space: expect length")?;
+                                match expr::eval(word)? {
+                                    length @ 0.. => TokenKind::INDICATE(IndicateKind::space(length as u32)),
+                                    length => return Err(format!(".space: invalid length: {}", length)),
+                                }
                             },
                             ".ascii" => {
                                 let mut s = (*words.next().unwrap()).to_string();
@@ -249,7 +328,12 @@ pub fn tokenize(nol: u32, fi: usize, line: &str, tokens: &mut Tokens)
                         word.remove(word.len()-1);
                         TokenKind::LITERAL(word)
                     } else {
-                        TokenKind::ADDRESS((*word).to_string())
+                        match integer {
+                            // Not a label
+                            Err(e) if word.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '(' | '~' | '-' | '+')) =>
+                                return Err(e),
+                            _ => TokenKind::ADDRESS((*word).to_string()),
+                        }
                     }
             };
 
@@ -344,285 +428,260 @@ fn is_register(word: &str) -> Result<(RegisterKind, usize), String> {
     Ok((register_kind, idx))
 }
 
-/// [0-9]?[0-9]* \( `is_register` \)
-fn is_memory(word: &str) -> Result<(RegisterKind, usize, u32), String> {
+/// [offset] \( `is_register` \)  e.g. `-4($sp)`, `0x10($t0)`, `($a0)`
+fn is_memory(word: &str) -> Result<(RegisterKind, usize, i32), String> {
     let errmsg = format!("is_memory(): not memory index: {}", word);
-    if Some(')') != word.chars().nth(word.len()-1) {
-        return  Err(errmsg);
-    }
-    let mut add = 0u32;
-    let mut s = word.to_string();
-    s.pop();  // Delete ')'
-    let mut s_chars = s.chars();
-    while let Some(c) = s_chars.next() {
-        if c.is_ascii_digit() {
-            add = add * 10 + (c as u32 - 48);
-        } else if c == '(' {
-            let mut reg = String::new();
-            #[allow(clippy::while_let_on_iterator)]
-            while let Some(c) = s_chars.next() {
-                reg.push(c);
-            }
-            let (reg, idx) = is_register(&reg)?;
-            return Ok((reg, idx, add));
-        } else {
-            break;
-        }
-    }
-    Err(errmsg)
+    let (offset, register) = split_base(word).ok_or_else(|| errmsg.clone())?;
+    let offset = match offset {
+        "" => 0,
+        _ => expr::eval(offset).map_err(|_| errmsg)?,
+    };
+    let (reg, idx) = is_register(register)?;
+    Ok((reg, idx, offset))
 }
 
-/// [a-zA-Z_][a-zA-Z_0-9]* \( `is_register` \)
-fn is_data_address(word: &str) -> Result<(RegisterKind, usize, String), String> {
+/// [a-zA-Z_][a-zA-Z_0-9]* [(+|-) offset] [\( `is_register` \)]  e.g. `x($t0)`, `x+8($t0)`, `x-4`
+/// Without the register, the base is $zero.  A bare label is ADDRESS.
+fn is_data_address(word: &str) -> Result<(RegisterKind, usize, String, i32), String> {
     let errmsg = format!("is_data_address(): not data identifier: {}", word);
-    if Some(')') != word.chars().nth(word.len()-1) {
-        return  Err(errmsg);
-    }
-    let mut s = word.to_string();
-    s.pop();  // Delete ')'
-    let mut s_chars = s.chars();
-
-    if let Some(c) = s_chars.next() {
-        if let 'A'..='Z' | 'a'..='z' | '_' = c {
-            let mut label = c.to_string();
-
-            while let Some(c) = s_chars.next() {
-                if let 'A'..='Z' | 'a'..='z' | '_' | '0'..='9' = c {
-                    label.push(c);
-                } else if c == '(' {
-                    let mut reg = String::new();
-                    #[allow(clippy::while_let_on_iterator)]
-                    while let Some(c) = s_chars.next() {
-                        reg.push(c);
-                    }
-                    let (reg, idx) = is_register(&reg)?;
-                    return Ok((reg, idx, label));
-                } else {
-                    break;
-                }
-            }
-        }
+    let (address, register) = match split_base(word) {
+        Some((address, register)) => (address, Some(register)),
+        None => (word, None),
+    };
+    let (label, offset) = match address.find(['+', '-']) {
+        Some(i) => (&address[..i], expr::eval(&address[i..]).map_err(|_| errmsg.clone())?),
+        None if register.is_some() => (address, 0),
+        None => return Err(errmsg),
+    };
+
+    if !is_identifier(label) {
+        return Err(errmsg);
     }
-    Err(errmsg)
-}
 
-fn is_label(word: &str) -> bool {
-    word.ends_with(':')
+    let (reg, idx) = match register {
+        Some(register) => is_register(register)?,
+        None => (RegisterKind::zero, 0),
+    };
+    Ok((reg, idx, label.to_string(), offset))
 }
 
-fn is_indicate(word: &str) -> bool {
-    word.starts_with('.')
+/// `address(register)` => (address, register)
+fn split_base(word: &str) -> Option<(&str, &str)> {
+    let s = word.strip_suffix(')')?;
+    let open = s.rfind('(')?;
+    Some((&s[..open], &s[open+1..]))
 }
 
-fn is_hexadecimal(word: &str) -> Option<i32> {
-    if word.starts_with("0x") {
-        let mut hex: i32 = 0;
-        let mut s = word.to_string();
-        s.remove(0);
-        s.remove(0);  // Delete "0x"
-
-        for h in s.chars() {
-            hex = match h {
-                '0'..='9' => (hex << 4) + (h as u8 - b'0') as i32,
-                'a'..='f' => (hex << 4) + (h as u8 - b'a' + 10) as i32,
-                'A'..='F' => (hex << 4) + (h as u8 - b'A' + 10) as i32,
-                _ => return None,
-            }
-        }
+/// `%hi(` address `)` or `%lo(` address `)` [\( `is_register` \)]  e.g. `%hi(x)`, `%lo(x+4)($t0)`
+/// The address is a label with an optional offset (RELOCATION), or an expression (INTEGER or MEMORY).
+fn is_relocation(word: &str) -> Result<TokenKind, String> {
+    let errmsg = format!("is_relocation(): not relocation: {}", word);
+    let (operator, register) = match split_base(word) {
+        Some((operator, register)) if register.starts_with('$') => (operator, Some(is_register(register)?)),
+        _ => (word, None),
+    };
+    let (relocation, address) = match split_base(operator) {
+        Some(("%hi", address)) => (Relocation::hi, address),
+        Some(("%lo", address)) => (Relocation::lo, address),
+        _ => return Err(errmsg),
+    };
 
-        Some(hex)
-    } else if word.starts_with("-0x") {
-        let mut hex: i32 = 0;
-        let mut s = word.to_string();
-        s.remove(0);
-        s.remove(0);
-        s.remove(0);  // Delete "-0x"
-
-        for h in s.chars() {
-            hex = match h {
-                '0'..='9' => (hex << 4) + (h as u8 - b'0') as i32,
-                'a'..='f' => (hex << 4) + (h as u8 - b'a' + 10) as i32,
-                'A'..='F' => (hex << 4) + (h as u8 - b'A' + 10) as i32,
-                _ => return None,
-            }
-        }
+    if let Ok(address) = expr::eval(address) {
+        let value = relocation.apply(address as u32);
+        return Ok(match register {
+            Some((reg, idx)) => TokenKind::MEMORY(reg, idx, value),
+            None => TokenKind::INTEGER(value),
+        });
+    }
 
-        Some(-hex)
-    } else {
-        None
+    let (label, offset) = match address.find(['+', '-']) {
+        Some(i) => (&address[..i], expr::eval(&address[i..]).map_err(|_| errmsg.clone())?),
+        None => (address, 0),
+    };
+    if !is_identifier(label) {
+        return Err(errmsg);
     }
+    Ok(TokenKind::RELOCATION(relocation, label.to_string(), offset, register.map(|(_, idx)| idx)))
 }
 
-fn indicate_word(tokens: &mut Tokens, nol: u32, fi: usize, mut words: std::slice::Iter<&str>) {
-    let mut int = 0;
-    while let Some(word) = words.next() {
-        if 1 < word.len() && word.ends_with(':') {
-            let mut word = (*word).to_string();
-            let word2 = words.next().unwrap();
-            word.pop().unwrap();
-            for _ in 0..word2.parse::<usize>().unwrap() {
-                int = {
-                    if let Some(num) = is_hexadecimal(&word) {
-                        num as u32
-                    } else if let Ok(num) = word.parse::<i32>() {
-                        num as u32
-                    } else {
-                        word.parse::<u32>().unwrap()
-                    }
-                };
-                tokens.push(TokenKind::INDICATE(IndicateKind::word(int)), nol, fi);
-            }
-        } else if &word[..] == ":" {
-            let word = words.next().unwrap();
-            for _ in 1..word.parse::<usize>().unwrap() {
-                tokens.push(TokenKind::INDICATE(IndicateKind::word(int)), nol, fi);
-            }
+/// [a-zA-Z_][a-zA-Z_0-9]*
+fn is_identifier(word: &str) -> bool {
+    let mut chars = word.chars();
+    matches!(chars.next(), Some('A'..='Z' | 'a'..='z' | '_')) &&
+        chars.all(|c| matches!(c, 'A'..='Z' | 'a'..='z' | '_' | '0'..='9'))
+}
 
-        } else {
-            int = {
-                if let Some(num) = is_hexadecimal(word) {
-                    num as u32
-                } else if let Ok(num) = word.parse::<i32>() {
-                    num as u32
-                } else {
-                    word.parse::<u32>().unwrap()
-                }
-            };
-            tokens.push(TokenKind::INDICATE(IndicateKind::word(int)), nol, fi);
-        }
-    };
+fn is_label(word: &str) -> bool {
+    word.ends_with(':')
 }
 
-fn indicate_half(tokens: &mut Tokens, nol: u32, fi: usize, mut words: std::slice::Iter<&str>) {
-    let mut half = 0;
+fn is_indicate(word: &str) -> bool {
+    word.starts_with('.')
+}
+
+/// Integers of .word, .half and .byte  e.g. `.word 1, SIZE*4, 0x10:3` (`value:count` repeats the value)
+fn indicate_integer<F>(tokens: &mut Tokens, nol: u32, fi: usize, mut words: std::slice::Iter<&str>, kind: F)
+    -> Result<(), String>
+where
+    F: Fn(i32) -> IndicateKind,
+{
+    let mut int = 0;
     while let Some(word) = words.next() {
         if 1 < word.len() && word.ends_with(':') {
-            let mut word = (*word).to_string();
-            let word2 = words.next().unwrap();
-            word.pop().unwrap();
-            for _ in 0..word2.parse::<usize>().unwrap() {
-                let half = {
-                    if let Some(num) = is_hexadecimal(&word) {
-                        num as u16
-                    } else if let Ok(num) = word.parse::<i16>() {
-                        num as u16
-                    } else {
-                        word.parse::<u16>().unwrap()
-                    }
-                };
-                tokens.push(TokenKind::INDICATE(IndicateKind::half(half)), nol, fi);
+            int = expr::eval(&word[..word.len()-1])?;
+            let len = expr::eval(words.next().ok_or("expect count after ':'")?)?;
+            for _ in 0..len {
+                tokens.push(TokenKind::INDICATE(kind(int)), nol, fi);
             }
         } else if &word[..] == ":" {
-            let word = words.next().unwrap();
-            for _ in 1..word.parse::<usize>().unwrap() {
-                tokens.push(TokenKind::INDICATE(IndicateKind::half(half)), nol, fi);
+            let len = expr::eval(words.next().ok_or("expect count after ':'")?)?;
+            for _ in 1..len {
+                tokens.push(TokenKind::INDICATE(kind(int)), nol, fi);
             }
-
         } else {
-            half = {
-                if let Some(num) = is_hexadecimal(word) {
-                    num as u16
-                } else if let Ok(num) = word.parse::<i16>() {
-                    num as u16
-                } else {
-                    word.parse::<u16>().unwrap()
-                }
-            };
-            tokens.push(TokenKind::INDICATE(IndicateKind::half(half)), nol, fi);
+            int = expr::eval(word)?;
+            tokens.push(TokenKind::INDICATE(kind(int)), nol, fi);
         }
     };
+    Ok(())
 }
 
-fn indicate_byte(tokens: &mut Tokens, nol: u32, fi: usize, mut words: std::slice::Iter<&str>) {
-    let mut byte = 0;
+fn indicate_float(tokens: &mut Tokens, nol: u32, fi: usize, mut words: std::slice::Iter<&str>)
+    -> Result<(), String>
+{
+    let parse = |word: &str| word.parse::<f32>().map_err(|_| format!(".float: invalid value: {}", word));
+    let mut float = 0.0;
     while let Some(word) = words.next() {
         if 1 < word.len() && word.ends_with(':') {
-            let mut word = (*word).to_string();
-            let word2 = words.next().unwrap();
-            word.pop().unwrap();
-            for _ in 0..word2.parse::<usize>().unwrap() {
-                byte = {
-                    if let Some(num) = is_hexadecimal(&word) {
-                        num as u8
-                    } else if let Ok(num) = word.parse::<i8>() {
-                        num as u8
-                    } else {
-                        word.parse::<u8>().unwrap()
-                    }
-                };
-                tokens.push(TokenKind::INDICATE(IndicateKind::byte(byte)), nol, fi);
+            float = parse(&word[..word.len()-1])?;
+            let len = expr::eval(words.next().ok_or("expect count after ':'")?)?;
+            for _ in 0..len {
+                tokens.push(TokenKind::INDICATE(IndicateKind::float(float)), nol, fi);
             }
         } else if &word[..] == ":" {
-            let word = words.next().unwrap();
-            for _ in 1..word.parse::<usize>().unwrap() {
-                tokens.push(TokenKind::INDICATE(IndicateKind::byte(byte)), nol, fi);
+            let len = expr::eval(words.next().ok_or("expect count after ':'")?)?;
+            for _ in 1..len {
+                tokens.push(TokenKind::INDICATE(IndicateKind::float(float)), nol, fi);
             }
         } else {
-            byte = {
-                if let Some(num) = is_hexadecimal(word) {
-                    num as u8
-                } else if let Ok(num) = word.parse::<i8>() {
-                    num as u8
-                } else {
-                    word.parse::<u8>().unwrap()
-                }
-            };
-            tokens.push(TokenKind::INDICATE(IndicateKind::byte(byte)), nol, fi);
+            float = parse(word)?;
+            tokens.push(TokenKind::INDICATE(IndicateKind::float(float)), nol, fi);
         }
     };
+    Ok(())
 }
 
-fn indicate_float(tokens: &mut Tokens, nol: u32, fi: usize, mut words: std::slice::Iter<&str>) {
-    let mut float = 0.0;
+fn indicate_double(tokens: &mut Tokens, nol: u32, fi: usize, mut words: std::slice::Iter<&str>)
+    -> Result<(), String>
+{
+    let parse = |word: &str| word.parse::<f64>().map_err(|_| format!(".double: invalid value: {}", word));
+    let mut double = 0.0;
     while let Some(word) = words.next() {
         if 1 < word.len() && word.ends_with(':') {
-            let mut word = (*word).to_string();
-            let len = words.next().unwrap().parse::<usize>().unwrap();
-            word.pop().unwrap();
+            double = parse(&word[..word.len()-1])?;
+            let len = expr::eval(words.next().ok_or("expect count after ':'")?)?;
             for _ in 0..len {
-                float = word.parse::<f32>().unwrap();
-                tokens.push(TokenKind::INDICATE(IndicateKind::float(float)), nol, fi);
+                tokens.push(TokenKind::INDICATE(IndicateKind::double(double)), nol, fi);
             }
         } else if &word[..] == ":" {
-            let len = words.next().unwrap().parse::<usize>().unwrap();
+            let len = expr::eval(words.next().ok_or("expect count after ':'")?)?;
             for _ in 1..len {
-                tokens.push(TokenKind::INDICATE(IndicateKind::float(float)), nol, fi);
+                tokens.push(TokenKind::INDICATE(IndicateKind::double(double)), nol, fi);
             }
-
         } else {
-            float = word.parse::<f32>().unwrap();
-            tokens.push(TokenKind::INDICATE(IndicateKind::float(float)), nol, fi);
+            double = parse(word)?;
+            tokens.push(TokenKind::INDICATE(IndicateKind::double(double)), nol, fi);
         }
     };
+    Ok(())
 }
 
-fn indicate_space(word: &str)
-    -> u32
-{
-    let mut word = word.to_string();
-
-    if word.starts_with("0x") {
-        let mut hex: u32 = 0;
-        word.remove(0);
-        word.remove(0);  // Delete "0x"
-
-        for h in word.chars() {
-            hex = match h {
-                '0'..='9' => (hex << 4) + (h as u8 - b'0') as u32,
-                'a'..='f' => (hex << 4) + (h as u8 - b'a' + 10) as u32,
-                'A'..='F' => (hex << 4) + (h as u8 - b'A' + 10) as u32,
-                _ => panic!("indicate_space(): invalid number: 0x{}", word),
-            }
+/// `.eqv NAME text` (textual substitution like MARS) or `.set NAME, expression`.
+/// `.set` without a value (e.g. `.set noreorder`) is ignored.
+/// Return: true if `line` is a definition
+fn define(line: &str, constants: &mut BTreeMap<String, String>) -> Result<bool, String> {
+    let line = line.trim_start();
+    let (directive, rest) = line.split_at(line.find(char::is_whitespace).unwrap_or(line.len()));
+    if directive != ".eqv" && directive != ".set" {
+        return Ok(false);
+    }
+
+    let rest = rest.trim_start();
+    let (name, text) = rest.split_at(rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len()));
+    if !is_identifier(name) {
+        return Err(format!("{}: invalid name: {}", directive, line));
+    }
+    let text = substitute(text.trim_start().strip_prefix(',').unwrap_or(text), constants);
+    let text = text.trim();
+
+    match directive {
+        ".set" if text.is_empty() => (),
+        ".set" => { constants.insert(name.to_string(), expr::eval(text)?.to_string()); },
+        _ if text.is_empty() => return Err(format!(".eqv: expect text: {}", line)),
+        _ => { constants.insert(name.to_string(), text.to_string()); },
+    }
+    Ok(true)
+}
+
+/// Replace the names of `constants` in `line`.  Strings, characters and the comment are not replaced.
+fn substitute(line: &str, constants: &BTreeMap<String, String>) -> String {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$' | '%');
+    let mut result = String::new();
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((i, ch)) = chars.next() {
+        match ch {
+            '#' => break,
+            '"' | '\'' => {
+                result.push(ch);
+                while let Some((_, ch2)) = chars.next() {
+                    result.push(ch2);
+                    if ch2 == '\\' {
+                        if let Some((_, ch3)) = chars.next() {
+                            result.push(ch3);
+                        }
+                    } else if ch2 == ch {
+                        break;
+                    }
+                }
+            },
+            _ if is_word(ch) => {
+                let mut end = i + ch.len_utf8();
+                while let Some(&(j, ch2)) = chars.peek() {
+                    if !is_word(ch2) {
+                        break;
+                    }
+                    end = j + ch2.len_utf8();
+                    chars.next();
+                }
+                let word = &line[i..end];
+                result.push_str(constants.get(word).map_or(word, |text| text));
+            },
+            _ => result.push(ch),
         }
+    }
 
-        hex
+    result
+}
 
-    } else if let Ok(num) = word.parse::<u32>() {
-        num
+/// Rejoin an expression split by white space.  e.g. `SIZE * (4 + 1)` => `SIZE*(4+1)`
+/// A word which starts with a sign is another operand.  e.g. `.word 1 -1`
+fn join_expressions(words: Vec<String>) -> Vec<String> {
+    let continues = |last: &str, word: &str| {
+        !last.starts_with('"') && !word.starts_with('"') && (
+            matches!(last.as_bytes().last(), Some(b'+' | b'-' | b'*' | b'/' | b'<' | b'>' | b'&' | b'|' | b'~' | b'(')) ||
+            matches!(word.as_bytes().first(), Some(b'*' | b'/' | b'<' | b'>' | b'&' | b'|' | b')')) ||
+            word == "+" || word == "-")
+    };
 
-    } else {
-        panic!("indicate_space(): invalid number: {}", word);
+    let mut joined: Vec<String> = Vec::new();
+    for word in words {
+        match joined.last_mut() {
+            Some(last) if continues(last, &word) => last.push_str(&word),
+            _ => joined.push(word),
+        }
     }
+    joined
 }
 
 fn split_words(line: &str) -> Vec<String> {
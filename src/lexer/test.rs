@@ -179,10 +179,10 @@ RST
     assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::BGT));
     assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::BLE));
     assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::BLT));
-    assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::BGE));
-    assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::BGT));
-    assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::BLE));
-    assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::BLT));
+    assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::BGEU));
+    assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::BGTU));
+    assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::BLEU));
+    assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::BLTU));
     assert_eq!(tokens.consume_kind(), TokenKind::EOL);
     assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::BEQZ));
     assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::BGEZ));
@@ -192,16 +192,16 @@ RST
     assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::BNEZ));
     assert_eq!(tokens.consume_kind(), TokenKind::EOL);
     assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::SLT));
-    assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::SLT));
-    assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::SLTI));
+    assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::SLTU));
     assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::SLTI));
+    assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::SLTIU));
     assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::SEQ));
     assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::SGE));
-    assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::SGE));
-    assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::SGT));
+    assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::SGEU));
     assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::SGT));
+    assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::SGTU));
     assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::SLE));
-    assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::SLE));
+    assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::SLEU));
     assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::SNE));
     assert_eq!(tokens.consume_kind(), TokenKind::EOL);
     assert_eq!(tokens.consume_kind(), TokenKind::INSTRUCTION(InstructionKind::REM));
@@ -407,6 +407,99 @@ main: ## Start of code section\n
     assert_eq!(tokens.consume_kind(), TokenKind::EOL);
 }
 
+#[test]
+#[cfg(test)]
+fn test_tokenize_memory() {
+    use super::tokenize;
+    use crate::token::register::RegisterKind;
+
+    let mut tokens: Tokens = Tokens::new();
+    tokenize(1, 0, "lw $ra, -4($sp)", &mut tokens).unwrap();
+    tokenize(2, 0, "sw $t0, 0x10($t1)", &mut tokens).unwrap();
+    tokenize(3, 0, "lb $t0, x+8($t2)", &mut tokens).unwrap();
+    tokenize(4, 0, "lh $t0, x-2", &mut tokens).unwrap();
+    tokenize(5, 0, "lw $t0, 0x10000000", &mut tokens).unwrap();
+
+    let kinds: Vec<TokenKind> = (0..tokens.len()).map(|_| tokens.consume_kind())
+        .filter(|kind| matches!(kind, TokenKind::MEMORY(..) | TokenKind::DATA(..) | TokenKind::INTEGER(_)))
+        .collect();
+    assert_eq!(kinds, [
+        TokenKind::MEMORY(RegisterKind::sp, 29, -4),
+        TokenKind::MEMORY(RegisterKind::t1, 9, 0x10),
+        TokenKind::DATA(RegisterKind::t2, 10, "x".to_string(), 8),
+        TokenKind::DATA(RegisterKind::zero, 0, "x".to_string(), -2),
+        TokenKind::INTEGER(0x10000000),
+    ]);
+}
+
+#[test]
+#[cfg(test)]
+fn test_tokenize_expression() {
+    use super::tokenize;
+    use crate::token::register::RegisterKind;
+
+    let mut tokens: Tokens = Tokens::new();
+    tokenize(1, 0, ".eqv SIZE 10", &mut tokens).unwrap();
+    tokenize(2, 0, ".set MASK, (1 << 4) - 1  # 15", &mut tokens).unwrap();
+    tokenize(3, 0, "li $t0, SIZE * 4 + 1", &mut tokens).unwrap();
+    tokenize(4, 0, "li $t0, MASK & ~0x3 | 0x100", &mut tokens).unwrap();
+    tokenize(5, 0, "li $t0, 0xffffffff >> 4", &mut tokens).unwrap();
+    tokenize(6, 0, "lw $t0, SIZE*4($sp)", &mut tokens).unwrap();
+    tokenize(7, 0, "lw $t0, x + SIZE", &mut tokens).unwrap();
+    tokenize(8, 0, "lui $t0, %hi(x+4)", &mut tokens).unwrap();
+    tokenize(9, 0, "lw $t0, %lo(x)($t0)", &mut tokens).unwrap();
+    tokenize(10, 0, "addiu $t0, $t0, %lo(0x1234abcd)", &mut tokens).unwrap();
+    tokenize(11, 0, ".word SIZE, -1 -2, 1:SIZE/5", &mut tokens).unwrap();
+    tokenize(12, 0, ".space SIZE*4", &mut tokens).unwrap();
+
+    let kinds: Vec<TokenKind> = (0..tokens.len()).map(|_| tokens.consume_kind())
+        .filter(|kind| !matches!(kind, TokenKind::INSTRUCTION(_) | TokenKind::REGISTER(..) | TokenKind::EOL))
+        .collect();
+    assert_eq!(kinds, [
+        TokenKind::INTEGER(41),
+        TokenKind::INTEGER(0x10c),
+        TokenKind::INTEGER(0x0fffffff),
+        TokenKind::MEMORY(RegisterKind::sp, 29, 40),
+        TokenKind::DATA(RegisterKind::zero, 0, "x".to_string(), 10),
+        TokenKind::RELOCATION(Relocation::hi, "x".to_string(), 4, None),
+        TokenKind::RELOCATION(Relocation::lo, "x".to_string(), 0, Some(8)),
+        TokenKind::INTEGER(-0x5433),
+        TokenKind::INDICATE(IndicateKind::word(10)),
+        TokenKind::INDICATE(IndicateKind::word(-1i32 as u32)),
+        TokenKind::INDICATE(IndicateKind::word(-2i32 as u32)),
+        TokenKind::INDICATE(IndicateKind::word(1)),
+        TokenKind::INDICATE(IndicateKind::word(1)),
+        TokenKind::INDICATE(IndicateKind::space(40)),
+    ]);
+
+    assert!(tokenize(13, 0, "li $t0, 1/0", &mut tokens).is_err());
+    assert!(tokenize(14, 0, ".eqv 1x 2", &mut tokens).is_err());
+}
+
+#[test]
+#[cfg(test)]
+fn test_tokenize_float() {
+    use super::tokenize;
+
+    let mut tokens: Tokens = Tokens::new();
+    tokenize(1, 0, ".float 1.5, -2:2", &mut tokens).unwrap();
+    tokenize(2, 0, ".double 0.1", &mut tokens).unwrap();
+
+    let kinds: Vec<TokenKind> = (0..tokens.len()).map(|_| tokens.consume_kind())
+        .filter(|kind| *kind != TokenKind::EOL)
+        .collect();
+    assert_eq!(kinds, [
+        TokenKind::INDICATE(IndicateKind::float(1.5)),
+        TokenKind::INDICATE(IndicateKind::float(-2.0)),
+        TokenKind::INDICATE(IndicateKind::float(-2.0)),
+        TokenKind::INDICATE(IndicateKind::double(0.1)),
+    ]);
+
+    assert_eq!(tokenize(3, 0, ".double abc", &mut tokens), Err(".double: invalid value: abc".to_string()));
+    assert_eq!(tokenize(4, 0, ".float 1.2.3", &mut tokens), Err(".float: invalid value: 1.2.3".to_string()));
+    assert!(tokenize(5, 0, ".float 1.0:", &mut tokens).is_err());
+}
+
 #[cfg(test)]
 impl Tokens {
     pub fn consume_kind(&mut self) -> TokenKind {
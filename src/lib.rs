@@ -0,0 +1,22 @@
+//! A MIPS-32 interpreter(simulator)
+//!
+//! ```
+//! use mipsi::{Simulator, RegisterKind};
+//!
+//! let mut simulator = Simulator::new();
+//! simulator.load("main.asm", "li $t0, 42\naddi $t1, $t0, 1\n").unwrap();
+//! simulator.run().unwrap();
+//! assert_eq!(simulator.register(RegisterKind::t1), 43);
+//! ```
+
+pub mod assembler;
+pub mod elf;
+pub mod lexer;
+pub mod parser;
+pub mod token;
+mod simulator;
+
+pub use simulator::{Simulator, Checkpoint, Execution};
+pub use parser::Status;
+pub use token::register::RegisterKind;
+pub use token::memory::Endian;
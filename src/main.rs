@@ -1,29 +1,136 @@
 mod cli;
-mod lexer;
-mod parser;
 mod repl;
-mod token;
 
 use std::path::PathBuf;
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser)]
-#[command(version, about)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     #[arg(value_name = "FILE")]
     files: Vec<PathBuf>,
+
+    /// Fetch, decode and execute the machine code instead of the source
+    #[arg(long)]
+    machine: bool,
+
+    /// Byte order of data, machine code and loads/stores
+    #[arg(long, value_enum, default_value_t = Endian::Big)]
+    endian: Endian,
 }
 
-fn main() {
-    let cli = Cli::parse();
+#[derive(Subcommand)]
+enum Command {
+    /// Run source files or a statically linked ELF32 MIPS executable
+    Run {
+        #[arg(value_name = "FILE", required = true)]
+        files: Vec<PathBuf>,
+
+        /// Fetch, decode and execute the machine code instead of the source
+        #[arg(long)]
+        machine: bool,
+
+        /// Byte order of data, machine code and loads/stores (ELF executables use their own)
+        #[arg(long, value_enum, default_value_t = Endian::Big)]
+        endian: Endian,
+
+        /// Raise the timer interrupt (hardware interrupt 5) after N instructions
+        #[arg(long, value_name = "N")]
+        timer: Option<u32>,
+
+        /// Directory which the file syscalls (13-16) can access (no files without it)
+        #[arg(long, value_name = "DIR")]
+        fs_root: Option<PathBuf>,
+
+        /// Time (30) starts at 0 ms and only sleep (32) advances it
+        #[arg(long)]
+        virtual_clock: bool,
+
+        /// Seed of the random generators (40-44) which are not seeded by the program
+        #[arg(long, value_name = "N")]
+        seed: Option<u64>,
+    },
+
+    /// Assemble the .text segment into MIPS32 machine code
+    Asm {
+        #[arg(value_name = "FILE", required = true)]
+        files: Vec<PathBuf>,
+
+        /// Output file
+        #[arg(short, long, value_name = "FILE", default_value = "a.bin")]
+        output: PathBuf,
+
+        /// Byte order of the output
+        #[arg(short, long, value_enum, default_value_t = Endian::Big)]
+        endian: Endian,
+    },
+
+    /// Disassemble a raw binary or hex text of MIPS32 machine code
+    Disasm {
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Input format
+        #[arg(short, long, value_enum, default_value_t = Format::Auto)]
+        format: Format,
 
-    // REPL
-    if cli.files.is_empty() {
-        repl::run();
-        return;
+        /// Byte order of binary (and byte-wise hex)
+        #[arg(short, long, value_enum, default_value_t = Endian::Big)]
+        endian: Endian,
+
+        /// Address of the first word
+        #[arg(short, long, value_name = "ADDR", default_value = "0x00400000", value_parser = parse_address)]
+        base: u32,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    /// Hex text if the file is only hex digits, otherwise binary
+    Auto,
+    Bin,
+    Hex,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Endian {
+    Big,
+    Little,
+}
+
+impl From<Endian> for mipsi::Endian {
+    fn from(endian: Endian) -> Self {
+        match endian {
+            Endian::Big    => mipsi::Endian::Big,
+            Endian::Little => mipsi::Endian::Little,
+        }
     }
+}
 
-    // CLI
-    cli::run(&cli.files);
+fn parse_address(s: &str) -> Result<u32, String> {
+    let result = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse::<u32>(),
+    };
+    result.map_err(|e| e.to_string())
 }
 
+fn main() {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Command::Run { files, machine, endian, timer, fs_root, virtual_clock, seed }) =>
+            cli::run(&files, machine, endian.into(), timer, fs_root.as_deref(), virtual_clock, seed),
+        Some(Command::Asm { files, output, endian }) => cli::asm(&files, &output, endian.into()),
+        Some(Command::Disasm { file, format, endian, base }) => cli::disasm(&file, format, endian.into(), base),
+
+        // REPL
+        None if cli.files.is_empty() => repl::run(cli.machine, cli.endian.into()),
+
+        // CLI
+        None => cli::run(&cli.files, cli.machine, cli.endian.into(), None, None, false, None),
+    }
+}
@@ -0,0 +1,1107 @@
+//! One test per `InstructionKind`: the effects on registers, hi/lo and memory (MIPS32),
+//! in both execution modes
+
+use crate::{Simulator, Execution};
+use crate::token::InstructionKind;
+use crate::token::register::RegisterKind::{self, *};
+use crate::token::memory::STATIC_DATA;
+use crate::token::console::BufferConsole;
+
+/// Source and its expected effects
+#[derive(Default)]
+struct Case {
+    source: &'static str,
+    input: &'static str,
+    registers: Vec<(RegisterKind, i32)>,
+    singles: Vec<(usize, f32)>,      // ($fN, value)
+    doubles: Vec<(usize, f64)>,      // ($fN, value)
+    fcc: Vec<(usize, bool)>,         // (cc, value)
+    hilo: Option<(u32, u32)>,
+    memory: Vec<(u32, usize, u64)>,  // (address, byte, value)
+    output: Option<&'static str>,
+    error: Option<&'static str>,
+}
+
+fn case(source: &'static str) -> Case {
+    Case { source, ..Case::default() }
+}
+
+impl Case {
+    fn input(mut self, input: &'static str) -> Self {
+        self.input = input;
+        self
+    }
+
+    fn register(mut self, register: RegisterKind, value: i32) -> Self {
+        self.registers.push((register, value));
+        self
+    }
+
+    fn single(mut self, n: usize, value: f32) -> Self {
+        self.singles.push((n, value));
+        self
+    }
+
+    fn double(mut self, n: usize, value: f64) -> Self {
+        self.doubles.push((n, value));
+        self
+    }
+
+    fn fcc(mut self, cc: usize, value: bool) -> Self {
+        self.fcc.push((cc, value));
+        self
+    }
+
+    fn hilo(mut self, hi: u32, lo: u32) -> Self {
+        self.hilo = Some((hi, lo));
+        self
+    }
+
+    fn memory(mut self, address: u32, byte: usize, value: u64) -> Self {
+        self.memory.push((address, byte, value));
+        self
+    }
+
+    fn output(mut self, output: &'static str) -> Self {
+        self.output = Some(output);
+        self
+    }
+
+    /// The run stops with `error` (no exception handler)
+    fn error(mut self, error: &'static str) -> Self {
+        self.error = Some(error);
+        self
+    }
+
+    fn check(&self, kind: InstructionKind) {
+        for execution in [Execution::Source, Execution::Machine] {
+            let console = BufferConsole::new(self.input);
+            let mut simulator = Simulator::new();
+            simulator.set_execution(execution);
+            simulator.set_console(Box::new(console.clone()));
+            simulator.load("main.asm", self.source).unwrap();
+            simulator.assemble().unwrap();
+            assert!(simulator.program().instructions.iter().any(|i| i.kind == kind), "{:?} is not in the source", kind);
+
+            let result = simulator.run().map(|_| ()).map_err(|e| e.to_string());
+            assert_eq!(result, self.error.map_or(Ok(()), |e| Err(e.to_string())), "{:?}", execution);
+
+            let registers = simulator.registers();
+            for (register, value) in &self.registers {
+                assert_eq!(registers[register.clone()], *value, "{:?}: {:?}", execution, register);
+            }
+            for &(n, value) in &self.singles {
+                let actual = f32::from_bits(registers[32 + n] as u32);
+                assert!(actual.to_bits() == value.to_bits() || actual.is_nan() && value.is_nan(),
+                    "{:?}: $f{}: {} != {}", execution, n, actual, value);
+            }
+            for &(n, value) in &self.doubles {
+                let actual = registers.double(32 + n);
+                assert!(actual.to_bits() == value.to_bits() || actual.is_nan() && value.is_nan(),
+                    "{:?}: $f{}: {} != {}", execution, n, actual, value);
+            }
+            for &(cc, value) in &self.fcc {
+                assert_eq!(registers.fcc(cc), value, "{:?}: cc {}", execution, cc);
+            }
+            if let Some(hilo) = self.hilo {
+                assert_eq!((simulator.hi(), simulator.lo()), hilo, "{:?}: hi, lo", execution);
+            }
+            for &(address, byte, value) in &self.memory {
+                let actual = match byte {
+                    8 => simulator.memory().load_u64(address),
+                    _ => simulator.memory().load(address, byte).map(u64::from),
+                };
+                assert_eq!(actual, Ok(value), "{:?}: 0x{:08x}", execution, address);
+            }
+            if let Some(output) = self.output {
+                assert_eq!(console.output(), output, "{:?}", execution);
+            }
+        }
+    }
+}
+
+/// Single precision operands: $f0 = -1.5, $f1 = 2.0, $f2 = 0.1, $f3 = 4.0
+macro_rules! singles {
+    ($source:expr) => {
+        concat!("\
+.data
+x:  .float -1.5, 2.0, 0.1, 4.0
+.text
+    la      $t0, x
+    lwc1    $f0, 0($t0)
+    lwc1    $f1, 4($t0)
+    lwc1    $f2, 8($t0)
+    lwc1    $f3, 12($t0)
+", $source)
+    };
+}
+
+/// Double precision operands: $f0 = -1.5, $f2 = 2.0, $f4 = 0.1, $f6 = 4.0
+macro_rules! doubles {
+    ($source:expr) => {
+        concat!("\
+.data
+x:  .double -1.5, 2.0, 0.1, 4.0
+.text
+    la      $t0, x
+    ldc1    $f0, 0($t0)
+    ldc1    $f2, 8($t0)
+    ldc1    $f4, 16($t0)
+    ldc1    $f6, 24($t0)
+", $source)
+    };
+}
+
+/// `#[test] fn KIND()` for each case.  The match fails to compile if a kind has no case.
+macro_rules! conformance {
+    ($($kind:ident => $case:expr,)*) => {
+        #[allow(dead_code)]
+        fn exhaustive(kind: InstructionKind) {
+            match kind {
+                $(InstructionKind::$kind => (),)*
+            }
+        }
+
+        $(
+            #[test]
+            #[allow(non_snake_case)]
+            fn $kind() {
+                $case.check(InstructionKind::$kind);
+            }
+        )*
+    };
+}
+
+conformance! {
+    // Arithmetic, Logic
+    ADD => case("\
+    li      $t0, 5
+    li      $t1, -7
+    add     $t2, $t0, $t1
+    li      $t0, 0x7fffffff
+    add     $t3, $t0, $t0")
+        .register(t2, -2).register(t3, 0)
+        .error("main.asm:5: panicked at 'arithmetic operation overflowed'"),
+    ADDU => case("\
+    li      $t0, 0x7fffffff
+    addu    $t1, $t0, $t0")
+        .register(t1, -2),
+    ADDI => case("\
+    li      $t0, 5
+    addi    $t1, $t0, -8
+    li      $t0, 0x7fffffff
+    addi    $t2, $t0, 1")
+        .register(t1, -3).register(t2, 0)
+        .error("main.asm:4: panicked at 'arithmetic operation overflowed'"),
+    ADDIU => case("\
+    li      $t0, 0x7fffffff
+    addiu   $t1, $t0, 1")
+        .register(t1, i32::MIN),
+    SUB => case("\
+    li      $t0, 3
+    li      $t1, 5
+    sub     $t2, $t0, $t1
+    lui     $t0, 0x8000
+    sub     $t3, $t0, $t1")
+        .register(t2, -2).register(t3, 0)
+        .error("main.asm:5: panicked at 'arithmetic operation overflowed'"),
+    SUBU => case("\
+    lui     $t0, 0x8000
+    li      $t1, 1
+    subu    $t2, $t0, $t1")
+        .register(t2, i32::MAX),
+    MUL => case("\
+    li      $t0, -3
+    li      $t1, 7
+    mul     $t2, $t0, $t1
+    li      $t0, 0x10000
+    mul     $t3, $t0, $t0")
+        .register(t2, -21).register(t3, 0),
+    REM => case("\
+    li      $t0, -7
+    li      $t1, 2
+    rem     $t2, $t0, $t1
+    rem     $t3, $t0, $zero")
+        .register(t2, -1).hilo(-1i32 as u32, -3i32 as u32)
+        .error("main.asm:4: break: 0"),
+    REMU => case("\
+    li      $t0, -7
+    li      $t1, 2
+    remu    $t2, $t0, $t1")
+        .register(t2, 1).hilo(1, 0x7ffffffc),
+    MULO => case("\
+    li      $t0, 0x10000
+    li      $t1, 0x7fff
+    mulo    $t2, $t0, $t1
+    mulo    $t3, $t0, $t0")
+        .register(t2, 0x7fff0000).hilo(1, 0)
+        .error("main.asm:4: break: 0"),
+    MULOU => case("\
+    li      $t0, 0xffff
+    li      $t1, 0x10001
+    mulou   $t2, $t0, $t1
+    mulou   $t3, $t1, $t1")
+        .register(t2, -1).hilo(1, 0x00020001)
+        .error("main.asm:4: break: 0"),
+    CLO => case("\
+    lui     $t0, 0xf000
+    clo     $t1, $t0
+    clo     $t2, $zero")
+        .register(t1, 4).register(t2, 0),
+    CLZ => case("\
+    li      $t0, 1
+    clz     $t1, $t0
+    clz     $t2, $zero")
+        .register(t1, 31).register(t2, 32),
+    ROR => case("\
+    li      $t0, 0x12345678
+    ror     $t1, $t0, 8
+    li      $t2, 36
+    ror     $t3, $t0, $t2")
+        .register(t1, 0x78123456).register(t3, 0x81234567u32 as i32),
+    ROL => case("\
+    li      $t0, 0x12345678
+    rol     $t1, $t0, 8
+    li      $t2, 36
+    rol     $t3, $t0, $t2")
+        .register(t1, 0x34567812).register(t3, 0x23456781),
+    DIV => case("\
+    li      $t0, -7
+    li      $t1, 2
+    div     $t2, $t0, $t1
+    div     $t0, $t1")
+        .register(t2, -3).hilo(-1i32 as u32, -3i32 as u32),
+    DIVU => case("\
+    li      $t0, -7
+    li      $t1, 2
+    divu    $t2, $t0, $t1
+    divu    $t0, $t1")
+        .register(t2, 0x7ffffffc).hilo(1, 0x7ffffffc),
+    MULT => case("\
+    li      $t0, -2
+    li      $t1, 3
+    mult    $t0, $t1")
+        .hilo(0xffffffff, -6i32 as u32),
+    MULTU => case("\
+    li      $t0, -2
+    li      $t1, 3
+    multu   $t0, $t1")
+        .hilo(2, -6i32 as u32),
+    MADD => case("\
+    li      $t0, 5
+    mtlo    $t0
+    li      $t0, -2
+    li      $t1, 3
+    madd    $t0, $t1")
+        .hilo(0xffffffff, 0xffffffff),
+    MADDU => case("\
+    li      $t0, -1
+    mtlo    $t0
+    li      $t0, 1
+    maddu   $t0, $t0")
+        .hilo(1, 0),
+    MSUB => case("\
+    li      $t0, -2
+    li      $t1, 3
+    msub    $t0, $t1")
+        .hilo(0, 6),
+    MSUBU => case("\
+    li      $t0, 1
+    msubu   $t0, $t0")
+        .hilo(0xffffffff, 0xffffffff),
+    NOR => case("\
+    li      $t0, 0xf0
+    li      $t1, 0x0f
+    nor     $t2, $t0, $t1")
+        .register(t2, -256),
+    NOT => case("\
+    li      $t0, 0x0f0f
+    not     $t1, $t0")
+        .register(t1, !0x0f0f),
+    NEG => case("\
+    li      $t0, 5
+    neg     $t1, $t0
+    lui     $t0, 0x8000
+    neg     $t2, $t0")
+        .register(t1, -5).register(t2, 0)
+        .error("main.asm:4: panicked at 'arithmetic operation overflowed'"),
+    NEGU => case("\
+    lui     $t0, 0x8000
+    negu    $t1, $t0")
+        .register(t1, i32::MIN),
+    SLL => case("\
+    li      $t0, 0x40000001
+    sll     $t1, $t0, 1")
+        .register(t1, 0x80000002u32 as i32),
+    SLLV => case("\
+    li      $t0, 1
+    li      $t1, 33
+    sllv    $t2, $t0, $t1")
+        .register(t2, 2),
+    SRA => case("\
+    lui     $t0, 0x8000
+    sra     $t1, $t0, 4")
+        .register(t1, 0xf8000000u32 as i32),
+    SRAV => case("\
+    lui     $t0, 0x8000
+    li      $t1, 36
+    srav    $t2, $t0, $t1")
+        .register(t2, 0xf8000000u32 as i32),
+    SRL => case("\
+    lui     $t0, 0x8000
+    srl     $t1, $t0, 4")
+        .register(t1, 0x08000000),
+    SRLV => case("\
+    lui     $t0, 0x8000
+    li      $t1, 35
+    srlv    $t2, $t0, $t1")
+        .register(t2, 0x10000000),
+    AND => case("\
+    li      $t0, 0xff00
+    li      $t1, 0x0ff0
+    and     $t2, $t0, $t1")
+        .register(t2, 0x0f00),
+    ANDI => case("\
+    li      $t0, -1
+    andi    $t1, $t0, 0xffff")
+        .register(t1, 0xffff),
+    OR => case("\
+    li      $t0, 0xff00
+    li      $t1, 0x0ff0
+    or      $t2, $t0, $t1")
+        .register(t2, 0xfff0),
+    ORI => case("\
+    lui     $t0, 0x1234
+    ori     $t1, $t0, 0x8000")
+        .register(t1, 0x12348000),
+    XOR => case("\
+    li      $t0, 0xff00
+    li      $t1, 0x0ff0
+    xor     $t2, $t0, $t1")
+        .register(t2, 0xf0f0),
+    XORI => case("\
+    li      $t0, -1
+    xori    $t1, $t0, 0xffff")
+        .register(t1, 0xffff0000u32 as i32),
+
+    // Constant
+    LI => case("\
+    li      $t0, -1
+    li      $t1, 0x12345678")
+        .register(t0, -1).register(t1, 0x12345678),
+    LUI => case("\
+    lui     $t0, 0xffff")
+        .register(t0, 0xffff0000u32 as i32),
+
+    // Comparison
+    SLT => case("\
+    li      $t0, -1
+    li      $t1, 1
+    slt     $t2, $t0, $t1
+    slt     $t3, $t1, $t0")
+        .register(t2, 1).register(t3, 0),
+    SLTI => case("\
+    li      $t0, -1
+    slti    $t1, $t0, 0
+    slti    $t2, $t0, -1")
+        .register(t1, 1).register(t2, 0),
+    SLTU => case("\
+    li      $t0, -1
+    li      $t1, 1
+    sltu    $t2, $t0, $t1
+    sltu    $t3, $t1, $t0")
+        .register(t2, 0).register(t3, 1),
+    SLTIU => case("\
+    li      $t0, 1
+    sltiu   $t1, $t0, -1
+    sltiu   $t2, $t0, 1")
+        .register(t1, 1).register(t2, 0),
+    SEQ => case("\
+    li      $t0, 5
+    li      $t1, 5
+    seq     $t2, $t0, $t1
+    seq     $t3, $t0, $zero")
+        .register(t2, 1).register(t3, 0),
+    SGE => case("\
+    li      $t0, -1
+    li      $t1, 1
+    sge     $t2, $t0, $t1
+    sge     $t3, $t0, $t0")
+        .register(t2, 0).register(t3, 1),
+    SGEU => case("\
+    li      $t0, -1
+    li      $t1, 1
+    sgeu    $t2, $t0, $t1
+    sgeu    $t3, $t1, $t0")
+        .register(t2, 1).register(t3, 0),
+    SGT => case("\
+    li      $t0, -1
+    li      $t1, 1
+    sgt     $t2, $t1, $t0
+    sgt     $t3, $t0, $t0")
+        .register(t2, 1).register(t3, 0),
+    SGTU => case("\
+    li      $t0, -1
+    li      $t1, 1
+    sgtu    $t2, $t1, $t0
+    sgtu    $t3, $t0, $t1")
+        .register(t2, 0).register(t3, 1),
+    SLE => case("\
+    li      $t0, -1
+    li      $t1, 1
+    sle     $t2, $t0, $t1
+    sle     $t3, $t1, $t0")
+        .register(t2, 1).register(t3, 0),
+    SLEU => case("\
+    li      $t0, -1
+    li      $t1, 1
+    sleu    $t2, $t0, $t1
+    sleu    $t3, $t1, $t0")
+        .register(t2, 0).register(t3, 1),
+    SNE => case("\
+    li      $t0, 5
+    li      $t1, 5
+    sne     $t2, $t0, $t1
+    sne     $t3, $t0, $zero")
+        .register(t2, 0).register(t3, 1),
+
+    // Branch: the first is taken, the second is not
+    B => case("\
+    b       l1
+    li      $t2, 1
+l1:")
+        .register(t2, 0),
+    BEQ => case("\
+    li      $t0, -1
+    li      $t1, 1
+    beq     $t0, $t0, l1
+    li      $t2, 1
+l1: beq     $t0, $t1, l2
+    li      $t3, 1
+l2:")
+        .register(t2, 0).register(t3, 1),
+    BNE => case("\
+    li      $t0, -1
+    li      $t1, 1
+    bne     $t0, $t1, l1
+    li      $t2, 1
+l1: bne     $t0, $t0, l2
+    li      $t3, 1
+l2:")
+        .register(t2, 0).register(t3, 1),
+    BGE => case("\
+    li      $t0, -1
+    li      $t1, 1
+    bge     $t1, $t0, l1
+    li      $t2, 1
+l1: bge     $t0, $t1, l2
+    li      $t3, 1
+l2:")
+        .register(t2, 0).register(t3, 1),
+    BGEU => case("\
+    li      $t0, -1
+    li      $t1, 1
+    bgeu    $t0, $t1, l1
+    li      $t2, 1
+l1: bgeu    $t1, $t0, l2
+    li      $t3, 1
+l2:")
+        .register(t2, 0).register(t3, 1),
+    BGT => case("\
+    li      $t0, -1
+    li      $t1, 1
+    bgt     $t1, $t0, l1
+    li      $t2, 1
+l1: bgt     $t0, $t0, l2
+    li      $t3, 1
+l2:")
+        .register(t2, 0).register(t3, 1),
+    BGTU => case("\
+    li      $t0, -1
+    li      $t1, 1
+    bgtu    $t0, $t1, l1
+    li      $t2, 1
+l1: bgtu    $t1, $t0, l2
+    li      $t3, 1
+l2:")
+        .register(t2, 0).register(t3, 1),
+    BLE => case("\
+    li      $t0, -1
+    li      $t1, 1
+    ble     $t0, $t1, l1
+    li      $t2, 1
+l1: ble     $t1, $t0, l2
+    li      $t3, 1
+l2:")
+        .register(t2, 0).register(t3, 1),
+    BLEU => case("\
+    li      $t0, -1
+    li      $t1, 1
+    bleu    $t1, $t0, l1
+    li      $t2, 1
+l1: bleu    $t0, $t1, l2
+    li      $t3, 1
+l2:")
+        .register(t2, 0).register(t3, 1),
+    BLT => case("\
+    li      $t0, -1
+    li      $t1, 1
+    blt     $t0, $t1, l1
+    li      $t2, 1
+l1: blt     $t1, $t0, l2
+    li      $t3, 1
+l2:")
+        .register(t2, 0).register(t3, 1),
+    BLTU => case("\
+    li      $t0, -1
+    li      $t1, 1
+    bltu    $t1, $t0, l1
+    li      $t2, 1
+l1: bltu    $t0, $t1, l2
+    li      $t3, 1
+l2:")
+        .register(t2, 0).register(t3, 1),
+    BEQZ => case("\
+    li      $t1, 1
+    beqz    $zero, l1
+    li      $t2, 1
+l1: beqz    $t1, l2
+    li      $t3, 1
+l2:")
+        .register(t2, 0).register(t3, 1),
+    BGEZ => case("\
+    li      $t0, -1
+    bgez    $zero, l1
+    li      $t2, 1
+l1: bgez    $t0, l2
+    li      $t3, 1
+l2:")
+        .register(t2, 0).register(t3, 1),
+    BGTZ => case("\
+    li      $t1, 1
+    bgtz    $t1, l1
+    li      $t2, 1
+l1: bgtz    $zero, l2
+    li      $t3, 1
+l2:")
+        .register(t2, 0).register(t3, 1),
+    BLEZ => case("\
+    li      $t1, 1
+    blez    $zero, l1
+    li      $t2, 1
+l1: blez    $t1, l2
+    li      $t3, 1
+l2:")
+        .register(t2, 0).register(t3, 1),
+    BLTZ => case("\
+    li      $t0, -1
+    bltz    $t0, l1
+    li      $t2, 1
+l1: bltz    $zero, l2
+    li      $t3, 1
+l2:")
+        .register(t2, 0).register(t3, 1),
+    BNEZ => case("\
+    li      $t0, -1
+    bnez    $t0, l1
+    li      $t2, 1
+l1: bnez    $zero, l2
+    li      $t3, 1
+l2:")
+        .register(t2, 0).register(t3, 1),
+    BGEZAL => case("\
+    li      $t0, -1
+    bgezal  $zero, l1
+    li      $t2, 1
+l1: bgezal  $t0, l2
+    li      $t3, 1
+l2:")
+        .register(t2, 0).register(t3, 1).register(ra, 0x00400010),
+    BLTZAL => case("\
+    li      $t0, -1
+    bltzal  $t0, l1
+    li      $t2, 1
+l1: bltzal  $zero, l2
+    li      $t3, 1
+l2:")
+        .register(t2, 0).register(t3, 1).register(ra, 0x00400010),
+
+    // Jump
+    J => case("\
+    j       l1
+    li      $t2, 1
+l1:")
+        .register(t2, 0),
+    JAL => case("\
+    jal     l1
+    li      $t2, 1
+l1:")
+        .register(t2, 0).register(ra, 0x00400004),
+    JR => case("\
+    la      $t0, l1
+    jr      $t0
+    li      $t2, 1
+l1:")
+        .register(t2, 0),
+    JALR => case("\
+    la      $t0, l1
+    jalr    $t0
+    li      $t2, 1
+l1: la      $t0, l2
+    jalr    $t3, $t0
+l2:")
+        .register(t2, 0).register(ra, 0x0040000c).register(t3, 0x0040001c),
+
+    // Load
+    LA => case("\
+.data
+    .word 0
+x:  .word 1
+.text
+    la      $t0, x")
+        .register(t0, (STATIC_DATA + 4) as i32),
+    LB => case("\
+.data
+x:  .byte 1, 0x80
+.text
+    la      $t0, x
+    lb      $t1, 0($t0)
+    lb      $t2, 1($t0)")
+        .register(t1, 1).register(t2, -128),
+    LBU => case("\
+.data
+x:  .byte 1, 0x80
+.text
+    la      $t0, x
+    lbu     $t1, 0($t0)
+    lbu     $t2, 1($t0)")
+        .register(t1, 1).register(t2, 0x80),
+    LH => case("\
+.data
+x:  .half 1, 0x8001
+.text
+    la      $t0, x
+    lh      $t1, 0($t0)
+    lh      $t2, 2($t0)
+    lh      $t3, 1($t0)")
+        .register(t1, 1).register(t2, -0x7fff)
+        .error("main.asm:7: unaligned address: 0x10000001"),
+    LHU => case("\
+.data
+x:  .half 1, 0x8001
+.text
+    la      $t0, x
+    lhu     $t1, 0($t0)
+    lhu     $t2, 2($t0)")
+        .register(t1, 1).register(t2, 0x8001),
+    LW => case("\
+.data
+x:  .word -2
+.text
+    la      $t0, x
+    lw      $t1, 0($t0)
+    lw      $t2, 2($t0)")
+        .register(t1, -2)
+        .error("main.asm:6: unaligned address: 0x10000002"),
+
+    // Store
+    SB => case("\
+.data
+x:  .word 0
+.text
+    li      $t0, 0x1ff
+    la      $t1, x
+    sb      $t0, 1($t1)")
+        .memory(STATIC_DATA, 1, 0).memory(STATIC_DATA + 1, 1, 0xff),
+    SH => case("\
+.data
+x:  .word 0
+.text
+    li      $t0, 0x12345678
+    la      $t1, x
+    sh      $t0, 2($t1)
+    sh      $t0, 1($t1)")
+        .memory(STATIC_DATA, 2, 0).memory(STATIC_DATA + 2, 2, 0x5678)
+        .error("main.asm:7: unaligned address: 0x10000001"),
+    SW => case("\
+.data
+x:  .word 0, 0
+.text
+    li      $t0, 0x12345678
+    la      $t1, x
+    sw      $t0, 4($t1)")
+        .memory(STATIC_DATA, 4, 0).memory(STATIC_DATA + 4, 4, 0x12345678),
+
+    // Transfer
+    MOVE => case("\
+    li      $t0, -5
+    move    $t1, $t0")
+        .register(t1, -5),
+    MFHI => case("\
+    li      $t0, 7
+    mthi    $t0
+    mfhi    $t1")
+        .register(t1, 7),
+    MFLO => case("\
+    li      $t0, 7
+    mtlo    $t0
+    mflo    $t1")
+        .register(t1, 7),
+    MTHI => case("\
+    li      $t0, -1
+    mthi    $t0")
+        .hilo(0xffffffff, 0),
+    MTLO => case("\
+    li      $t0, -1
+    mtlo    $t0")
+        .hilo(0, 0xffffffff),
+    MOVN => case("\
+    li      $t0, 5
+    li      $t1, 1
+    movn    $t2, $t0, $t1
+    movn    $t3, $t0, $zero")
+        .register(t2, 5).register(t3, 0),
+    MOVZ => case("\
+    li      $t0, 5
+    li      $t1, 1
+    movz    $t2, $t0, $zero
+    movz    $t3, $t0, $t1")
+        .register(t2, 5).register(t3, 0),
+
+    // Exception, Interrupt
+    SYSCALL => case("\
+    li      $v0, 5
+    syscall
+    move    $t0, $v0
+    li      $v0, 1
+    li      $a0, -3
+    syscall
+    li      $v0, 10
+    syscall
+    li      $t1, 1")
+        .input("42\n").output("-3")
+        .register(t0, 42).register(t1, 0),
+    BREAK => case("\
+    li      $t0, 1
+    break   3")
+        .register(t0, 1)
+        .error("main.asm:2: break: 3"),
+    TEQ => case("\
+    li      $t0, 1
+    teq     $t0, $zero
+    teq     $t0, $t0")
+        .error("main.asm:3: trap: condition is true"),
+    TNE => case("\
+    li      $t0, 1
+    tne     $t0, $t0
+    tne     $t0, $zero")
+        .error("main.asm:3: trap: condition is true"),
+    TGE => case("\
+    li      $t0, -1
+    tge     $t0, $zero
+    tge     $zero, $t0")
+        .error("main.asm:3: trap: condition is true"),
+    TGEU => case("\
+    li      $t0, -1
+    tgeu    $zero, $t0
+    tgeu    $t0, $zero")
+        .error("main.asm:3: trap: condition is true"),
+    TLT => case("\
+    li      $t0, -1
+    tlt     $zero, $t0
+    tlt     $t0, $zero")
+        .error("main.asm:3: trap: condition is true"),
+    TLTU => case("\
+    li      $t0, -1
+    tltu    $t0, $zero
+    tltu    $zero, $t0")
+        .error("main.asm:3: trap: condition is true"),
+    TEQI => case("\
+    li      $t0, -1
+    teqi    $t0, 0
+    teqi    $t0, -1")
+        .error("main.asm:3: trap: condition is true"),
+    TNEI => case("\
+    li      $t0, -1
+    tnei    $t0, -1
+    tnei    $t0, 0")
+        .error("main.asm:3: trap: condition is true"),
+    TGEI => case("\
+    li      $t0, -1
+    tgei    $t0, 0
+    tgei    $t0, -1")
+        .error("main.asm:3: trap: condition is true"),
+    TGEIU => case("\
+    li      $t0, -1
+    tgeiu   $zero, -1
+    tgeiu   $t0, 0")
+        .error("main.asm:3: trap: condition is true"),
+    TLTI => case("\
+    li      $t0, -1
+    tlti    $t0, -1
+    tlti    $t0, 0")
+        .error("main.asm:3: trap: condition is true"),
+    TLTIU => case("\
+    li      $t0, -1
+    tltiu   $t0, -1
+    tltiu   $zero, -1")
+        .error("main.asm:3: trap: condition is true"),
+    // Privileged instructions run in the exception handler
+    ERET => case("\
+    la      $t0, l1
+    break
+    li      $t2, 1
+l1:
+    .ktext 0x80000180
+    mtc0    $t0, $14
+    eret")
+        .register(t2, 0),
+    MFC0 => case("\
+    break
+    .ktext 0x80000180
+    mfc0    $t1, $14")
+        .register(t1, 0x00400000),
+    MTC0 => case("\
+    li      $t0, 0x00400008
+    break
+    .ktext 0x80000180
+    mtc0    $t0, $14
+    mfc0    $t1, $14")
+        .register(t1, 0x00400008),
+    NOP => case("\
+    li      $t0, 1
+    nop
+    li      $t1, 2")
+        .register(t0, 1).register(t1, 2),
+
+    // My own
+    PRTN => case("\
+    prtn")
+        .output("\n"),
+    PRTI => case("\
+    li      $t0, -5
+    prti    $t0")
+        .output("-5"),
+    PRTH => case("\
+    li      $t0, 255
+    prth    $t0")
+        .output("ff"),
+    PRTX => case("\
+    li      $t0, 255
+    prtx    $t0")
+        .output("0xff"),
+    PRTC => case("\
+    li      $t0, 65
+    prtc    $t0")
+        .output("A"),
+    PRTS => case("\
+.data
+s:  .asciiz \"hi\"
+.text
+    prts    s")
+        .output("hi"),
+    RST => case("\
+    li      $t0, 1
+    rst
+    li      $t1, 1")
+        .register(t0, 0).register(t1, 0),
+
+    // FPU: Loads and stores
+    LDC1 => case(doubles!(""))
+        .double(0, -1.5).double(2, 2.0).double(4, 0.1).double(6, 4.0),
+    SDC1 => case(doubles!("\
+    sdc1    $f4, 0($t0)"))
+        .memory(STATIC_DATA, 8, 0.1f64.to_bits()),
+
+    // FPU: Moves
+    CFC1 => case("\
+    cfc1    $t0, $0
+    cfc1    $t1, $31")
+        .register(t0, 0x00130000).register(t1, 0),
+    CTC1 => case("\
+    li      $t0, 3
+    ctc1    $t0, $31
+    cfc1    $t1, $31
+    li      $t0, 0x1080
+    ctc1    $t0, $31")
+        .register(t1, 3)
+        .error("main.asm:5: floating point exception: inexact"),
+    MTC1 => case("\
+    li      $t0, 0x3fc00000
+    mtc1    $t0, $f1")
+        .single(1, 1.5),
+    MOVF => case(singles!("\
+    c.eq.s  $f0, $f0
+    li      $t1, 5
+    movf    $t2, $t1
+    movf    $t3, $t1, 1"))
+        .register(t2, 0).register(t3, 5),
+    MOVT => case(singles!("\
+    c.eq.s  $f0, $f0
+    li      $t1, 5
+    movt    $t2, $t1
+    movt    $t3, $t1, 1"))
+        .register(t2, 5).register(t3, 0),
+    MOVN_S => case(singles!("\
+    li      $t1, 1
+    movn.s  $f4, $f0, $t1
+    movn.s  $f5, $f0, $zero"))
+        .single(4, -1.5).single(5, 0.0),
+    MOVZ_S => case(singles!("\
+    li      $t1, 1
+    movz.s  $f4, $f0, $zero
+    movz.s  $f5, $f0, $t1"))
+        .single(4, -1.5).single(5, 0.0),
+
+    // FPU: Arithmetic (FCSR = Cause << 12 | Flags << 2)
+    ABS_S => case(singles!("\
+    abs.s   $f4, $f0"))
+        .single(4, 1.5),
+    ADD_S => case(singles!("\
+    add.s   $f4, $f0, $f1
+    add.s   $f5, $f1, $f2"))
+        .single(4, 0.5).single(5, 2.0f32 + 0.1)
+        .register(fcsr, 0x1004),
+    DIV_S => case(singles!("\
+    div.s   $f4, $f0, $f1
+    div.s   $f5, $f1, $f31"))
+        .single(4, -0.75).single(5, f32::INFINITY)
+        .register(fcsr, 0x8020),
+    MADD_S => case(singles!("\
+    madd.s  $f4, $f0, $f1, $f1"))
+        .single(4, 2.5),
+    MUL_S => case(singles!("\
+    mul.s   $f4, $f0, $f1"))
+        .single(4, -3.0),
+    NEG_S => case(singles!("\
+    neg.s   $f4, $f0
+    neg.s   $f5, $f31"))
+        .single(4, 1.5).single(5, -0.0),
+    RECIP_S => case(singles!("\
+    recip.s $f4, $f1"))
+        .single(4, 0.5),
+    RSQRT_S => case(singles!("\
+    rsqrt.s $f4, $f3"))
+        .single(4, 0.5),
+    SQRT_S => case(singles!("\
+    sqrt.s  $f4, $f3
+    sqrt.s  $f5, $f0"))
+        .single(4, 2.0).single(5, f32::NAN)
+        .register(fcsr, 0x10040),
+    SUB_S => case(singles!("\
+    sub.s   $f4, $f0, $f1"))
+        .single(4, -3.5),
+    ABS_D => case(doubles!("\
+    abs.d   $f8, $f0"))
+        .double(8, 1.5),
+    ADD_D => case(doubles!("\
+    add.d   $f8, $f0, $f2
+    add.d   $f10, $f2, $f4"))
+        .double(8, 0.5).double(10, 2.0 + 0.1)
+        .register(fcsr, 0x1004),
+    DIV_D => case(doubles!("\
+    div.d   $f8, $f0, $f2
+    div.d   $f10, $f2, $f30"))
+        .double(8, -0.75).double(10, f64::INFINITY)
+        .register(fcsr, 0x8020),
+    MOV_D => case(doubles!("\
+    mov.d   $f8, $f4"))
+        .double(8, 0.1),
+    MUL_D => case(doubles!("\
+    mul.d   $f8, $f0, $f2"))
+        .double(8, -3.0),
+    NEG_D => case(doubles!("\
+    neg.d   $f8, $f0"))
+        .double(8, 1.5),
+    SQRT_D => case(doubles!("\
+    sqrt.d  $f8, $f6
+    sqrt.d  $f10, $f0"))
+        .double(8, 2.0).double(10, f64::NAN)
+        .register(fcsr, 0x10040),
+    SUB_D => case(doubles!("\
+    sub.d   $f8, $f0, $f2"))
+        .double(8, -3.5),
+
+    // FPU: Branches and compares
+    BC1F => case(singles!("\
+    c.lt.s  $f1, $f0
+    c.lt.s  2, $f0, $f1
+    bc1f    l1
+    li      $t2, 1
+l1: bc1f    2, l2
+    li      $t3, 1
+l2:"))
+        .register(t2, 0).register(t3, 1),
+    BC1T => case(singles!("\
+    c.lt.s  $f0, $f1
+    c.lt.s  2, $f1, $f0
+    bc1t    l1
+    li      $t2, 1
+l1: bc1t    2, l2
+    li      $t3, 1
+l2:"))
+        .register(t2, 0).register(t3, 1),
+    C_EQ_S => case(singles!("\
+    c.eq.s  $f0, $f0
+    c.eq.s  1, $f0, $f1"))
+        .fcc(0, true).fcc(1, false),
+    C_LE_S => case(singles!("\
+    c.le.s  $f0, $f1
+    c.le.s  1, $f1, $f0"))
+        .fcc(0, true).fcc(1, false),
+    C_LT_S => case(singles!("\
+    c.lt.s  $f0, $f1
+    c.lt.s  1, $f0, $f0"))
+        .fcc(0, true).fcc(1, false),
+    C_EQ_D => case(doubles!("\
+    c.eq.d  $f0, $f0
+    c.eq.d  1, $f0, $f2"))
+        .fcc(0, true).fcc(1, false),
+    C_LE_D => case(doubles!("\
+    c.le.d  $f0, $f2
+    c.le.d  1, $f2, $f0"))
+        .fcc(0, true).fcc(1, false),
+    C_LT_D => case(doubles!("\
+    c.lt.d  $f0, $f2
+    c.lt.d  1, $f0, $f0"))
+        .fcc(0, true).fcc(1, false),
+
+    // FPU: Conversions
+    CVT_S_W => case("\
+    li      $t0, 16777217
+    mtc1    $t0, $f0
+    cvt.s.w $f1, $f0")
+        .single(1, 16777216.0)
+        .register(fcsr, 0x1004),
+    CVT_W_S => case(singles!("\
+    cvt.w.s $f4, $f0
+    cvt.w.s $f5, $f2"))
+        .register(f4, -2).register(f5, 0),
+    ROUND_W_S => case(singles!("\
+    round.w.s $f4, $f0"))
+        .register(f4, -2),
+    TRUNC_W_S => case(singles!("\
+    trunc.w.s $f4, $f0"))
+        .register(f4, -1),
+    CEIL_W_S => case(singles!("\
+    ceil.w.s $f4, $f0"))
+        .register(f4, -1),
+    FLOOR_W_S => case(singles!("\
+    floor.w.s $f4, $f0"))
+        .register(f4, -2),
+    CVT_D_S => case(singles!("\
+    cvt.d.s $f4, $f2"))
+        .double(4, 0.1f32 as f64),
+    CVT_D_W => case("\
+    li      $t0, -7
+    mtc1    $t0, $f0
+    cvt.d.w $f2, $f0")
+        .double(2, -7.0),
+    CVT_S_D => case(doubles!("\
+    cvt.s.d $f8, $f4"))
+        .single(8, 0.1)
+        .register(fcsr, 0x1004),
+    CVT_W_D => case(doubles!("\
+    cvt.w.d $f8, $f0"))
+        .register(f8, -2),
+}
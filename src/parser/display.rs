@@ -1,5 +1,6 @@
 use std::io::Write;
-use crate::token::register::Registers;
+use crate::token::memory::*;
+use crate::token::register::{Registers, RegisterKind};
 
 // Display help
 // Use only in REPL
@@ -47,38 +48,31 @@ pub fn display_fp_register(registers: &Registers) {
     println!("================================================================");
 }
 
-pub fn display_data_per_4byte(data: &[u8]) {
+pub fn display_data_per_4byte(memory: &Memory) {
     println!("\n====[ DATA ]====================================================");
-    for i in 0..=data.len()/16 {
-        print!(" 0x{:08x}:   ", i*16);
-        for j in 0..4 {
-            let mut int = 0;
-            for k in 0..4 {
-                if i*16+j*4+k < data.len() {
-                    int |= (data[i*16+j*4+k] as i32) << ((4-1-k) * 8);
-                }
-            }
-            if int == 0 {
-                print!("  0x{:08x}", int);
-            } else {
-                print!("  \x1b[31m0x{:08x}\x1b[m", int);
-            }
-            std::io::stdout().flush().unwrap();
-        }
-        println!();
-    }
+    display_words(memory, STATIC_DATA, memory.data_end(false));
     println!("================================================================");
 }
 
-pub fn display_stack(stack: &[u8]) {
+/// From $sp to the bottom of the stack
+pub fn display_stack(memory: &Memory) {
     println!("\n====[ STACK ]===================================================");
-    for i in 0..=stack.len()/16 {
-        print!(" 0x{:08x}:   ", i*16);
+    let sp = memory.registers[RegisterKind::sp] as u32;
+    display_words(memory, sp.max(STACK_LIMIT) & !0xf, USER_END);
+    println!("================================================================");
+}
+
+/// 16 bytes per line
+fn display_words(memory: &Memory, start: u32, end: u32) {
+    for line in (start as u64..end.max(start+1) as u64).step_by(16) {
+        print!(" 0x{:08x}:   ", line);
         for j in 0..4 {
+            let address = line as u32 + j*4;
             let mut int = 0;
             for k in 0..4 {
-                if i*16+j*4+k < stack.len() {
-                    int |= (stack[i*16+j*4+k] as i32) << ((4-1-k) * 8);
+                if address + k < end {
+                    let byte = memory.load_u8(address + k).unwrap_or(0);
+                    int |= (byte as u32) << memory.endian.shift(4, k as usize);
                 }
             }
             if int == 0 {
@@ -90,6 +84,5 @@ pub fn display_stack(stack: &[u8]) {
         }
         println!();
     }
-    println!("================================================================");
 }
 
@@ -1,456 +1,535 @@
 use super::super::token::*;
 use super::super::token::register::{Registers, RegisterKind::*};
 use super::super::token::memory::*;
-use super::super::parser::{SignExtension, get_int, get_string};
+use super::super::token::cp0::Exception;
+use super::super::token::fpu::{self, Rounding, Operation};
+use super::super::assembler::Operand;
+use super::super::assembler::encode::condition_code;
+use super::super::parser::{SignExtension, get_int};
 
-use std::io::Write;
 use std::error::Error;
 
 type Result<T> = std::result::Result<T, Box<dyn Error>>;
 
-pub fn eval_arithmetic<F>(registers: &mut Registers, tokens: &mut Tokens, fun: F)
+fn invalid_operands(operands: &[Operand]) -> Box<dyn Error> {
+    format!("invalid operands: {:?}", operands).into()
+}
+
+pub fn eval_arithmetic<F>(registers: &mut Registers, operands: &[Operand], fun: F)
     -> Result<()>
 where
     F: Fn(i32, i32) -> Option<i32>,
 {
-    tokens.consume().ok_or(CONSUME_ERR)?;
-    if let Ok(rd_idx) = tokens.expect_register() {
-        registers[rd_idx] = {
-            tokens.consume().ok_or(CONSUME_ERR)?;
-            let register_idx = tokens.expect_register()?;
-            let r1 = registers[register_idx];
-
-            if tokens.next().ok_or(CONSUME_ERR)?.kind != TokenKind::EOL {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let r2 = {
-                    if let Ok(register_idx) = tokens.expect_register() {
-                        registers[register_idx]
-                    } else {
-                        tokens.expect_integer()?
-                    }
-                };
-                let result = fun(r1, r2);
-                if let Some(res) = result {
-                    res
-                } else {
-                    return Err(format!("panicked at 'arithmetic operation overflowed': {}:{}",
-                            tokens.filename(), tokens.token[tokens.idx()].line).into());
-                }
-            } else {
-                // CLO, CLZ
-                fun(r1, 0).unwrap()
-            }
+    let (rd_idx, result) = match *operands {
+        [Operand::Register(rd_idx), Operand::Register(rs_idx), Operand::Register(rt_idx)] =>
+            (rd_idx, fun(registers[rs_idx], registers[rt_idx])),
+        [Operand::Register(rd_idx), Operand::Register(rs_idx), Operand::Integer(imm)] =>
+            (rd_idx, fun(registers[rs_idx], imm)),
+        // CLO, CLZ, NOT, NEG, MOVE, ...
+        [Operand::Register(rd_idx), Operand::Register(rs_idx)] =>
+            (rd_idx, fun(registers[rs_idx], 0)),
+        _ => return Err(invalid_operands(operands)),
+    };
+
+    if let Some(res) = result {
+        registers[rd_idx] = res;
+        Ok(())
+    } else {
+        Err(Exception::Overflow.into())
+    }
+}
+
+/// hi:lo is a 64-bit accumulator.
+/// Division by zero leaves hi and lo unchanged (like MARS).  Overflow wraps around.
+pub fn eval_arithmetic_hilo(memory: &mut Memory, operands: &[Operand], kind: InstructionKind) -> Result<()> {
+    let registers = &mut memory.registers;
+    let value = |operand: &Operand| match *operand {
+        Operand::Register(idx) => Ok(registers[idx]),
+        Operand::Integer(imm) => Ok(imm),
+        _ => Err(invalid_operands(operands)),
+    };
+
+    let (rd_idx, x, y) = match *operands {
+        [Operand::Register(rs_idx), Operand::Register(rt_idx)] => (None, registers[rs_idx], registers[rt_idx]),
+        [Operand::Register(rd_idx), ref rs, ref rt] => (Some(rd_idx), value(rs)?, value(rt)?),
+        _ => return Err(invalid_operands(operands)),
+    };
+
+    // Pseudo instructions break if divided by zero (like MARS)
+    let division = matches!(kind, InstructionKind::DIV | InstructionKind::DIVU | InstructionKind::REM | InstructionKind::REMU);
+    if rd_idx.is_some() && division && y == 0 {
+        return Err(Exception::Breakpoint(0).into());
+    }
+
+    let (x, y) = (x as i64, y as i64);
+    let (ux, uy) = (x as u32 as u64, y as u32 as u64);
+    let acc = (memory.hi as u64) << 32 | memory.lo as u64;
+    let acc = match kind {
+        _ if division && y == 0 => acc,
+        InstructionKind::DIV  |
+        InstructionKind::REM  => ((x % y) as u32 as u64) << 32 | (x / y) as u32 as u64,
+        InstructionKind::DIVU |
+        InstructionKind::REMU => (ux % uy) << 32 | (ux / uy),
+        InstructionKind::MULT |
+        InstructionKind::MULO => (x * y) as u64,
+        InstructionKind::MULTU |
+        InstructionKind::MULOU => ux * uy,
+        InstructionKind::MADD  => acc.wrapping_add((x * y) as u64),
+        InstructionKind::MADDU => acc.wrapping_add(ux * uy),
+        InstructionKind::MSUB  => acc.wrapping_sub((x * y) as u64),
+        InstructionKind::MSUBU => acc.wrapping_sub(ux * uy),
+        _ => return Err(format!("eval_arithmetic_hilo(): invalid InstructionKind: {:?}", kind).into()),
+    };
+    memory.hi = (acc >> 32) as u32;
+    memory.lo = acc as u32;
+
+    if let Some(rd_idx) = rd_idx {
+        let overflow = match kind {
+            InstructionKind::MULO  => memory.hi != ((memory.lo as i32) >> 31) as u32,
+            InstructionKind::MULOU => memory.hi != 0,
+            _ => false,
         };
+        if overflow {
+            return Err(Exception::Breakpoint(0).into());
+        }
+        let rem = matches!(kind, InstructionKind::REM | InstructionKind::REMU);
+        memory.registers[rd_idx] = if rem { memory.hi } else { memory.lo } as i32;
     }
 
     Ok(())
 }
 
-pub fn eval_arithmetic_hilo(memory: &mut Memory, tokens: &mut Tokens, kind: InstructionKind) -> Result<()> {
-    let registers = &mut memory.registers;
-    let hi        = &mut memory.hi;
-    let lo        = &mut memory.lo;
-
-    tokens.consume().ok_or(CONSUME_ERR)?;
-    let rd_idx = tokens.expect_register()?;
-    tokens.consume().ok_or(CONSUME_ERR)?;
-    let rs_idx = tokens.expect_register()?;
+pub fn eval_transfer_hilo(memory: &mut Memory, operands: &[Operand], kind: InstructionKind) -> Result<()> {
+    let r_idx = if let [Operand::Register(r_idx)] = *operands {
+        r_idx
+    } else {
+        return Err(invalid_operands(operands));
+    };
 
     match kind {
-        InstructionKind::DIV => {
-            if tokens.next().ok_or(CONSUME_ERR)?.kind != TokenKind::EOL {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let rt_idx = tokens.expect_register()?;
-                registers[rd_idx] = registers[rs_idx] / registers[rt_idx];
-            } else {
-                *lo = (registers[rd_idx] / registers[rs_idx]) as u32;
-                *hi = (registers[rd_idx] % registers[rs_idx]) as u32;
-            }
-        },
-        InstructionKind::DIVU => {
-            if tokens.next().ok_or(CONSUME_ERR)?.kind != TokenKind::EOL {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let rt_idx = tokens.expect_register()?;
-                registers[rd_idx] = (registers[rs_idx] as u32 / registers[rt_idx] as u32) as i32;
-            } else {
-                *lo = registers[rd_idx] as u32 / registers[rs_idx] as u32;
-                *hi = registers[rd_idx] as u32 % registers[rs_idx] as u32;
-            }
-        },
-        InstructionKind::MULT => {
-            let ans = registers[rd_idx] as i64 * registers[rs_idx] as i64;
-            *lo = ans as u32;
-            *hi = ((ans as u64) >> 32) as u32;
-        },
-        InstructionKind::MULTU => {
-            let ans = registers[rd_idx] as u64 * registers[rs_idx] as u64;
-            *lo = ans as u32;
-            *hi = (ans >> 32) as u32;
-        },
-        InstructionKind::MADD => {
-            let ans = registers[rd_idx] as i64 * registers[rs_idx] as i64;
-            *lo += ans as u32;
-            *hi += ((ans as u64) >> 32) as u32;
-        },
-        InstructionKind::MADDU => {
-            let ans = registers[rd_idx] as u64 * registers[rs_idx] as u64;
-            *lo += ans as u32;
-            *hi += (ans >> 32) as u32;
-        },
-        InstructionKind::MSUB => {
-            let ans = registers[rd_idx] as i64 * registers[rs_idx] as i64;
-            *lo -= ans as u32;
-            *hi -= ((ans as u64) >> 32) as u32;
-        },
-        InstructionKind::MSUBU => {
-            let ans = registers[rd_idx] as u64 * registers[rs_idx] as u64;
-            *lo -= ans as u32;
-            *hi -= (ans >> 32) as u32;
-        },
-        _ => return Err(format!("eval_arithmetic_hilo(): invalid TokenKind: {:?}", kind).into()),
+        InstructionKind::MFHI => memory.registers[r_idx] = memory.hi as i32,
+        InstructionKind::MFLO => memory.registers[r_idx] = memory.lo as i32,
+        InstructionKind::MTHI => memory.hi = memory.registers[r_idx] as u32,
+        InstructionKind::MTLO => memory.lo = memory.registers[r_idx] as u32,
+        _ => return Err(format!("eval_transfer_hilo(): invalid InstructionKind: {:?}", kind).into()),
     }
 
     Ok(())
 }
 
-pub fn eval_constant<F>(registers: &mut Registers, tokens: &mut Tokens, fun: F)
+pub fn eval_constant<F>(registers: &mut Registers, operands: &[Operand], fun: F)
     -> Result<()>
 where
     F: Fn(i32) -> i32,
 {
-    tokens.consume().ok_or(CONSUME_ERR)?;
-    let register_idx = tokens.expect_register()?;
-    registers[register_idx] = {
-        tokens.consume().ok_or(CONSUME_ERR)?;
-        if let Ok(integer) = tokens.expect_integer() {
-            fun(integer)
-        } else {
-            let float = tokens.expect_floating()?;
-            float.to_bits() as i32
-        }
+    match *operands {
+        [Operand::Register(register_idx), Operand::Integer(integer)] =>
+            registers[register_idx] = fun(integer),
+        [Operand::Register(register_idx), Operand::Floating(float)] =>
+            registers[register_idx] = float.to_bits() as i32,
+        _ => return Err(invalid_operands(operands)),
+    }
+
+    Ok(())
+}
+
+pub fn eval_comparison<F>(registers: &mut Registers, operands: &[Operand], fun: F)
+    -> Result<()>
+where
+    F: Fn(i32, i32) -> bool,
+{
+    let (rd_idx, result) = match *operands {
+        [Operand::Register(rd_idx), Operand::Register(rs_idx), Operand::Register(rt_idx)] =>
+            (rd_idx, fun(registers[rs_idx], registers[rt_idx])),
+        [Operand::Register(rd_idx), Operand::Register(rs_idx), Operand::Integer(num)] =>
+            (rd_idx, fun(registers[rs_idx], num)),
+        _ => return Err(invalid_operands(operands)),
     };
+    registers[rd_idx] = if result { 1 } else { 0 };
 
     Ok(())
 }
 
-pub fn eval_comparison<F>(registers: &mut Registers, tokens: &mut Tokens, fun: F)
+pub fn eval_branch<F>(memory: &mut Memory, operands: &[Operand], fun: F)
     -> Result<()>
 where
     F: Fn(i32, i32) -> bool,
 {
-    tokens.consume().ok_or(CONSUME_ERR)?;
-    if let Ok(rd_idx) = tokens.expect_register() {
-        tokens.consume().ok_or(CONSUME_ERR)?;
-        if let Ok(rs_idx) = tokens.expect_register() {
-            tokens.consume().ok_or(CONSUME_ERR)?;
-            if let Ok(rt_idx) = tokens.expect_register() {
-                registers[rd_idx] = if fun(registers[rs_idx], registers[rt_idx]) {
-                    1
-                } else {
-                    0
-                }
-            } else {
-                let num = tokens.expect_integer()?;
-                registers[rd_idx] = if fun(registers[rs_idx], num) {
-                    1
-                } else {
-                    0
-                }
-            }
-        }
+    let registers = &memory.registers;
+    let (condition, target) = match *operands {
+        [Operand::Register(rsrc1_idx), Operand::Register(rsrc2_idx), Operand::Target(target)] =>
+            (fun(registers[rsrc1_idx], registers[rsrc2_idx]), target),
+        [Operand::Register(rsrc1_idx), Operand::Integer(num), Operand::Target(target)] =>
+            (fun(registers[rsrc1_idx], num), target),
+        // BEQZ, BGEZ, BGTZ, BLEZ, BLTZ, BNEZ, BGEZAL, BLTZAL
+        [Operand::Register(rsrc1_idx), Operand::Target(target)] =>
+            (fun(registers[rsrc1_idx], 0), target),
+        // B
+        [Operand::Target(target)] =>
+            (fun(0, 0), target),
+        _ => return Err(invalid_operands(operands)),
+    };
+
+    if condition {
+        memory.pc = target;
     }
 
     Ok(())
 }
 
-pub fn eval_branch<F>(registers: &mut Registers, tokens: &mut Tokens, fun: F)
-    -> Result<bool>
+/// Trap exception if the condition is true
+pub fn eval_trap<F>(registers: &Registers, operands: &[Operand], fun: F) -> Result<()>
 where
     F: Fn(i32, i32) -> bool,
 {
-    tokens.consume().ok_or(CONSUME_ERR)?;
-    if let Ok(rsrc1_idx) = tokens.expect_register() {
-        tokens.consume().ok_or(CONSUME_ERR)?;
-        if let Ok(rsrc2_idx) = tokens.expect_register() {
-            tokens.consume().ok_or(CONSUME_ERR)?;
-            if fun(registers[rsrc1_idx], registers[rsrc2_idx]) {
-                let idx = tokens.expect_label()?;
-                tokens.goto(idx-1);
-                return Ok(true);
-            }
-        } else if let Ok(num) = tokens.expect_integer() {
-            tokens.consume().ok_or(CONSUME_ERR)?;
-            if fun(registers[rsrc1_idx], num) {
-                let idx = tokens.expect_label()?;
-                tokens.goto(idx-1);
-                return Ok(true);
-            }
-        } else {
-            // BEQZ, BGEZ, BGTZ, BLEZ, BLTZ, BNEZ
-            let idx = tokens.expect_label()?;
-            if fun(registers[rsrc1_idx], 0) {
-                tokens.goto(idx-1);
-                return Ok(true);
-            }
-        }
-    } else {
-        // B // TODO
-        let idx = tokens.expect_label()?;
-        tokens.goto(idx-1);
-        return Ok(true);
+    let condition = match *operands {
+        [Operand::Register(rs_idx), Operand::Register(rt_idx)] => fun(registers[rs_idx], registers[rt_idx]),
+        [Operand::Register(rs_idx), Operand::Integer(imm)] => fun(registers[rs_idx], imm),
+        _ => return Err(invalid_operands(operands)),
+    };
+
+    if condition {
+        return Err(Exception::Trap.into());
     }
 
-    Ok(false)
+    Ok(())
 }
 
-/// Return: can continue
-pub fn eval_jump(registers: &mut Registers, tokens: &mut Tokens, kind: InstructionKind)
+pub fn eval_jump(memory: &mut Memory, operands: &[Operand], kind: InstructionKind)
     -> Result<()>
 {
-    match kind {
-        InstructionKind::J => {
-            tokens.consume().ok_or(CONSUME_ERR)?;
-            let idx = tokens.expect_label()?;
-            tokens.goto(idx-1);
+    match (kind, operands) {
+        (InstructionKind::J, &[Operand::Target(target)]) => {
+            memory.pc = target;
         },
-        InstructionKind::JAL => {
-            tokens.consume().ok_or(CONSUME_ERR)?;
-            let idx = tokens.expect_label()?;
-            registers[ra] = tokens.idx() as i32 + 1;
-            tokens.goto(idx-1);
+        (InstructionKind::JAL, &[Operand::Target(target)]) => {
+            memory.registers[ra] = memory.pc as i32;
+            memory.pc = target;
         },
-        InstructionKind::JR => {
-            tokens.consume().ok_or(CONSUME_ERR)?;
-            let idx = tokens.expect_register()?;
-            tokens.goto(registers[idx] as usize);
+        (InstructionKind::JR, &[Operand::Register(rs_idx)]) => {
+            memory.pc = memory.registers[rs_idx] as u32;
         },
-        InstructionKind::JALR => {
-            tokens.consume().ok_or(CONSUME_ERR)?;
-            let rs_idx = tokens.expect_register()?;
-            tokens.consume();
-            let rd_idx = tokens.expect_register()?;
-            registers[rd_idx] = tokens.idx() as i32 + 1;
-            tokens.goto(rs_idx-1);
+        (InstructionKind::JALR, &[Operand::Register(rs_idx)]) => {
+            let target = memory.registers[rs_idx] as u32;
+            memory.registers[ra] = memory.pc as i32;
+            memory.pc = target;
         },
-        _ => return Err(format!("eval_jump(): invalid InstructionKind: {:?}", kind).into()),
+        (InstructionKind::JALR, &[Operand::Register(rd_idx), Operand::Register(rs_idx)]) => {
+            let target = memory.registers[rs_idx] as u32;
+            memory.registers[rd_idx] = memory.pc as i32;
+            memory.pc = target;
+        },
+        _ => return Err(invalid_operands(operands)),
     }
 
     Ok(())
 }
 
-pub fn eval_load(memory: &mut Memory, tokens: &mut Tokens, byte: usize, se: SignExtension) -> Result<()> {
-    tokens.consume().ok_or(CONSUME_ERR)?;
-    let register_idx = tokens.expect_register()?;
-    tokens.consume().ok_or(CONSUME_ERR)?;
-    let idx = if let Ok((r_idx, s_idx)) = tokens.expect_memory() { // data or stack
-        memory.registers[r_idx] as u32 + s_idx
-    } else if let Ok((r_idx, d_idx)) = tokens.expect_data() {
-        d_idx as u32 + memory.registers[r_idx] as u32
-    } else {
-        tokens.expect_address()? as u32
-    };
-    memory.registers[register_idx] = get_int(memory, idx, byte, se)?;
+/// Address of the memory operand
+fn address(memory: &Memory, operand: &Operand) -> Result<u32> {
+    match *operand {
+        Operand::Memory(r_idx, offset) => Ok((memory.registers[r_idx] as u32).wrapping_add(offset as u32)),
+        Operand::Address(address) => Ok(address),
+        Operand::Integer(address) => Ok(address as u32),
+        _ => Err(format!("expect address. but got: {:?}", operand).into()),
+    }
+}
 
-    Ok(())
+/// la: the address itself
+pub fn eval_load_address(memory: &mut Memory, operands: &[Operand]) -> Result<()> {
+    if let [Operand::Register(register_idx), ref operand] = *operands {
+        memory.registers[register_idx] = address(memory, operand)? as i32;
+        Ok(())
+    } else {
+        Err(invalid_operands(operands))
+    }
 }
 
-pub fn eval_store(memory: &mut Memory, tokens: &mut Tokens, byte: usize) -> Result<()> {
-    tokens.consume().ok_or(CONSUME_ERR)?;
-    let register_idx = tokens.expect_register()?;
-    tokens.consume().ok_or(CONSUME_ERR)?;
-    if let Ok((r_idx, append)) = tokens.expect_memory() {
-        let idx = memory.registers[r_idx] as u32 + append;
-
-        // data
-        if idx < DYNAMIC_DATA_EXIT {
-            if idx < DYNAMIC_DATA{
-                // static data
-                let index = (idx - if STATIC_DATA <= idx { STATIC_DATA } else { 0 } - 1) as usize;
-                for i in 0..byte {
-                    memory.static_data[index+i] = (memory.registers[register_idx] >> ((byte-1-i)*8)) as u8;
-                }
-            } else {
-                // dynamic data
-                let index = (idx - DYNAMIC_DATA) as usize;
-                for i in 0..byte {
-                    memory.dynamic_data[index+i] = (memory.registers[register_idx] >> ((byte-1-i)*8)) as u8;
-                }
-            }
-
-        // stack
-        } else {
-            let index = (STACK_SEGMENT - idx) as usize;
-            if memory.stack.len() <= index+byte {
-                memory.stack.resize(index+byte+1, 0);
-            }
-            for i in 0..byte {
-                memory.stack[index+i] = (memory.registers[register_idx] >> ((byte-1-i)*8)) as u8;
-            }
-        }
-    } else if let Ok((r_idx, d_idx)) = tokens.expect_data() {
-        let index = memory.registers[r_idx] as usize + d_idx - 1;
-        for i in 0..byte {
-            memory.static_data[index - if STATIC_DATA as usize <= index { STATIC_DATA as usize } else { 0 } + i]
-                = (memory.registers[register_idx] >> ((byte-1-i)*8)) as u8;
-        }
+pub fn eval_load(memory: &mut Memory, operands: &[Operand], byte: usize, se: SignExtension) -> Result<()> {
+    if let [Operand::Register(register_idx), ref operand] = *operands {
+        let idx = address(memory, operand)?;
+        memory.registers[register_idx] = get_int(memory, idx, byte, se).map_err(Exception::AddressLoad)?;
+        Ok(())
     } else {
-        let index = tokens.expect_address()? - 1;
-        for i in 0..byte {
-            memory.static_data[index - if STATIC_DATA as usize <= index { STATIC_DATA as usize } else { 0 } + i]
-                = (memory.registers[register_idx] >> ((byte-1-i)*8)) as u8;
-        }
+        Err(invalid_operands(operands))
     }
+}
 
-    Ok(())
+pub fn eval_store(memory: &mut Memory, operands: &[Operand], byte: usize) -> Result<()> {
+    if let [Operand::Register(register_idx), ref operand] = *operands {
+        let idx = address(memory, operand)?;
+        let value = memory.registers[register_idx];
+        memory.store(idx, byte, value as u32).map_err(Exception::AddressStore)?;
+        Ok(())
+    } else {
+        Err(invalid_operands(operands))
+    }
 }
 
-pub fn eval_myown(memory: &Memory, tokens: &mut Tokens, kind: InstructionKind) -> Result<()> {
-    match kind {
-        InstructionKind::PRTN => {
-            println!();
+pub fn eval_myown(memory: &mut Memory, operands: &[Operand], kind: InstructionKind) -> Result<()> {
+    // Register or immediate or memory
+    let int = |operand: &Operand| -> Result<i32> {
+        match *operand {
+            Operand::Register(r_idx) => Ok(memory.registers[r_idx]),
+            Operand::Integer(num) => Ok(num),
+            _ => Ok(get_int(memory, address(memory, operand)?, 4, SignExtension::Unsigned).map_err(Exception::AddressLoad)?),
+        }
+    };
+
+    let output = match (kind, operands) {
+        (InstructionKind::PRTN, []) => {
+            "\n".to_string()
         },
-        InstructionKind::PRTI => {
-            tokens.consume().ok_or(CONSUME_ERR)?;
-            if let Ok(r_idx) = tokens.expect_register() {
-                print!("{}", memory.registers[r_idx]);
-            } else if let Ok(num) = tokens.expect_integer() {
-                print!("{}", num);
-            } else if let Ok((r_idx, s_idx)) = tokens.expect_memory() { // data or stack
-                let idx = memory.registers[r_idx] as u32 + s_idx;
-                print!("{}", get_int(memory, idx, 4, SignExtension::Unsigned)?);
-            } else if let Ok((r_idx, d_idx)) = tokens.expect_data() {
-                let idx = (memory.registers[r_idx] as usize + d_idx) as u32;
-                print!("{}", get_int(memory, idx, 4, SignExtension::Unsigned)?);
-            } else {
-                let idx = tokens.expect_address()? as u32;
-                print!("{}", get_int(memory, idx, 4, SignExtension::Unsigned)?);
-            }
-            let _ = std::io::stdout().flush();
+        (InstructionKind::PRTI, [operand]) => {
+            format!("{}", int(operand)?)
         },
-        InstructionKind::PRTH => {
-            tokens.consume().ok_or(CONSUME_ERR)?;
-            if let Ok(r_idx) = tokens.expect_register() {
-                print!("{:x}", memory.registers[r_idx]);
-            } else if let Ok(num) = tokens.expect_integer() {
-                print!("{:x}", num);
-            } else if let Ok((r_idx, s_idx)) = tokens.expect_memory() { // data or stack
-                let idx = memory.registers[r_idx] as u32 + s_idx;
-                print!("{:x}", get_int(memory, idx, 4, SignExtension::Unsigned)?);
-            } else if let Ok((r_idx, d_idx)) = tokens.expect_data() {
-                let idx = memory.registers[r_idx] as u32 + d_idx as u32;
-                print!("{:x}", get_int(memory, idx, 4, SignExtension::Unsigned)?);
-            } else {
-                let idx = tokens.expect_address()? as u32;
-                print!("{:x}", get_int(memory, idx, 4, SignExtension::Unsigned)?);
-            }
-            let _ = std::io::stdout().flush();
+        (InstructionKind::PRTH, [operand]) => {
+            format!("{:x}", int(operand)?)
         },
-        InstructionKind::PRTX => {
-            tokens.consume().ok_or(CONSUME_ERR)?;
-            if let Ok(r_idx) = tokens.expect_register() {
-                print!("0x{:x}", memory.registers[r_idx]);
-            } else if let Ok(num) = tokens.expect_integer() {
-                print!("0x{:x}", num);
-            } else if let Ok((r_idx, s_idx)) = tokens.expect_memory() { // data or stack
-                let idx = memory.registers[r_idx] as u32 + s_idx;
-                print!("0x{:x}", get_int(memory, idx, 4, SignExtension::Unsigned)?);
-            } else if let Ok((r_idx, d_idx)) = tokens.expect_data() {
-                let idx = memory.registers[r_idx] as u32 + d_idx as u32;
-                print!("0x{:x}", get_int(memory, idx, 4, SignExtension::Unsigned)?);
-            } else {
-                let idx = tokens.expect_address()? as u32;
-                print!("0x{:x}", get_int(memory, idx, 4, SignExtension::Unsigned)?);
-            }
-            let _ = std::io::stdout().flush();
+        (InstructionKind::PRTX, [operand]) => {
+            format!("0x{:x}", int(operand)?)
         },
-        InstructionKind::PRTC => {
-            tokens.consume().ok_or(CONSUME_ERR)?;
-            if let Ok(r_idx) = tokens.expect_register() {
-                print!("{}", memory.registers[r_idx] as u8 as char);
-            } else if let Ok(d_idx) = tokens.expect_address() {
-                print!("{}", memory.static_data[d_idx-1] as char);
-            } else if let Ok((r_idx, s_idx)) = tokens.expect_memory() { // data or stack
-                let idx = memory.registers[r_idx] as u32 + s_idx;
-                print!("{}", &get_string(memory, idx)?[..1]);
-            } else if let Ok((r_idx, d_idx)) = tokens.expect_data() {
-                let idx = memory.registers[r_idx] as usize + d_idx;
-                print!("{}", memory.static_data[idx-1] as char);
-            } else {
-                let ch = tokens.expect_integer()? as u8 as char;
-                print!("{}", ch);
-            }
-            let _ = std::io::stdout().flush();
+        (InstructionKind::PRTC, [operand]) => {
+            let ch = match *operand {
+                Operand::Register(r_idx) => memory.registers[r_idx],
+                Operand::Integer(num) => num,
+                _ => get_int(memory, address(memory, operand)?, 1, SignExtension::Unsigned).map_err(Exception::AddressLoad)?,
+            };
+            format!("{}", ch as u8 as char)
         },
-        InstructionKind::PRTS => {
-            tokens.consume().ok_or(CONSUME_ERR)?;
-            if let Ok(r_idx) = tokens.expect_register() {
-                print!("{}", get_string(memory, memory.registers[r_idx] as u32)?);
-            } else if let Ok(d_idx) = tokens.expect_address() {
-                print!("{}", get_string(memory, d_idx as u32)?);
-            } else if let Ok((r_idx, s_idx)) = tokens.expect_memory() { // data or stack
-                let idx = memory.registers[r_idx] as u32 + s_idx;
-                print!("{}", get_string(memory, idx)?);
-            } else if let Ok((r_idx, d_idx)) = tokens.expect_data() {
-                let idx = memory.registers[r_idx] as u32 + d_idx as u32;
-                print!("{}", get_string(memory, idx)?);
-            } else {
-                let s = tokens.expect_literal()?;
-                print!("{}", s);
-            }
-            let _ = std::io::stdout().flush();
+        (InstructionKind::PRTS, [operand]) => {
+            let address = match operand {
+                Operand::Register(r_idx) => memory.registers[*r_idx] as u32,
+                _ => address(memory, operand)?,
+            };
+            memory.load_string(address).map_err(Exception::AddressLoad)?
         },
-        _ => return Err(format!("eval_myown(): invalid TokenKind: {:?}", kind).into()),
-    }
+        _ => return Err(format!("eval_myown(): invalid operands: {:?} {:?}", kind, operands).into()),
+    };
+    memory.console.print(&output)?;
 
     Ok(())
 }
 
-pub fn eval_fp_arithmetic(registers: &mut Registers, tokens: &mut Tokens, kind: InstructionKind) -> Result<()> {
-    tokens.consume().ok_or(CONSUME_ERR)?;
-    if let Ok(rd_idx) = tokens.expect_register() {
-        registers[rd_idx] = {
-            tokens.consume().ok_or(CONSUME_ERR)?;
-            let r1 = match tokens.expect_register()? {
-                idx @ 0..=31 => registers[idx] as f32,
-                idx => f32::from_bits(registers[idx] as u32),
-            };
-            tokens.consume().ok_or(CONSUME_ERR)?;
-            let r2 = if let Ok(register_idx) = tokens.expect_register() {
-                match register_idx {
-                    0..=31 => registers[register_idx] as f32,
-                    _ => f32::from_bits(registers[register_idx] as u32),
-                }
-            } else {
-                tokens.expect_integer()? as f32
+pub fn eval_fp_arithmetic(registers: &mut Registers, operands: &[Operand], kind: InstructionKind) -> Result<()> {
+    // Integer registers are converted to floating point
+    let float = |operand: &Operand| -> Result<f32> {
+        match *operand {
+            Operand::Register(idx @ 0..=31) => Ok(registers[idx] as f32),
+            Operand::Register(idx) => Ok(f32::from_bits(registers[idx] as u32)),
+            Operand::Integer(num) => Ok(num as f32),
+            _ => Err(invalid_operands(operands)),
+        }
+    };
+
+    let rounding = Rounding::of(registers);
+    let (fd, (result, cause)) = match *operands {
+        [ref fd, ref fs, ref ft] => {
+            let operation = match kind {
+                InstructionKind::ADD_S => Operation::Add,
+                InstructionKind::SUB_S => Operation::Sub,
+                InstructionKind::MUL_S => Operation::Mul,
+                InstructionKind::DIV_S => Operation::Div,
+                _ => return Err(invalid_operands(operands)),
             };
+            (fd, fpu::arithmetic(operation, float(fs)?, float(ft)?, rounding))
+        },
+        [ref fd, ref fs] => {
+            let x = float(fs)?;
             let result = match kind {
-                InstructionKind::ADD_S => r1 + r2,
-                InstructionKind::SUB_S => r1 - r2,
-                InstructionKind::MUL_S => r1 * r2,
-                InstructionKind::DIV_S => r1 / r2,
-                _ => unreachable!(),
+                InstructionKind::SQRT_S  => fpu::arithmetic(Operation::Sqrt, x, 0.0, rounding),
+                InstructionKind::RECIP_S => fpu::arithmetic(Operation::Div, 1.0, x, rounding),
+                InstructionKind::RSQRT_S => {
+                    let (root, cause) = fpu::arithmetic(Operation::Sqrt, x, 0.0, rounding);
+                    let (result, cause2) = fpu::arithmetic(Operation::Div, 1.0, root, rounding);
+                    (result, cause | cause2)
+                },
+                _ => return Err(invalid_operands(operands)),
             };
-            result.to_bits() as i32
-        };
-    }
+            (fd, result)
+        },
+        // MADD_S: the product is rounded before the addition
+        [ref fd, ref fr, ref fs, ref ft] if kind == InstructionKind::MADD_S => {
+            let (product, cause) = fpu::arithmetic(Operation::Mul, float(fs)?, float(ft)?, rounding);
+            let (result, cause2) = fpu::arithmetic(Operation::Add, product, float(fr)?, rounding);
+            (fd, (result, cause | cause2))
+        },
+        _ => return Err(invalid_operands(operands)),
+    };
 
+    fpu::signal(registers, cause)?;
+    registers[fp_register(operands, fd)?] = result.to_bits() as i32;
     Ok(())
 }
 
-pub fn eval_fp_condition<F>(registers: &mut Registers, tokens: &mut Tokens, fun: F) -> Result<()>
+/// c.eq is quiet, and c.lt and c.le signal the invalid operation if unordered (NaN)
+pub fn eval_fp_condition<F>(registers: &mut Registers, operands: &[Operand], signaling: bool, fun: F) -> Result<()>
 where
     F: Fn(f32, f32) -> bool,
 {
-    tokens.consume().ok_or(CONSUME_ERR)?;
-    let r1_idx = tokens.expect_register()?;
-    tokens.consume().ok_or(CONSUME_ERR)?;
-    let r2_idx = tokens.expect_register()?;
+    let (cc, rest) = condition_code(operands);
+    let (r1_idx, r2_idx) = if let [Operand::Register(r1_idx), Operand::Register(r2_idx)] = *rest {
+        (r1_idx, r2_idx)
+    } else {
+        return Err(invalid_operands(operands));
+    };
+
+    let (x, y) = (f32::from_bits(registers[r1_idx] as u32), f32::from_bits(registers[r2_idx] as u32));
+    let unordered = x.is_nan() || y.is_nan();
+    fpu::signal(registers, if signaling && unordered { fpu::INVALID } else { 0 })?;
+    registers.set_fcc(cc, fun(x, y));
+
+    Ok(())
+}
+
+/// Even register of the pair which holds a double
+fn double_register(operands: &[Operand], operand: &Operand) -> Result<usize> {
+    match *operand {
+        Operand::Register(idx @ 32..=63) if idx % 2 == 0 => Ok(idx),
+        _ => Err(invalid_operands(operands)),
+    }
+}
+
+/// abs.d, mov.d and neg.d are not arithmetic (FCSR is not updated)
+pub fn eval_fp_double(registers: &mut Registers, operands: &[Operand], kind: InstructionKind) -> Result<()> {
+    let rounding = Rounding::of(registers);
+    let (fd, result) = match *operands {
+        [ref fd, ref fs, ref ft] => {
+            let x = registers.double(double_register(operands, fs)?);
+            let y = registers.double(double_register(operands, ft)?);
+            let operation = match kind {
+                InstructionKind::ADD_D => Operation::Add,
+                InstructionKind::SUB_D => Operation::Sub,
+                InstructionKind::MUL_D => Operation::Mul,
+                InstructionKind::DIV_D => Operation::Div,
+                _ => return Err(invalid_operands(operands)),
+            };
+            let (result, cause) = fpu::arithmetic(operation, x, y, rounding);
+            fpu::signal(registers, cause)?;
+            (fd, result)
+        },
+        [ref fd, ref fs] => {
+            let x = registers.double(double_register(operands, fs)?);
+            let result = match kind {
+                InstructionKind::SQRT_D => {
+                    let (result, cause) = fpu::arithmetic(Operation::Sqrt, x, 0.0, rounding);
+                    fpu::signal(registers, cause)?;
+                    result
+                },
+                InstructionKind::ABS_D  => x.abs(),
+                InstructionKind::MOV_D  => x,
+                InstructionKind::NEG_D  => -x,
+                _ => return Err(invalid_operands(operands)),
+            };
+            (fd, result)
+        },
+        _ => return Err(invalid_operands(operands)),
+    };
 
-    if fun(f32::from_bits(registers[r1_idx] as u32), f32::from_bits(registers[r2_idx] as u32)) {
-        registers[fcsr] |= 0x00800000;  // FCC(0)
+    registers.set_double(double_register(operands, fd)?, result);
+    Ok(())
+}
+
+pub fn eval_fp_double_condition<F>(registers: &mut Registers, operands: &[Operand], signaling: bool, fun: F) -> Result<()>
+where
+    F: Fn(f64, f64) -> bool,
+{
+    let (cc, rest) = condition_code(operands);
+    let (fs, ft) = if let [ref fs, ref ft] = *rest {
+        (double_register(operands, fs)?, double_register(operands, ft)?)
+    } else {
+        return Err(invalid_operands(operands));
+    };
+
+    let (x, y) = (registers.double(fs), registers.double(ft));
+    let unordered = x.is_nan() || y.is_nan();
+    fpu::signal(registers, if signaling && unordered { fpu::INVALID } else { 0 })?;
+    registers.set_fcc(cc, fun(x, y));
+
+    Ok(())
+}
+
+/// cvt.d.s, cvt.d.w, cvt.s.d, cvt.s.w, cvt.w.d, cvt.w.s, round.w.s, trunc.w.s, ceil.w.s, floor.w.s
+pub fn eval_fp_convert(registers: &mut Registers, operands: &[Operand], kind: InstructionKind) -> Result<()> {
+    let (fd, fs) = if let [ref fd, ref fs] = *operands {
+        (fd, fs)
     } else {
-        registers[fcsr] &= !0x00800000;  // FCC(0)
+        return Err(invalid_operands(operands));
+    };
+
+    let rounding = Rounding::of(registers);
+    match (kind, fs) {
+        (InstructionKind::CVT_D_S, &Operand::Register(fs @ 32..=63)) => {
+            let value = f32::from_bits(registers[fs] as u32) as f64;
+            fpu::signal(registers, 0)?;
+            registers.set_double(double_register(operands, fd)?, value);
+        },
+        (InstructionKind::CVT_D_W, &Operand::Register(fs @ 32..=63)) => {
+            let value = registers[fs] as f64;
+            fpu::signal(registers, 0)?;
+            registers.set_double(double_register(operands, fd)?, value);
+        },
+        (InstructionKind::CVT_S_D, _) => {
+            let (value, cause) = fpu::to_single(registers.double(double_register(operands, fs)?), rounding);
+            fpu::signal(registers, cause)?;
+            registers[fp_register(operands, fd)?] = value.to_bits() as i32;
+        },
+        (InstructionKind::CVT_S_W, _) => {
+            let (value, cause) = fpu::word_to_single(registers[fp_register(operands, fs)?], rounding);
+            fpu::signal(registers, cause)?;
+            registers[fp_register(operands, fd)?] = value.to_bits() as i32;
+        },
+        (InstructionKind::CVT_W_D, _) => {
+            let (word, cause) = fpu::to_word(registers.double(double_register(operands, fs)?), rounding);
+            fpu::signal(registers, cause)?;
+            registers[fp_register(operands, fd)?] = word;
+        },
+        (_, fs) => {
+            let rounding = match kind {
+                InstructionKind::CVT_W_S   => rounding,
+                InstructionKind::ROUND_W_S => Rounding::Nearest,
+                InstructionKind::TRUNC_W_S => Rounding::Zero,
+                InstructionKind::CEIL_W_S  => Rounding::Up,
+                InstructionKind::FLOOR_W_S => Rounding::Down,
+                _ => return Err(invalid_operands(operands)),
+            };
+            let value = f32::from_bits(registers[fp_register(operands, fs)?] as u32) as f64;
+            let (word, cause) = fpu::to_word(value, rounding);
+            fpu::signal(registers, cause)?;
+            registers[fp_register(operands, fd)?] = word;
+        },
     }
 
     Ok(())
 }
+
+fn fp_register(operands: &[Operand], operand: &Operand) -> Result<usize> {
+    match *operand {
+        Operand::Register(idx @ 32..=63) => Ok(idx),
+        _ => Err(invalid_operands(operands)),
+    }
+}
+
+pub fn eval_load_double(memory: &mut Memory, operands: &[Operand]) -> Result<()> {
+    if let [ref ft, ref operand] = *operands {
+        let ft = double_register(operands, ft)?;
+        let address = address(memory, operand)?;
+        let bits = memory.load_u64(address).map_err(Exception::AddressLoad)?;
+        memory.registers.set_double(ft, f64::from_bits(bits));
+        Ok(())
+    } else {
+        Err(invalid_operands(operands))
+    }
+}
+
+pub fn eval_store_double(memory: &mut Memory, operands: &[Operand]) -> Result<()> {
+    if let [ref ft, ref operand] = *operands {
+        let ft = double_register(operands, ft)?;
+        let address = address(memory, operand)?;
+        let bits = memory.registers.double(ft).to_bits();
+        memory.store_u64(address, bits).map_err(Exception::AddressStore)?;
+        Ok(())
+    } else {
+        Err(invalid_operands(operands))
+    }
+}
@@ -1,588 +1,780 @@
 extern crate rand;
 use rand::prelude::*;
 
-use std::io::Write;
 use std::error::Error;
 
 use super::token::*;
 use super::token::register::RegisterKind::*;
 use super::token::memory::*;
+use super::token::cp0::*;
+use super::assembler::{assemble, Program, Instruction, Operand};
+use super::assembler::decode::decode;
+use super::assembler::encode::{condition_code, encode, faulting_word};
 
 pub mod display;
 use crate::parser::display::*;
 mod eval;
 use crate::parser::eval::*;
 mod test;
-
-macro_rules! read_line {
-    () => ({
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).unwrap();
-        input
-    })
+#[cfg(test)]
+mod conformance;
+
+/// State of the program after `step()`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    Running,    // Can continue
+    Exit(i32),  // exit, exit2 (exit code)
+    Finished,   // No more instructions
 }
 
-#[allow(clippy::cognitive_complexity)]
 pub fn parse(tokens: &mut Tokens, memory: &mut Memory) -> Result<(), Box<dyn Error>> {
+    let mut program = Program::new();
+    assemble(tokens, &mut program, memory)?;
+
+    while step(tokens, &mut program, memory)? == Status::Running {}
+
+    //display_data_per_4byte(&data);
+    //display_stack(&stack);
+    //display_register(&registers);
+    Ok(())
+}
 
-    data_analysis(tokens, memory);
-    //println!("data: {:?}", data);
-    //println!("tokens: {:?}", tokens);
+/// Execute one instruction at `memory.pc`
+pub fn step(tokens: &mut Tokens, program: &mut Program, memory: &mut Memory) -> Result<Status, Box<dyn Error>> {
+    if interrupt(memory) {
+        return Ok(Status::Running);
+    }
 
-    while tokens.consume().is_some() {
-        //println!("{:?}", *tokens.kind()); continue;
+    let instruction = match program.index_of(memory.pc) {
+        // .ktext is fetched only in kernel mode
+        Some(idx) if memory.pc < USER_END || memory.kernel_mode() => &program.instructions[idx],
+        // e.g. Exception handler, middle of a pseudo instruction
+        _ => return fetch_execute(tokens, program, memory),
+    };
+
+    // `TOKEN_TRACE=1 cargo run`
+    if tokens.token_trace() {
+        println!("{}:{:?}:0x{:08x},\t{:?}",
+            tokens.filename_of(instruction.filename_idx), instruction.line, memory.pc, instruction);
+    }
 
-        // Skip until .text
-        if TokenKind::INDICATE(IndicateKind::data) == *tokens.kind() {
-            while tokens.consume().is_some() {
-                if TokenKind::INDICATE(IndicateKind::text) == *tokens.kind() {
-                    break;
-                }
+    if instruction.kind == InstructionKind::RST {
+        reset(memory, tokens, program);
+        return Ok(Status::Finished);
+    }
+
+    // Words of pseudo instructions are counted as Machine mode does.
+    // If the timer expires in the middle, run them one by one to take the interrupt there.
+    let words = (instruction.size / 4).max(1);
+    if (0..words-1).contains(&memory.cp0[COMPARE].wrapping_sub(memory.cp0[COUNT])) && encode(instruction).is_ok() {
+        return fetch_execute(tokens, program, memory);
+    }
+
+    memory.pc = instruction.address + instruction.size;
+    let status = match execute(memory, instruction) {
+        Ok(status) => {
+            for _ in 1..words {
+                memory.cp0.tick();
             }
-            if tokens.is_none() {
-                break;
+            status
+        },
+        // Raised at the same word of the pseudo instruction as Machine mode
+        Err(e) => {
+            let index = e.downcast_ref::<Exception>().map_or(0, |&e| faulting_word(instruction, e));
+            for _ in 0..index {
+                memory.cp0.tick();
             }
-        }
+            match exception(memory, &*e, instruction.address + index * 4) {
+                true  => Status::Running,
+                false => return Err(format!("{}:{}: {}",
+                    tokens.filename_of(instruction.filename_idx), instruction.line, e).into()),
+            }
+        },
+    };
+
+    trace(tokens, memory);
+    Ok(status)
+}
+
+/// Fetch, decode and execute one instruction at `memory.pc` from memory
+pub fn step_machine(tokens: &mut Tokens, program: &mut Program, memory: &mut Memory) -> Result<Status, Box<dyn Error>> {
+    if interrupt(memory) {
+        return Ok(Status::Running);
+    }
+    fetch_execute(tokens, program, memory)
+}
 
-        // Skip LABEL, INDICATE and EOL
-        while matches!(*tokens.kind(), TokenKind::LABEL(_, _, _) | TokenKind::INDICATE(_) | TokenKind::EOL) {
-            if tokens.consume().is_none() {
-                return Ok(());
+fn fetch_execute(tokens: &mut Tokens, program: &mut Program, memory: &mut Memory) -> Result<Status, Box<dyn Error>> {
+    let pc = memory.pc;
+
+    // Source line of the (pseudo) instruction which includes `pc`, or the nearest label
+    let location = |tokens: &Tokens| match program.find(pc) {
+        Some(i) => format!("{}:{}", tokens.filename_of(i.filename_idx), i.line),
+        None => match program.symbols().label_of(pc) {
+            Some((name, symbol, 0)) => format!("{}:{}", tokens.filename_of(symbol.filename_idx), name),
+            Some((name, symbol, offset)) =>
+                format!("{}:{}+0x{:x}", tokens.filename_of(symbol.filename_idx), name, offset),
+            None => format!("0x{:08x}", pc),
+        },
+    };
+    let decoded = match memory.load_u32(pc) {
+        Ok(word) => decode(word, pc).map_err(|_| Exception::ReservedInstruction(word)),
+        Err(fault @ Fault::Kernel(_)) => Err(Exception::AddressLoad(fault)),
+        // Out of the program
+        Err(_) => return Ok(Status::Finished),
+    };
+    let instruction = match decoded {
+        Ok(instruction) => instruction,
+        Err(e) => {
+            if raise(memory, e, pc) {
+                return Ok(Status::Running);
             }
-        }
-
-        let instruction_kind = tokens.expect_instruction()?;
-
-        match instruction_kind {
-            // Arithmetic, Logic
-            InstructionKind::ADD |
-            InstructionKind::ADDI =>
-                eval_arithmetic(&mut memory.registers, tokens, |x, y| x.checked_add(y))?,
-            InstructionKind::ADDU |
-            InstructionKind::ADDIU =>
-                eval_arithmetic(&mut memory.registers, tokens, |x, y| Some(x + y))?,
-            InstructionKind::SUB =>
-                eval_arithmetic(&mut memory.registers, tokens, |x, y| x.checked_sub(y))?,
-            InstructionKind::SUBU =>
-                eval_arithmetic(&mut memory.registers, tokens, |x, y| Some(x - y))?,
-            InstructionKind::MUL => {
-                //eval_arithmetic(&mut memory.registers, &mut tokens, |x, y| x.checked_mul(y))?, // TODO: mult $2,$3;mflo $1
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let r1_idx = tokens.expect_register()?;
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let r2_idx = tokens.expect_register()?;
-                if let TokenKind::REGISTER(_, r3_idx) = tokens.next().unwrap().kind {
-                    tokens.consume().ok_or(CONSUME_ERR)?;
+            return Err(format!("{}: {}", location(tokens), e).into());
+        },
+    };
+
+    // `TOKEN_TRACE=1 cargo run`
+    if tokens.token_trace() {
+        println!("{}:0x{:08x},\t{}", location(tokens), pc, instruction);
+    }
+
+    if instruction.kind == InstructionKind::RST {
+        reset(memory, tokens, program);
+        return Ok(Status::Finished);
+    }
+
+    memory.pc = pc.wrapping_add(4);
+    let status = execute(memory, &instruction).or_else(|e| match exception(memory, &*e, pc) {
+        true  => Ok(Status::Running),
+        false => Err(format!("{}: {}", location(tokens), e)),
+    })?;
+
+    trace(tokens, memory);
+    Ok(status)
+}
+
+/// Take a pending interrupt before the next instruction, or count it for the timer
+fn interrupt(memory: &mut Memory) -> bool {
+    if memory.cp0.interrupted() && raise(memory, Exception::Interrupt, memory.pc) {
+        return true;
+    }
+    memory.cp0.tick();
+    false
+}
+
+/// Raise `error` if it is an `Exception`
+fn exception(memory: &mut Memory, error: &(dyn Error + 'static), epc: u32) -> bool {
+    match error.downcast_ref::<Exception>() {
+        Some(&e) => raise(memory, e, epc),
+        None => false,
+    }
+}
+
+/// Jump to the exception handler if it is installed (and not in the handler).
+/// `epc` is the address of the instruction which caused `exception`.
+fn raise(memory: &mut Memory, exception: Exception, epc: u32) -> bool {
+    if !memory.is_mapped(EXCEPTION_HANDLER) || memory.cp0[STATUS] & STATUS_EXL != 0 {
+        return false;
+    }
+    memory.cp0[EPC] = epc;
+    memory.cp0[CAUSE] = memory.cp0[CAUSE] & !CAUSE_EXC_CODE | exception.code() << 2;
+    if let Some(address) = exception.bad_address() {
+        memory.cp0[BADVADDR] = address;
+    }
+    memory.cp0[STATUS] |= STATUS_EXL;
+    memory.pc = EXCEPTION_HANDLER;
+    true
+}
+
+/// `*_TRACE=1 cargo run`
+fn trace(tokens: &Tokens, memory: &Memory) {
+    if tokens.data_trace() {
+        display_data_per_4byte(memory);
+    }
+    if tokens.stack_trace() {
+        display_stack(memory);
+    }
+    if tokens.register_trace() {
+        display_register(&memory.registers);
+    }
+    if tokens.fp_register_trace() {
+        display_fp_register(&memory.registers);
+    }
+}
+
+#[allow(clippy::cognitive_complexity)]
+fn execute(memory: &mut Memory, instruction: &Instruction) -> Result<Status, Box<dyn Error>> {
+    let operands = &instruction.operands[..];
+
+    match instruction.kind {
+        // Arithmetic, Logic
+        InstructionKind::ADD |
+        InstructionKind::ADDI =>
+            eval_arithmetic(&mut memory.registers, operands, |x, y| x.checked_add(y))?,
+        InstructionKind::ADDU |
+        InstructionKind::ADDIU =>
+            eval_arithmetic(&mut memory.registers, operands, |x, y| Some(x.wrapping_add(y)))?,
+        InstructionKind::SUB =>
+            eval_arithmetic(&mut memory.registers, operands, |x, y| x.checked_sub(y))?,
+        InstructionKind::SUBU =>
+            eval_arithmetic(&mut memory.registers, operands, |x, y| Some(x.wrapping_sub(y)))?,
+        InstructionKind::MUL => {
+            //eval_arithmetic(&mut memory.registers, operands, |x, y| x.checked_mul(y))?, // TODO: mult $2,$3;mflo $1
+            match *operands {
+                [Operand::Register(r1_idx), Operand::Register(r2_idx), Operand::Register(r3_idx)] => {
                     // mult $2, $3
                     let ans = memory.registers[r2_idx] as i64 * memory.registers[r3_idx] as i64;
                     memory.lo = ans as u32;
                     memory.hi = ((ans as u64) >> 32) as u32;
                     // mflo $1
                     memory.registers[r1_idx] = memory.lo as i32;
-                } else {
+                },
+                [Operand::Register(r1_idx), Operand::Register(r2_idx)] => {
                     // mult
                     let ans = memory.registers[r1_idx] as i64 * memory.registers[r2_idx] as i64;
                     memory.lo = ans as u32;
                     memory.hi = ((ans as u64) >> 32) as u32;
+                },
+                _ => return Err(invalid_operands(instruction)),
+            }
+        },
+        InstructionKind::DIV |
+        InstructionKind::DIVU |
+        InstructionKind::REM |
+        InstructionKind::REMU |
+        InstructionKind::MULT |
+        InstructionKind::MULTU |
+        InstructionKind::MULO |
+        InstructionKind::MULOU |
+        InstructionKind::MADD |
+        InstructionKind::MADDU |
+        InstructionKind::MSUB |
+        InstructionKind::MSUBU =>
+            eval_arithmetic_hilo(memory, operands, instruction.kind)?,
+        InstructionKind::CLO =>
+            eval_arithmetic(&mut memory.registers, operands, move |x, _| {
+                let mut cnt: i32 = 0;
+                for i in (0..=31).rev() {
+                    if (x as usize) >> i & 1 != 1 { break; }
+                    cnt += 1;
                 }
-            },
-            InstructionKind::REM =>
-                eval_arithmetic(&mut memory.registers, tokens, |x, y| Some(x % y))?,
-            InstructionKind::REMU =>
-                eval_arithmetic(&mut memory.registers, tokens, |x, y| x.checked_rem(y))?,
-
-            InstructionKind::DIV =>
-                eval_arithmetic_hilo(memory, tokens, InstructionKind::DIV)?,
-            InstructionKind::DIVU =>
-                eval_arithmetic_hilo(memory, tokens, InstructionKind::DIVU)?,
-            InstructionKind::MULT =>
-                eval_arithmetic_hilo(memory, tokens, InstructionKind::MULT)?,
-            InstructionKind::MULTU =>
-                eval_arithmetic_hilo(memory, tokens, InstructionKind::MULTU)?,
-            InstructionKind::MADD =>
-                eval_arithmetic_hilo(memory, tokens, InstructionKind::MADD)?,
-            InstructionKind::MADDU =>
-                eval_arithmetic_hilo(memory, tokens, InstructionKind::MADDU)?,
-            InstructionKind::MSUB =>
-                eval_arithmetic_hilo(memory, tokens, InstructionKind::MSUB)?,
-            InstructionKind::MSUBU =>
-                eval_arithmetic_hilo(memory, tokens, InstructionKind::MSUBU)?,
-
-            InstructionKind::MULO =>
-                eval_arithmetic(&mut memory.registers, tokens, |x, y| Some(x * y))?,
-            InstructionKind::MULOU =>
-                eval_arithmetic(&mut memory.registers, tokens, |x, y| Some((x as u32 * y as u32) as i32))?,
-            InstructionKind::CLO =>
-                eval_arithmetic(&mut memory.registers, tokens, move |x, _| {
-                    let mut cnt: i32 = 0;
-                    for i in (0..=31).rev() {
-                        if (x as usize) >> i & 1 != 1 { break; }
-                        cnt += 1;
-                    }
-                    Some(cnt)
-                })?,
-            InstructionKind::CLZ =>
-                eval_arithmetic(&mut memory.registers, tokens, move |x, _| {
-                    let mut cnt: i32 = 0;
-                    for i in (0..=31).rev() {
-                        if (x as usize) >> i & 1 != 0 { break; }
-                        cnt += 1;
-                    }
-                    Some(cnt)
-                })?,
-            InstructionKind::ROR => {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let rd_idx = tokens.expect_register()?;
-                memory.registers[rd_idx] = {
-                    tokens.consume().ok_or(CONSUME_ERR)?;
-                    let rs_idx = tokens.expect_register()?;
-                    let rs = memory.registers[rs_idx];
-                    tokens.consume().ok_or(CONSUME_ERR)?;
-                    let rt = {
-                        if let Ok(rt_idx) = tokens.expect_register() {
-                            memory.registers[rt_idx]
-                        } else if let Ok(num) = tokens.expect_integer() {
-                            num
-                        } else {
-                            return Err("ROR: invalid token".into());
-                        }
-                    };
-                    memory.registers[at] = (rs as u32 >> rt) as i32;
-                    memory.registers[rd_idx] = rs << (32 - rt);
-                    memory.registers[rd_idx] | memory.registers[at]
-                };
-            },
-            InstructionKind::ROL => {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let rd_idx = tokens.expect_register()?;
-                memory.registers[rd_idx] = {
-                    tokens.consume().ok_or(CONSUME_ERR)?;
-                    let rs_idx = tokens.expect_register()?;
-                    let rs = memory.registers[rs_idx];
-                    tokens.consume().ok_or(CONSUME_ERR)?;
-                    let rt = {
-                        if let Ok(rt_idx) = tokens.expect_register() {
-                            memory.registers[rt_idx]
-                        } else if let Ok(num) = tokens.expect_integer() {
-                            num
-                        } else {
-                            return Err("ROL: invalid token".into());
-                        }
-                    };
-                    memory.registers[at] = rs << rt;
-                    memory.registers[rd_idx] = (rs as u32 >> (32-rt)) as i32;
-                    memory.registers[rd_idx] | memory.registers[at]
-                };
-            },
-
-            InstructionKind::NOR =>
-                eval_arithmetic(&mut memory.registers, tokens, |x, y| Some(!(x | y)))?,
-            InstructionKind::NOT => {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let rd_idx = tokens.expect_register()?;
-                memory.registers[rd_idx] = {
-                    tokens.consume().ok_or(CONSUME_ERR)?;
-                    let register_idx = tokens.expect_register()?;
-                    !memory.registers[register_idx]
-                };
-            },
-            InstructionKind::NEG =>
-                eval_arithmetic(&mut memory.registers, tokens, |x, _| Some(-x))?, // TODO (with overflow)
-            InstructionKind::NEGU =>
-                eval_arithmetic(&mut memory.registers, tokens, |x, _| Some(-x))?,
-
-            InstructionKind::SLL |
-            InstructionKind::SLLV =>
-                eval_arithmetic(&mut memory.registers, tokens, |x, y| Some(x << y))?,
-            InstructionKind::SRA |
-            InstructionKind::SRAV =>
-                eval_arithmetic(&mut memory.registers, tokens, |x, y| Some(x >> y))?,
-            InstructionKind::SRL |
-            InstructionKind::SRLV =>
-                eval_arithmetic(&mut memory.registers, tokens, |x, y| Some((x as u32 >> y) as i32))?,
-
-            InstructionKind::AND |
-            InstructionKind::ANDI =>
-                eval_arithmetic(&mut memory.registers, tokens, |x, y| Some(x & y))?,
-            InstructionKind::OR |
-            InstructionKind::ORI =>
-                eval_arithmetic(&mut memory.registers, tokens, |x, y| Some(x | y))?,
-            InstructionKind::XOR |
-            InstructionKind::XORI =>
-                eval_arithmetic(&mut memory.registers, tokens, |x, y| Some(x ^ y))?,
-
-            // Constant
-            InstructionKind::LI =>
-                eval_constant(&mut memory.registers, tokens, |x| x)?,
-            InstructionKind::LUI =>
-                eval_constant(&mut memory.registers, tokens, |x| x & (std::u32::MAX-65535) as i32)?,
-
-            // Comparison
-            InstructionKind::SLT |
-            InstructionKind::SLTI =>
-                eval_comparison(&mut memory.registers, tokens, |x, y| x < y)?,
-            InstructionKind::SEQ =>
-                eval_comparison(&mut memory.registers, tokens, |x, y| x == y)?,
-            InstructionKind::SGE =>
-                eval_comparison(&mut memory.registers, tokens, |x, y| x >= y)?,
-            InstructionKind::SGT =>
-                eval_comparison(&mut memory.registers, tokens, |x, y| x > y)?,
-            InstructionKind::SLE =>
-                eval_comparison(&mut memory.registers, tokens, |x, y| x <= y)?,
-            InstructionKind::SNE =>
-                eval_comparison(&mut memory.registers, tokens, |x, y| x != y)?,
-
-            // Branch
-            InstructionKind::B =>
-                if eval_branch(&mut memory.registers, tokens, |_, _| true)?   { continue; },
-            InstructionKind::BEQ =>
-                if eval_branch(&mut memory.registers, tokens, |x, y| x == y)? { continue; },
-            InstructionKind::BNE =>
-                if eval_branch(&mut memory.registers, tokens, |x, y| x != y)? { continue; },
-            InstructionKind::BGE =>
-                if eval_branch(&mut memory.registers, tokens, |x, y| x >= y)? { continue; },
-            InstructionKind::BGT =>
-                if eval_branch(&mut memory.registers, tokens, |x, y| x > y)?  { continue; },
-            InstructionKind::BLE =>
-                if eval_branch(&mut memory.registers, tokens, |x, y| x <= y)? { continue; },
-            InstructionKind::BLT =>
-                if eval_branch(&mut memory.registers, tokens, |x, y| x < y)?  { continue; },
-            InstructionKind::BEQZ =>
-                if eval_branch(&mut memory.registers, tokens, |x, y| x == y)? { continue; },
-            InstructionKind::BGEZ =>
-                if eval_branch(&mut memory.registers, tokens, |x, y| x >= y)? { continue; },
-            InstructionKind::BGTZ =>
-                if eval_branch(&mut memory.registers, tokens, |x, y| x > y)?  { continue; },
-            InstructionKind::BLEZ =>
-                if eval_branch(&mut memory.registers, tokens, |x, y| x <= y)? { continue; },
-            InstructionKind::BLTZ =>
-                if eval_branch(&mut memory.registers, tokens, |x, y| x < y)?  { continue; },
-            InstructionKind::BNEZ =>
-                if eval_branch(&mut memory.registers, tokens, |x, y| x != y)? { continue; },
-            InstructionKind::BGEZAL => {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let r_idx = tokens.expect_register()?;
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let l_idx = tokens.expect_label()?;
-                memory.registers[ra] = tokens.idx() as i32 + 1;
-                if 0 <= memory.registers[r_idx] {
-                    tokens.goto(l_idx-1);
-                }
-            },
-            InstructionKind::BLTZAL => {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let r_idx = tokens.expect_register()?;
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let l_idx = tokens.expect_label()?;
-                memory.registers[ra] = tokens.idx() as i32 + 1;
-                if memory.registers[r_idx] < 0 {
-                    tokens.goto(l_idx-1);
+                Some(cnt)
+            })?,
+        InstructionKind::CLZ =>
+            eval_arithmetic(&mut memory.registers, operands, move |x, _| {
+                let mut cnt: i32 = 0;
+                for i in (0..=31).rev() {
+                    if (x as usize) >> i & 1 != 0 { break; }
+                    cnt += 1;
                 }
-            },
-
-            // Jump
-            InstructionKind::J =>
-                { eval_jump(&mut memory.registers, tokens, InstructionKind::J)?;    continue; },
-            InstructionKind::JAL =>
-                { eval_jump(&mut memory.registers, tokens, InstructionKind::JAL)?;  continue; },
-            InstructionKind::JR =>
-                { eval_jump(&mut memory.registers, tokens, InstructionKind::JR)?;   continue; },
-            InstructionKind::JALR =>
-                { eval_jump(&mut memory.registers, tokens, InstructionKind::JALR)?; continue; },
-
-            // Load
-            InstructionKind::LA => {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let register_idx = tokens.expect_register()?;
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                memory.registers[register_idx] = {
-                    if let Ok(data_idx) = tokens.expect_address() {
-                        data_idx as i32
-                    } else {
-                        tokens.expect_label().unwrap() as i32
-                    }
-                };
-            },
-            InstructionKind::LB =>   // Rt = *((int*)address) (8bit)
-                eval_load(memory, tokens, 1, SignExtension::Signed)?,
-            InstructionKind::LBU =>  // Rt = *((int*)address) (8bit)
-                eval_load(memory, tokens, 1, SignExtension::Unsigned)?,
-            InstructionKind::LH =>   // Rt = *((int*)address) (16bit)
-                eval_load(memory, tokens, 2, SignExtension::Signed)?,
-            InstructionKind::LHU =>  // Rt = *((int*)address) (16bit)
-                eval_load(memory, tokens, 2, SignExtension::Unsigned)?,
-            InstructionKind::LW =>   // Rt = *((int*)address) (32bit)
-                eval_load(memory, tokens, 4, SignExtension::Unsigned)?,
-
-            // Store
-            InstructionKind::SB =>  // *((int*)address) = Rt (8bit)
-                eval_store(memory, tokens, 1)?,
-            InstructionKind::SH =>  // *((int*)address) = Rt (16bit)
-                eval_store(memory, tokens, 2)?,
-            InstructionKind::SW =>  // *((int*)address) = Rt (32bit)
-                eval_store(memory, tokens, 4)?,
-
-            // Transfer
-            InstructionKind::MOVE => {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let rd_idx = tokens.expect_register()?;
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let rs_idx = tokens.expect_register()?;
-                memory.registers[rd_idx] = memory.registers[rs_idx];
-            },
-            InstructionKind::MFHI => {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let r_idx = tokens.expect_register()?;
-                memory.registers[r_idx] = memory.hi as i32;
-            },
-            InstructionKind::MFLO => {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let r_idx = tokens.expect_register()?;
-                memory.registers[r_idx] = memory.lo as i32;
-            },
-            InstructionKind::MTHI => {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let r_idx = tokens.expect_register()?;
-                memory.hi = memory.registers[r_idx] as u32;
-            },
-            InstructionKind::MTLO => {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let r_idx = tokens.expect_register()?;
-                memory.lo = memory.registers[r_idx] as u32;
-            },
-            InstructionKind::MOVN => {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let rd_idx = tokens.expect_register()?;
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let rs_idx = tokens.expect_register()?;
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let rt_idx = tokens.expect_register()?;
+                Some(cnt)
+            })?,
+        InstructionKind::ROR =>
+            eval_arithmetic(&mut memory.registers, operands, |x, y| Some(x.rotate_right(y as u32 & 31)))?,
+        InstructionKind::ROL =>
+            eval_arithmetic(&mut memory.registers, operands, |x, y| Some(x.rotate_left(y as u32 & 31)))?,
+
+        InstructionKind::NOR =>
+            eval_arithmetic(&mut memory.registers, operands, |x, y| Some(!(x | y)))?,
+        InstructionKind::NOT =>
+            eval_arithmetic(&mut memory.registers, operands, |x, _| Some(!x))?,
+        InstructionKind::NEG =>
+            eval_arithmetic(&mut memory.registers, operands, |x, _| x.checked_neg())?,
+        InstructionKind::NEGU =>
+            eval_arithmetic(&mut memory.registers, operands, |x, _| Some(x.wrapping_neg()))?,
+
+        // The shift amount is the low 5 bits
+        InstructionKind::SLL |
+        InstructionKind::SLLV =>
+            eval_arithmetic(&mut memory.registers, operands, |x, y| Some(x.wrapping_shl(y as u32)))?,
+        InstructionKind::SRA |
+        InstructionKind::SRAV =>
+            eval_arithmetic(&mut memory.registers, operands, |x, y| Some(x.wrapping_shr(y as u32)))?,
+        InstructionKind::SRL |
+        InstructionKind::SRLV =>
+            eval_arithmetic(&mut memory.registers, operands, |x, y| Some((x as u32).wrapping_shr(y as u32) as i32))?,
+
+        InstructionKind::AND |
+        InstructionKind::ANDI =>
+            eval_arithmetic(&mut memory.registers, operands, |x, y| Some(x & y))?,
+        InstructionKind::OR |
+        InstructionKind::ORI =>
+            eval_arithmetic(&mut memory.registers, operands, |x, y| Some(x | y))?,
+        InstructionKind::XOR |
+        InstructionKind::XORI =>
+            eval_arithmetic(&mut memory.registers, operands, |x, y| Some(x ^ y))?,
+
+        // Constant
+        InstructionKind::LI =>
+            eval_constant(&mut memory.registers, operands, |x| x)?,
+        InstructionKind::LUI =>
+            eval_constant(&mut memory.registers, operands, |x| x << 16)?,
+
+        // Comparison
+        InstructionKind::SLT |
+        InstructionKind::SLTI =>
+            eval_comparison(&mut memory.registers, operands, |x, y| x < y)?,
+        InstructionKind::SLTU |
+        InstructionKind::SLTIU =>
+            eval_comparison(&mut memory.registers, operands, |x, y| (x as u32) < (y as u32))?,
+        InstructionKind::SEQ =>
+            eval_comparison(&mut memory.registers, operands, |x, y| x == y)?,
+        InstructionKind::SGE =>
+            eval_comparison(&mut memory.registers, operands, |x, y| x >= y)?,
+        InstructionKind::SGEU =>
+            eval_comparison(&mut memory.registers, operands, |x, y| (x as u32) >= (y as u32))?,
+        InstructionKind::SGT =>
+            eval_comparison(&mut memory.registers, operands, |x, y| x > y)?,
+        InstructionKind::SGTU =>
+            eval_comparison(&mut memory.registers, operands, |x, y| (x as u32) > (y as u32))?,
+        InstructionKind::SLE =>
+            eval_comparison(&mut memory.registers, operands, |x, y| x <= y)?,
+        InstructionKind::SLEU =>
+            eval_comparison(&mut memory.registers, operands, |x, y| (x as u32) <= (y as u32))?,
+        InstructionKind::SNE =>
+            eval_comparison(&mut memory.registers, operands, |x, y| x != y)?,
+
+        // Branch
+        InstructionKind::B =>
+            eval_branch(memory, operands, |_, _| true)?,
+        InstructionKind::BEQ |
+        InstructionKind::BEQZ =>
+            eval_branch(memory, operands, |x, y| x == y)?,
+        InstructionKind::BNE |
+        InstructionKind::BNEZ =>
+            eval_branch(memory, operands, |x, y| x != y)?,
+        InstructionKind::BGE |
+        InstructionKind::BGEZ =>
+            eval_branch(memory, operands, |x, y| x >= y)?,
+        InstructionKind::BGT |
+        InstructionKind::BGTZ =>
+            eval_branch(memory, operands, |x, y| x > y)?,
+        InstructionKind::BLE |
+        InstructionKind::BLEZ =>
+            eval_branch(memory, operands, |x, y| x <= y)?,
+        InstructionKind::BLT |
+        InstructionKind::BLTZ =>
+            eval_branch(memory, operands, |x, y| x < y)?,
+        InstructionKind::BGEU =>
+            eval_branch(memory, operands, |x, y| (x as u32) >= (y as u32))?,
+        InstructionKind::BGTU =>
+            eval_branch(memory, operands, |x, y| (x as u32) > (y as u32))?,
+        InstructionKind::BLEU =>
+            eval_branch(memory, operands, |x, y| (x as u32) <= (y as u32))?,
+        InstructionKind::BLTU =>
+            eval_branch(memory, operands, |x, y| (x as u32) < (y as u32))?,
+        InstructionKind::BGEZAL => {
+            memory.registers[ra] = memory.pc as i32;
+            eval_branch(memory, operands, |x, y| x >= y)?;
+        },
+        InstructionKind::BLTZAL => {
+            memory.registers[ra] = memory.pc as i32;
+            eval_branch(memory, operands, |x, y| x < y)?;
+        },
+
+        // Jump
+        InstructionKind::J |
+        InstructionKind::JAL |
+        InstructionKind::JR |
+        InstructionKind::JALR =>
+            eval_jump(memory, operands, instruction.kind)?,
+
+        // Load
+        InstructionKind::LA =>
+            eval_load_address(memory, operands)?,
+        InstructionKind::LB =>   // Rt = *((int*)address) (8bit)
+            eval_load(memory, operands, 1, SignExtension::Signed)?,
+        InstructionKind::LBU =>  // Rt = *((int*)address) (8bit)
+            eval_load(memory, operands, 1, SignExtension::Unsigned)?,
+        InstructionKind::LH =>   // Rt = *((int*)address) (16bit)
+            eval_load(memory, operands, 2, SignExtension::Signed)?,
+        InstructionKind::LHU =>  // Rt = *((int*)address) (16bit)
+            eval_load(memory, operands, 2, SignExtension::Unsigned)?,
+        InstructionKind::LW =>   // Rt = *((int*)address) (32bit)
+            eval_load(memory, operands, 4, SignExtension::Unsigned)?,
+
+        // Store
+        InstructionKind::SB =>  // *((int*)address) = Rt (8bit)
+            eval_store(memory, operands, 1)?,
+        InstructionKind::SH =>  // *((int*)address) = Rt (16bit)
+            eval_store(memory, operands, 2)?,
+        InstructionKind::SW =>  // *((int*)address) = Rt (32bit)
+            eval_store(memory, operands, 4)?,
+
+        // Transfer
+        InstructionKind::MOVE =>
+            eval_arithmetic(&mut memory.registers, operands, |x, _| Some(x))?,
+        InstructionKind::MFHI |
+        InstructionKind::MFLO |
+        InstructionKind::MTHI |
+        InstructionKind::MTLO =>
+            eval_transfer_hilo(memory, operands, instruction.kind)?,
+        InstructionKind::MOVN => {
+            if let [Operand::Register(rd_idx), Operand::Register(rs_idx), Operand::Register(rt_idx)] = *operands {
                 if memory.registers[rt_idx] != 0 {
                     memory.registers[rd_idx] = memory.registers[rs_idx];
                 }
-            },
-            InstructionKind::MOVZ => {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let rd_idx = tokens.expect_register()?;
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let rs_idx = tokens.expect_register()?;
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let rt_idx = tokens.expect_register()?;
+            } else {
+                return Err(invalid_operands(instruction));
+            }
+        },
+        InstructionKind::MOVZ => {
+            if let [Operand::Register(rd_idx), Operand::Register(rs_idx), Operand::Register(rt_idx)] = *operands {
                 if memory.registers[rt_idx] == 0 {
                     memory.registers[rd_idx] = memory.registers[rs_idx];
                 }
-            },
-
-            // Exception, Interrupt
-            InstructionKind::SYSCALL => {
-                match memory.registers[v0] {
-                    // print_int: $a0=integer
-                    1  => {
-                        print!("{}", memory.registers[a0]);
-                        let _ = std::io::stdout().flush();
-                    },
-                    // print_float: $f12=integer
-                    2  => {
-                        print!("{}", f32::from_bits(memory.registers[f12] as u32));
-                        let _ = std::io::stdout().flush();
-                    },
-                    // print_string: $a0=string(data index)
-                    4  => {
-                        print!("{}", get_string(memory, memory.registers[a0] as u32)?);
-                        let _ = std::io::stdout().flush();
-                    },
-                    // read_int: return $v0
-                    5  => {
-                        let input = read_line!();
-                        memory.registers[v0] = if let Ok(num) = input.trim().parse::<i32>() {
-                            num
-                        } else {
-                            0
-                        };
-                    },
-                    // read_float: return $f0
-                    6  => {
-                        let input = read_line!();
-                        memory.registers[f0] = if let Ok(num) = input.trim().parse::<f32>() {
-                            num.to_bits() as i32
-                        } else {
-                            0
-                        };
-                    },
-                    // read_string: $a0=buffer, $a1=length.  write buffer
-                    8  => {
-                        let input = read_line!();
-                        let mut index = memory.registers[a0] as usize - 1;
-                        if memory.static_data.len() < index + input.len() {  // TODO
-                            return Err(format!("not enough space for .data: {}", memory.registers[a0]).into());
-                        }
-                        for (i, ch) in input.into_bytes().iter().enumerate() {
-                            if i >= memory.registers[a1] as usize {
-                                break;
-                            }
-                            memory.static_data[index] = *ch;
-                            index += 1;
-                        }
-                    },
-                    // sbrk(allocate heap memory): $a0=size. $v0=address
-                    9 => {
-                        let size = memory.registers[a0];
-                        memory.registers[v0] = memory.malloc(size);
-                    },
-                    // exit
-                    10 => {
-                        //reset(&mut memory, &mut tokens);
-                        break;
-                    },
-                    // print_character
-                    11 => {
-                        print!("{}", memory.registers[a0] as u8 as char);
-                        let _ = std::io::stdout().flush();
-                    },
-                    // read character
-                    12 => {
-                        let input = read_line!();
-                        memory.registers[v0] = input.as_bytes()[0] as i32;
-                    },
-                    // exit2
-                    17 => {
-                        std::process::exit(memory.registers[a0]);
-                    },
-                    // random_int:
-                    // $a0 = random number(int)
-                    41 => {
-                        let rnd = rand::thread_rng().gen();
-                        memory.registers[a0] = rnd;
-                    },
-                    // random_int_range:
-                    // $a0 = random number(int)
-                    // $a1 = upper bound of range of returned valus.
-                    42 => {
-                        let rnd = rand::thread_rng().gen_range(0..memory.registers[a1]);
-                        memory.registers[a0] = rnd;
-                    },
-                    _ => return Err(format!("SYSCALL: invalid code: {}", memory.registers[v0]).into()),
-                }
-            },
-            InstructionKind::NOP => (),  // Do nothing
-
-            // My own
-            InstructionKind::PRTN =>
-                eval_myown(memory, tokens, InstructionKind::PRTN)?,
-            InstructionKind::PRTI =>
-                eval_myown(memory, tokens, InstructionKind::PRTI)?,
-            InstructionKind::PRTH =>
-                eval_myown(memory, tokens, InstructionKind::PRTH)?,
-            InstructionKind::PRTX =>
-                eval_myown(memory, tokens, InstructionKind::PRTX)?,
-            InstructionKind::PRTC =>
-                eval_myown(memory, tokens, InstructionKind::PRTC)?,
-            InstructionKind::PRTS =>
-                eval_myown(memory, tokens, InstructionKind::PRTS)?,
-            InstructionKind::RST => {
-                reset(memory, tokens);
-                break;
-            },
-
-            // FPU Instructions
-            InstructionKind::ADD_S =>
-                eval_fp_arithmetic(&mut memory.registers, tokens, InstructionKind::ADD_S)?,
-            InstructionKind::SUB_S =>
-                eval_fp_arithmetic(&mut memory.registers, tokens, InstructionKind::SUB_S)?,
-            InstructionKind::DIV_S =>
-                eval_fp_arithmetic(&mut memory.registers, tokens, InstructionKind::DIV_S)?,
-            InstructionKind::MUL_S =>
-                eval_fp_arithmetic(&mut memory.registers, tokens, InstructionKind::MUL_S)?,
-            InstructionKind::ABS_S => {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let rd_idx = tokens.expect_register()?;
-                memory.registers[rd_idx] = {
-                    tokens.consume().ok_or(CONSUME_ERR)?;
-                    let rs_idx = tokens.expect_register()?;
-                    f32::from_bits(memory.registers[rs_idx] as u32).abs().to_bits() as i32
-                };
-            },
-            InstructionKind::NEG_S => {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let rd_idx = tokens.expect_register()?;
-                memory.registers[rd_idx] = {
-                    tokens.consume().ok_or(CONSUME_ERR)?;
-                    let rs_idx = tokens.expect_register()?;
-                    (-f32::from_bits(memory.registers[rs_idx] as u32)).to_bits() as i32
+            } else {
+                return Err(invalid_operands(instruction));
+            }
+        },
+
+        // Exception, Interrupt
+        InstructionKind::SYSCALL =>
+            return eval_syscall(memory),
+        InstructionKind::BREAK => {
+            let code = match *operands {
+                [Operand::Integer(code)] => code as u32,
+                _ => 0,
+            };
+            return Err(Exception::Breakpoint(code).into());
+        },
+        InstructionKind::TEQ |
+        InstructionKind::TEQI =>
+            eval_trap(&memory.registers, operands, |x, y| x == y)?,
+        InstructionKind::TNE |
+        InstructionKind::TNEI =>
+            eval_trap(&memory.registers, operands, |x, y| x != y)?,
+        InstructionKind::TGE |
+        InstructionKind::TGEI =>
+            eval_trap(&memory.registers, operands, |x, y| x >= y)?,
+        InstructionKind::TGEU |
+        InstructionKind::TGEIU =>
+            eval_trap(&memory.registers, operands, |x, y| x as u32 >= y as u32)?,
+        InstructionKind::TLT |
+        InstructionKind::TLTI =>
+            eval_trap(&memory.registers, operands, |x, y| x < y)?,
+        InstructionKind::TLTU |
+        InstructionKind::TLTIU =>
+            eval_trap(&memory.registers, operands, |x, y| (x as u32) < y as u32)?,
+        // Privileged instructions
+        InstructionKind::ERET |
+        InstructionKind::MFC0 |
+        InstructionKind::MTC0 if !memory.kernel_mode() =>
+            return Err(Exception::CoprocessorUnusable.into()),
+        InstructionKind::ERET => {
+            memory.cp0[STATUS] &= !STATUS_EXL;
+            memory.pc = memory.cp0[EPC];
+        },
+        InstructionKind::MFC0 => {
+            if let [Operand::Register(rt_idx), Operand::Register(rd_idx @ 0..=31)] = *operands {
+                memory.registers[rt_idx] = memory.cp0[rd_idx] as i32;
+            } else {
+                return Err(invalid_operands(instruction));
+            }
+        },
+        InstructionKind::MTC0 => {
+            if let [Operand::Register(rt_idx), Operand::Register(rd_idx @ 0..=31)] = *operands {
+                memory.cp0.write(rd_idx, memory.registers[rt_idx] as u32);
+            } else {
+                return Err(invalid_operands(instruction));
+            }
+        },
+        InstructionKind::NOP => (),  // Do nothing
+
+        // My own
+        InstructionKind::PRTN |
+        InstructionKind::PRTI |
+        InstructionKind::PRTH |
+        InstructionKind::PRTX |
+        InstructionKind::PRTC |
+        InstructionKind::PRTS =>
+            eval_myown(memory, operands, instruction.kind)?,
+        InstructionKind::RST =>
+            unreachable!(),  // See `step()`
+
+        // FPU Instructions
+        InstructionKind::ADD_S |
+        InstructionKind::SUB_S |
+        InstructionKind::DIV_S |
+        InstructionKind::MUL_S |
+        InstructionKind::SQRT_S |
+        InstructionKind::RECIP_S |
+        InstructionKind::RSQRT_S |
+        InstructionKind::MADD_S =>
+            eval_fp_arithmetic(&mut memory.registers, operands, instruction.kind)?,
+        InstructionKind::ABS_S =>
+            eval_arithmetic(&mut memory.registers, operands, |x, _| {
+                Some(f32::from_bits(x as u32).abs().to_bits() as i32)
+            })?,
+        InstructionKind::NEG_S =>
+            eval_arithmetic(&mut memory.registers, operands, |x, _| {
+                Some((-f32::from_bits(x as u32)).to_bits() as i32)
+            })?,
+        InstructionKind::MTC1 => {
+            if let [Operand::Register(rs_idx), Operand::Register(rd_idx)] = *operands {
+                memory.registers[rd_idx] = memory.registers[rs_idx];
+            } else {
+                return Err(invalid_operands(instruction));
+            }
+        },
+        InstructionKind::CFC1 => {
+            if let [Operand::Register(rt_idx), Operand::Register(fs)] = *operands {
+                memory.registers[rt_idx] = match fs {
+                    fpu::FIR  => fpu::FIR_VALUE as i32,
+                    fpu::FCSR => memory.registers[fcsr],
+                    _ => return Err(invalid_operands(instruction)),
                 };
-            },
-            InstructionKind::MTC1 => {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let rs_idx = tokens.expect_register()?;
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let rd_idx = tokens.expect_register()?;
-                memory.registers[rd_idx] = (memory.registers[rs_idx] as f32).to_bits() as i32;
-            },
-            InstructionKind::BC1T => {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let target = tokens.expect_label()?;
-                if memory.registers[fcsr] & 0x00800000 != 0 {
-                    tokens.goto(target-1);
-                    continue;
+            } else {
+                return Err(invalid_operands(instruction));
+            }
+        },
+        InstructionKind::CTC1 => {
+            // Raises the floating point exception if the written cause is enabled
+            if let [Operand::Register(rt_idx), Operand::Register(fpu::FCSR)] = *operands {
+                memory.registers[fcsr] = (memory.registers[rt_idx] as u32 & fpu::FCSR_WRITABLE) as i32;
+                fpu::check(&memory.registers)?;
+            } else {
+                return Err(invalid_operands(instruction));
+            }
+        },
+        InstructionKind::MOVF |
+        InstructionKind::MOVT => {
+            let (rd_idx, rs_idx, cc) = match *operands {
+                [Operand::Register(rd_idx), Operand::Register(rs_idx)] => (rd_idx, rs_idx, 0),
+                [Operand::Register(rd_idx), Operand::Register(rs_idx), Operand::Integer(cc @ 0..=7)] =>
+                    (rd_idx, rs_idx, cc as usize),
+                _ => return Err(invalid_operands(instruction)),
+            };
+            if memory.registers.fcc(cc) == (instruction.kind == InstructionKind::MOVT) {
+                memory.registers[rd_idx] = memory.registers[rs_idx];
+            }
+        },
+        InstructionKind::MOVN_S |
+        InstructionKind::MOVZ_S => {
+            if let [Operand::Register(fd_idx), Operand::Register(fs_idx), Operand::Register(rt_idx)] = *operands {
+                if (memory.registers[rt_idx] != 0) == (instruction.kind == InstructionKind::MOVN_S) {
+                    memory.registers[fd_idx] = memory.registers[fs_idx];
                 }
-            },
-            InstructionKind::BC1F => {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let target = tokens.expect_label()?;
-                if memory.registers[fcsr] & 0x00800000 == 0 {
-                    tokens.goto(target-1);
-                    continue;
+            } else {
+                return Err(invalid_operands(instruction));
+            }
+        },
+        InstructionKind::BC1T |
+        InstructionKind::BC1F => {
+            let (cc, rest) = condition_code(operands);
+            if let [Operand::Target(target)] = *rest {
+                let condition = memory.registers.fcc(cc);
+                if condition == (instruction.kind == InstructionKind::BC1T) {
+                    memory.pc = target;
                 }
-            },
-            #[allow(clippy::float_cmp)]
-            InstructionKind::C_EQ_S =>
-                eval_fp_condition(&mut memory.registers, tokens, |x, y| x == y)?,
-            InstructionKind::C_LE_S =>
-                eval_fp_condition(&mut memory.registers, tokens, |x, y| x <= y)?,
-            InstructionKind::C_LT_S =>
-                eval_fp_condition(&mut memory.registers, tokens, |x, y| x < y)?,
-            InstructionKind::CVT_S_W => {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let rd_idx = tokens.expect_register()?;
-                memory.registers[rd_idx] = {
-                    tokens.consume().ok_or(CONSUME_ERR)?;
-                    let rs_idx = tokens.expect_register()?;
-                    (memory.registers[rs_idx] as f32).to_bits() as i32
-                };
-            },
-            InstructionKind::CVT_W_S => {
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let rs_idx = tokens.expect_register()?;
-                tokens.consume().ok_or(CONSUME_ERR)?;
-                let rd_idx = tokens.expect_register()?;
-                memory.registers[rd_idx] = (memory.registers[rs_idx] as f32).to_bits() as i32;
-            },
-
-            //_ => (),
-        }
-
-        // expect TokenKind::EOL
-        tokens.consume();
-        tokens.expect_eol()?;
-
-        if tokens.data_trace() {
-            display_data_per_4byte(&memory.static_data);
-        }
-        if tokens.stack_trace() {
-            display_stack(&memory.stack);
-        }
-        if tokens.register_trace() {
-            display_register(&memory.registers);
-        }
-        if tokens.fp_register_trace() {
-            display_fp_register(&memory.registers);
-        }
+            } else {
+                return Err(invalid_operands(instruction));
+            }
+        },
+        #[allow(clippy::float_cmp)]
+        InstructionKind::C_EQ_S =>
+            eval_fp_condition(&mut memory.registers, operands, false, |x, y| x == y)?,
+        InstructionKind::C_LE_S =>
+            eval_fp_condition(&mut memory.registers, operands, true, |x, y| x <= y)?,
+        InstructionKind::C_LT_S =>
+            eval_fp_condition(&mut memory.registers, operands, true, |x, y| x < y)?,
+        InstructionKind::LDC1 =>
+            eval_load_double(memory, operands)?,
+        InstructionKind::SDC1 =>
+            eval_store_double(memory, operands)?,
+        InstructionKind::ADD_D |
+        InstructionKind::SUB_D |
+        InstructionKind::MUL_D |
+        InstructionKind::DIV_D |
+        InstructionKind::SQRT_D |
+        InstructionKind::ABS_D |
+        InstructionKind::MOV_D |
+        InstructionKind::NEG_D =>
+            eval_fp_double(&mut memory.registers, operands, instruction.kind)?,
+        #[allow(clippy::float_cmp)]
+        InstructionKind::C_EQ_D =>
+            eval_fp_double_condition(&mut memory.registers, operands, false, |x, y| x == y)?,
+        InstructionKind::C_LE_D =>
+            eval_fp_double_condition(&mut memory.registers, operands, true, |x, y| x <= y)?,
+        InstructionKind::C_LT_D =>
+            eval_fp_double_condition(&mut memory.registers, operands, true, |x, y| x < y)?,
+        InstructionKind::CVT_S_W |
+        InstructionKind::CVT_W_S |
+        InstructionKind::ROUND_W_S |
+        InstructionKind::TRUNC_W_S |
+        InstructionKind::CEIL_W_S |
+        InstructionKind::FLOOR_W_S |
+        InstructionKind::CVT_D_S |
+        InstructionKind::CVT_D_W |
+        InstructionKind::CVT_S_D |
+        InstructionKind::CVT_W_D =>
+            eval_fp_convert(&mut memory.registers, operands, instruction.kind)?,
+
+        //_ => (),
     }
 
-    //display_data_per_4byte(&data);
-    //display_stack(&stack);
-    //display_register(&registers);
-    Ok(())
+    Ok(Status::Running)
+}
+
+fn eval_syscall(memory: &mut Memory) -> Result<Status, Box<dyn Error>> {
+    match memory.registers[v0] {
+        // print_int: $a0=integer
+        1  => {
+            memory.console.print(&format!("{}", memory.registers[a0]))?;
+        },
+        // print_float: $f12=integer
+        2  => {
+            memory.console.print(&format!("{}", f32::from_bits(memory.registers[f12] as u32)))?;
+        },
+        // print_double: $f12=double
+        3  => {
+            memory.console.print(&format!("{}", memory.registers.double(f12 as usize)))?;
+        },
+        // print_string: $a0=string(address)
+        4  => {
+            let s = memory.load_string(memory.registers[a0] as u32).map_err(Exception::AddressLoad)?;
+            memory.console.print(&s)?;
+        },
+        // read_int: return $v0
+        5  => {
+            let input = memory.console.read_line()?;
+            memory.registers[v0] = input.trim().parse::<i32>().unwrap_or_default();
+        },
+        // read_float: return $f0
+        6  => {
+            let input = memory.console.read_line()?;
+            memory.registers[f0] = if let Ok(num) = input.trim().parse::<f32>() {
+                num.to_bits() as i32
+            } else {
+                0
+            };
+        },
+        // read_double: return $f0
+        7  => {
+            let input = memory.console.read_line()?;
+            memory.registers.set_double(f0 as usize, input.trim().parse::<f64>().unwrap_or_default());
+        },
+        // read_string: $a0=buffer, $a1=length.  write buffer
+        8  => {
+            let input = memory.console.read_line()?;
+            let length = (memory.registers[a1].max(0) as usize).min(input.len());
+            memory.store_bytes(memory.registers[a0] as u32, &input.as_bytes()[..length])
+                .map_err(Exception::AddressStore)?;
+        },
+        // sbrk(allocate heap memory): $a0=size. $v0=address
+        9 => {
+            let size = memory.registers[a0];
+            memory.registers[v0] = memory.malloc(size);
+        },
+        // exit
+        10 => {
+            return Ok(Status::Exit(0));
+        },
+        // print_character
+        11 => {
+            memory.console.print(&format!("{}", memory.registers[a0] as u8 as char))?;
+        },
+        // read character
+        12 => {
+            let input = memory.console.read_line()?;
+            memory.registers[v0] = input.bytes().next().map_or(0, |ch| ch as i32);
+        },
+        // open: $a0=filename, $a1=flags (0: read, 1: write, 9: append). $v0=file descriptor (negative if error)
+        13 => {
+            let name = memory.load_string(memory.registers[a0] as u32).map_err(Exception::AddressLoad)?;
+            let flags = memory.registers[a1];
+            memory.registers[v0] = memory.files.open(&name, flags).unwrap_or(-1);
+        },
+        // read: $a0=file descriptor, $a1=buffer, $a2=length. $v0=number of bytes read (0 if EOF, negative if error)
+        14 => {
+            let mut buf = vec![0; memory.registers[a2].max(0) as usize];
+            let result = match memory.registers[a0] {
+                file::STDIN => memory.console.read(&mut buf).ok(),
+                fd => memory.files.read(fd, &mut buf),
+            };
+            memory.registers[v0] = match result {
+                Some(len) => {
+                    memory.store_bytes(memory.registers[a1] as u32, &buf[..len]).map_err(Exception::AddressStore)?;
+                    len as i32
+                },
+                None => -1,
+            };
+        },
+        // write: $a0=file descriptor, $a1=buffer, $a2=length. $v0=number of bytes written (negative if error)
+        15 => {
+            let bytes = memory.load_bytes(memory.registers[a1] as u32, memory.registers[a2].max(0) as usize)
+                .map_err(Exception::AddressLoad)?;
+            let result = match memory.registers[a0] {
+                file::STDOUT => memory.console.write(&bytes).ok().map(|_| bytes.len()),
+                file::STDERR => memory.console.write_error(&bytes).ok().map(|_| bytes.len()),
+                fd => memory.files.write(fd, &bytes),
+            };
+            memory.registers[v0] = result.map_or(-1, |len| len as i32);
+        },
+        // close: $a0=file descriptor
+        16 => {
+            memory.files.close(memory.registers[a0]);
+        },
+        // exit2
+        17 => {
+            return Ok(Status::Exit(memory.registers[a0]));
+        },
+        // time: $a0=low, $a1=high 32 bits of milliseconds since the epoch
+        30 => {
+            let now = memory.clock.now();
+            memory.registers[a0] = now as i32;
+            memory.registers[a1] = (now >> 32) as i32;
+        },
+        // sleep: $a0=milliseconds
+        32 => {
+            memory.clock.sleep(memory.registers[a0].max(0) as u64);
+        },
+        // print_int_hex
+        34 => {
+            memory.console.print(&format!("0x{:08x}", memory.registers[a0]))?;
+        },
+        // print_int_binary
+        35 => {
+            memory.console.print(&format!("{:032b}", memory.registers[a0]))?;
+        },
+        // print_int_unsigned
+        36 => {
+            memory.console.print(&format!("{}", memory.registers[a0] as u32))?;
+        },
+        // set_seed: $a0=id of the generator, $a1=seed
+        40 => {
+            memory.random.seed(memory.registers[a0], memory.registers[a1] as u32 as u64);
+        },
+        // random_int: $a0=id.  $a0=random number(int)
+        41 => {
+            memory.registers[a0] = memory.random.stream(memory.registers[a0]).gen();
+        },
+        // random_int_range: $a0=id, $a1=upper bound (exclusive).  $a0=random number(int)
+        42 => {
+            let bound = memory.registers[a1];
+            if bound <= 0 {
+                return Err(format!("random int range: upper bound must be positive: {}", bound).into());
+            }
+            memory.registers[a0] = memory.random.stream(memory.registers[a0]).gen_range(0..bound);
+        },
+        // random_float: $a0=id.  $f0=random number in [0, 1)
+        43 => {
+            let x: f32 = memory.random.stream(memory.registers[a0]).gen();
+            memory.registers[f0] = x.to_bits() as i32;
+        },
+        // random_double: $a0=id.  $f0=random number in [0, 1)
+        44 => {
+            let x: f64 = memory.random.stream(memory.registers[a0]).gen();
+            memory.registers.set_double(f0 as usize, x);
+        },
+        code => return Err(Exception::Syscall(code).into()),
+    }
+
+    Ok(Status::Running)
+}
+
+fn invalid_operands(instruction: &Instruction) -> Box<dyn Error> {
+    format!("{:?}: invalid operands: {:?}", instruction.kind, instruction.operands).into()
 }
 
 pub enum SignExtension {
@@ -590,236 +782,25 @@ pub enum SignExtension {
     Unsigned,
 }
 
-/// Return signed integer (32-bit)
+/// Load `byte` (1, 2 or 4) bytes from `address` as a 32-bit integer
 ///
 /// # Example
 ///
 /// ```rust,ignore
-/// let int: i32 = get_int(&memory, registers[a0], 4, SignExtension::Signed)?;
+/// let int: i32 = get_int(&memory, registers[a0] as u32, 4, SignExtension::Signed)?;
 /// ```
-///
-/// argument1: data:&[u8]
-/// argument2: stack:&[u8]
-/// argument3: index: isize  =>  stack(<=0) | data(0<)
-/// argument4: byte
-pub fn get_int(memory: &Memory, index: u32, byte: usize, se: SignExtension) -> Result<i32, String> {
-    let mut int: u32 = 0;
-
-    // data
-    if index < DYNAMIC_DATA_EXIT {
-        if index < DYNAMIC_DATA {
-            // static data
-            let index = (index - if STATIC_DATA <= index { STATIC_DATA } else { 0 } - 1) as usize;
-            if memory.static_data.len() < index+byte {
-                return Err(
-                    format!("get_int(): index out of bounds: the static-data len is {}, but the index is {}-{}",
-                    memory.static_data.len(), index, index+byte-1));
-            }
-            // Big Endian
-            for i in 0..byte {
-                int |= (memory.static_data[index+i] as u32) << ((byte-1-i) * 8);
-            }
-        } else {
-            // dynamic data
-            let index = (index - DYNAMIC_DATA) as usize;
-            if memory.dynamic_data.len() < index+byte {
-                return Err(
-                    format!("get_int(): index out of bounds: the dynamic-data len is {}, but the index is {}-{}",
-                    memory.dynamic_data.len(), index, index+byte-1));
-            }
-            // Big Endian
-            for i in 0..byte {
-                int |= (memory.dynamic_data[index+i] as u32) << ((byte-1-i) * 8);
-            }
-        }
-
-
-    // stack
-    } else {
-        let index = (STACK_SEGMENT - index) as usize;
-        if memory.stack.len() < index+byte {
-            return Err(
-                format!("get_int(): index out of bounds: the stack len is {}, but the index is {}-{}",
-                memory.stack.len(), index, index+byte-1));
-        }
-        // Big Endian
-        for i in 0..byte {
-            int |= (memory.stack[index+i] as u32) << ((byte-1-i) * 8);
-        }
-    }
+pub fn get_int(memory: &Memory, address: u32, byte: usize, se: SignExtension) -> Result<i32, Fault> {
+    let int = memory.load(address, byte)?;
+    let shift = 32 - 8 * byte as u32;
 
     match se {
-        SignExtension::Signed   => Ok(-(int as i32)),
-        SignExtension::Unsigned => Ok(  int as i32),
-    }
-}
-
-pub fn get_string(memory: &Memory, index: u32) -> Result<String, String> {
-    // data
-    if index < DYNAMIC_DATA_EXIT {
-        if index < DYNAMIC_DATA {
-            // static data
-            let mut i = (index - if STATIC_DATA <= index { STATIC_DATA } else { 0 } - 1) as usize;
-            let mut s = String::new();
-            let data_len = memory.static_data.len();
-
-            while i < data_len && memory.static_data[i] != 0 {
-                s.push(memory.static_data[i] as char);
-                i += 1;
-            }
-
-            Ok(s)
-        } else {
-            // dynamic data
-            let mut i = (index - DYNAMIC_DATA) as usize;
-            let mut s = String::new();
-            let data_len = memory.dynamic_data.len();
-
-            while i < data_len && memory.dynamic_data[i] != 0 {
-                s.push(memory.dynamic_data[i] as char);
-                i += 1;
-            }
-
-            Ok(s)
-        }
-
-    // stack
-    } else {
-        let mut i = (STACK_SEGMENT - index) as usize;
-        let mut s = String::new();
-        let stack_len = memory.stack.len();
-
-        while i < stack_len && memory.stack[i] != 0 {
-            s.push(memory.stack[i] as char);
-            i += 1;
-        }
-
-        Ok(s)
+        SignExtension::Signed   => Ok((int << shift) as i32 >> shift),
+        SignExtension::Unsigned => Ok(int as i32),
     }
 }
 
-fn reset(memory: &mut Memory, tokens: &mut Tokens) {
+fn reset(memory: &mut Memory, tokens: &mut Tokens, program: &mut Program) {
     memory.clear();
     tokens.init();
+    *program = Program::new();
 }
-
-/// Push to data: &Vec<u8> from .data segment's data
-fn data_analysis(tokens: &mut Tokens, memory: &mut Memory) {
-    let old_idx = tokens.idx();
-    tokens.goto(if old_idx == 0 {0} else {old_idx-1});
-
-    // Check all tokens
-    'outer: while tokens.consume().is_some() {
-        match tokens.token[tokens.idx()].kind {
-            TokenKind::INDICATE(IndicateKind::data) => {
-                tokens.data_area_now = true;
-                continue;
-            },
-            TokenKind::INDICATE(IndicateKind::text) => {
-                tokens.data_area_now = false;
-                continue;
-            },
-            TokenKind::INDICATE(IndicateKind::align(n)) => {
-                let padding = 2i32.pow(n as u32) as usize;
-                let i = memory.static_data.len() % padding;
-                memory.static_data.resize(memory.static_data.len() + i, 0);
-                continue;
-            },
-            _ => (),
-        }
-
-        // Ignore except .data segment
-        if tokens.data_area_now {
-            while {
-                // consume EOL
-                while TokenKind::EOL == *tokens.kind() {
-                    if tokens.next().is_some() {
-                        tokens.consume();
-                    } else {
-                        break 'outer;
-                    }
-                }
-
-                // until .text segment
-                if TokenKind::INDICATE(IndicateKind::text) == *tokens.kind() {
-                    tokens.data_area_now = false;
-                    continue 'outer;
-                }
-
-                // TokenKind::LABEL(usize) = data.len() + 1
-                if let TokenKind::LABEL(_, _, ref mut index) = &mut tokens.kind() {
-                    *index = Some(memory.static_data.len()+1);
-                    if tokens.next().is_some() && tokens.next().unwrap().kind == TokenKind::EOL {
-                        continue 'outer;
-                    }
-                }
-
-                match &*tokens.kind() {
-                    // Align 2^n
-                    TokenKind::INDICATE(IndicateKind::align(n)) => {
-                        let padding = 2i32.pow(*n as u32) as usize;
-                        let i = padding - memory.static_data.len() % padding;
-                        memory.static_data.resize(memory.static_data.len() + i, 0);
-                        continue 'outer;
-                    },
-                    TokenKind::INDICATE(IndicateKind::space(s)) => {
-                        memory.static_data.resize(memory.static_data.len() + *s as usize, 0);
-                        continue 'outer;
-                    },
-                    TokenKind::INDICATE(IndicateKind::ascii(s)) => {
-                        for ch in s.bytes() {
-                            memory.static_data.push(ch);
-                        }
-                        continue 'outer;
-                    },
-                    TokenKind::INDICATE(IndicateKind::asciiz(s)) => {
-                        for ch in s.bytes() {
-                            memory.static_data.push(ch);
-                        }
-                        memory.static_data.push(0);
-                        continue 'outer;
-                    },
-                    _ => (),
-                }
-
-                // until EOL
-                while {
-                    let still_indicate = match tokens.kind() {
-                        // Big Endian
-                        TokenKind::INDICATE(IndicateKind::word(w)) => {
-                            memory.static_data.push((*w>>24) as u8);
-                            memory.static_data.push((*w>>16) as u8);
-                            memory.static_data.push((*w>> 8) as u8);
-                            memory.static_data.push( *w      as u8);
-                            true
-                        },
-                        TokenKind::INDICATE(IndicateKind::half(h)) => {
-                            memory.static_data.push((*h>> 8) as u8);
-                            memory.static_data.push( *h      as u8);
-                            true
-                        },
-                        TokenKind::INDICATE(IndicateKind::byte(b)) => {
-                            memory.static_data.push(*b);
-                            true
-                        },
-                        TokenKind::INDICATE(IndicateKind::float(f)) => {
-                            memory.static_data.push((f.to_bits()>>24) as u8);
-                            memory.static_data.push((f.to_bits()>>16) as u8);
-                            memory.static_data.push((f.to_bits()>> 8) as u8);
-                            memory.static_data.push( f.to_bits()      as u8);
-                            true
-                        },
-                        _ => false,
-                    };
-
-                    still_indicate && tokens.consume().is_some()
-                } {}
-
-                tokens.consume().is_some()
-            } {}
-        }
-    }
-
-    tokens.goto(old_idx);
-}
-
@@ -69,3 +69,1101 @@ fn test_parse() {
     parse(&mut tokens, &mut memory).unwrap();
 }
 
+#[test]
+#[cfg(test)]
+fn test_step_machine() {
+    use crate::{Simulator, Execution};
+    use crate::token::register::RegisterKind;
+
+    let input = "\
+main:
+    li      $t0, 0
+    li      $t1, 1000
+loop:
+    addi    $t0, $t0, 1
+    blt     $t0, $t1, loop
+    addi    $sp, $sp, -4
+    sw      $t0, 0($sp)
+    lw      $t2, 0($sp)
+    jal     func
+    seq     $t4, $t2, 1000
+    li      $t5, 0x12345678
+    la      $t7, patch
+    li      $t8, 0x240e0007     # addiu $t6, $zero, 7
+    sw      $t8, 0($t7)
+patch:
+    nop
+    j       end
+func:
+    la      $t3, func
+    jr      $ra
+end:
+";
+
+    let run = |execution| {
+        let mut simulator = Simulator::new();
+        simulator.set_execution(execution);
+        simulator.load("main.asm", input).unwrap();
+        simulator.run().unwrap();
+        simulator
+    };
+    let source = run(Execution::Source);
+    let machine = run(Execution::Machine);
+
+    for r in [RegisterKind::t0, RegisterKind::t2, RegisterKind::t3, RegisterKind::t4, RegisterKind::t5, RegisterKind::ra] {
+        assert_eq!(source.register(r.clone()), machine.register(r));
+    }
+    assert_eq!(machine.register(RegisterKind::t4), 1);
+    assert_eq!(machine.register(RegisterKind::t5), 0x12345678);
+
+    // Self-modifying code
+    assert_eq!(source.register(RegisterKind::t6), 0);
+    assert_eq!(machine.register(RegisterKind::t6), 7);
+}
+
+#[test]
+#[cfg(test)]
+fn test_memory() {
+    use crate::Simulator;
+    use crate::token::memory::*;
+
+    let mut memory = Memory::default();
+    memory.store_u32(STACK_SEGMENT, 0x12345678).unwrap();
+    assert_eq!(memory.load_u8(STACK_SEGMENT), Ok(0x12));
+    assert_eq!(memory.load_u16(STACK_SEGMENT + 2), Ok(0x5678));
+    assert_eq!(memory.load_u32(STACK_SEGMENT - 4), Ok(0));
+    assert_eq!(memory.load_u32(STACK_SEGMENT + 1), Err(Fault::Unaligned(STACK_SEGMENT + 1)));
+    assert_eq!(memory.store_u8(0, 1), Err(Fault::Unmapped(0)));
+    assert_eq!(memory.load_u8(DYNAMIC_DATA), Err(Fault::Unmapped(DYNAMIC_DATA)));
+    assert_eq!(memory.malloc(8), DYNAMIC_DATA as i32);
+    assert_eq!(memory.load_u8(DYNAMIC_DATA + 7), Ok(0));
+
+    // Static data up to the page of the last data, the stack down to STACK_LIMIT
+    assert_eq!(memory.load_u8(STATIC_DATA), Err(Fault::Unmapped(STATIC_DATA)));
+    assert_eq!(memory.push_data(false, &[1, 2, 3]), Ok(STATIC_DATA));
+    assert_eq!(memory.load_u8(STATIC_DATA + 0xfff), Ok(0));
+    assert_eq!(memory.load_u8(STATIC_DATA + 0x1000), Err(Fault::Unmapped(STATIC_DATA + 0x1000)));
+    assert_eq!(memory.load_u8(STACK_LIMIT), Ok(0));
+    assert_eq!(memory.load_u8(STACK_LIMIT - 1), Err(Fault::Unmapped(STACK_LIMIT - 1)));
+
+    // .data cannot overflow into the heap
+    assert!(memory.reserve_data(false, DYNAMIC_DATA - STATIC_DATA - 3).is_ok());
+    assert!(memory.push_data(false, &[1]).is_err());
+    assert_eq!(memory.data_end(false), DYNAMIC_DATA);
+
+    // Faults abort with the location
+    for (input, error) in [
+        ("sw $t0, 0($zero)", "main.asm:1: address out of range: 0x00000000"),
+        ("lw $t0, 2($sp)",   "main.asm:1: unaligned address: 0x7ffffffe"),
+        ("lw $t0, 0x10001000", "main.asm:1: address out of range: 0x10001000"),
+    ] {
+        let mut simulator = Simulator::new();
+        simulator.load("main.asm", input).unwrap();
+        assert_eq!(simulator.run().unwrap_err().to_string(), error);
+    }
+
+    let mut simulator = Simulator::new();
+    simulator.load("main.asm", ".data\n.space 0x0ffffffc\n.word 1, 2").unwrap();
+    assert_eq!(simulator.run().unwrap_err().to_string(), "main.asm:3: .data segment overflows: 0x20000000 + 4 bytes");
+}
+
+#[test]
+#[cfg(test)]
+fn test_addressing() {
+    use crate::{Simulator, Execution};
+    use crate::token::register::RegisterKind;
+    use crate::token::memory::STATIC_DATA;
+
+    let input = "\
+.data
+x:  .word 1, 2, 3
+y:  .half -2
+.text
+    addi    $sp, $sp, -8
+    li      $t0, 7
+    sw      $t0, 4($sp)
+    lw      $s0, 0($sp)
+    lw      $s1, 0x4($sp)
+    addi    $sp, $sp, 8
+    lw      $s2, -4($sp)
+    li      $t1, 4
+    lw      $s3, x+4($t1)
+    lw      $s4, x+8
+    lh      $s5, y-0
+    la      $t2, x+4
+    la      $t3, -4($t2)
+    lw      $s6, 0x10000004
+    sw      $t0, x-0x0($zero)
+    lw      $s7, ($t3)
+";
+
+    for execution in [Execution::Source, Execution::Machine] {
+        let mut simulator = Simulator::new();
+        simulator.set_execution(execution);
+        simulator.load("main.asm", input).unwrap();
+        simulator.run().unwrap();
+
+        let registers: Vec<i32> = (16..=23).map(|i| simulator.registers()[i]).collect();
+        assert_eq!(registers, [0, 7, 7, 3, 3, -2, 2, 7]);
+        assert_eq!(simulator.register(RegisterKind::t2), (STATIC_DATA + 4) as i32);
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_expression() {
+    use crate::{Simulator, Execution};
+    use crate::token::memory::STATIC_DATA;
+
+    let input = "\
+.eqv    N       4
+.eqv    RESULT  $s0
+.set    WORDS,  N * 2
+.data
+pad:    .space  0x8000 - 4
+x:      .word   WORDS:2, ~0, 1 << 31 >> 31
+buf:    .space  WORDS * N
+.text
+    li      RESULT, (N + 1) * 3 - 1
+    lui     $t0, %hi(x)
+    addiu   $s1, $t0, %lo(x)
+    lui     $t1, %hi(x+12)
+    lw      $s2, %lo(x+12)($t1)
+    lw      $s3, x + N * 2
+    la      $s4, buf
+    sub     $s4, $s4, $s1
+    li      $s5, 0x80000000 >> 28 | 0x3 & ~1
+";
+
+    for execution in [Execution::Source, Execution::Machine] {
+        let mut simulator = Simulator::new();
+        simulator.set_execution(execution);
+        simulator.load("main.asm", input).unwrap();
+        simulator.run().unwrap();
+
+        let registers: Vec<i32> = (16..=21).map(|i| simulator.registers()[i]).collect();
+        assert_eq!(registers, [14, (STATIC_DATA + 0x8000 - 4) as i32, 1, -1, 16, 0xa]);
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_relocation_kdata() {
+    use crate::{Simulator, Execution};
+    use crate::token::memory::TEXT_SEGMENT;
+
+    // k is 0x90009000: %hi(k) is 0x9001 and %lo(k) is -0x7000
+    let input = "\
+.kdata
+        .space  0x9000
+k:      .word   5
+.text
+    addiu   $s0, $zero, %hi(k)
+    ori     $s1, $zero, %lo(k)
+    addi    $s2, $zero, %lo(k)
+    lui     $s3, %hi(k)
+    addiu   $s3, $s3, %lo(k)
+    la      $s4, end
+end:
+";
+
+    for execution in [Execution::Source, Execution::Machine] {
+        let mut simulator = Simulator::new();
+        simulator.set_execution(execution);
+        simulator.load("main.asm", input).unwrap();
+        simulator.run().unwrap();
+
+        let registers: Vec<u32> = (16..=20).map(|i| simulator.registers()[i] as u32).collect();
+        assert_eq!(registers, [0xffff9001, 0x9000, 0xffff9000, 0x90009000, TEXT_SEGMENT + 28]);
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_endian() {
+    use crate::{Simulator, Execution, Endian};
+    use crate::token::register::RegisterKind;
+
+    let input = "\
+.data
+w:  .word 0x11223344
+h:  .half 0x5566
+.text
+    lbu     $t0, w
+    lhu     $t1, w
+    lbu     $t2, h
+    la      $t3, w
+    li      $t4, 0x77
+    sb      $t4, 0($t3)
+    lw      $t5, w
+";
+
+    for execution in [Execution::Source, Execution::Machine] {
+        for (endian, t0, t1, t2, t5) in [
+            (Endian::Big,    0x11, 0x1122, 0x55, 0x77223344),
+            (Endian::Little, 0x44, 0x3344, 0x66, 0x11223377),
+        ] {
+            let mut simulator = Simulator::new();
+            simulator.set_execution(execution);
+            simulator.set_endian(endian);
+            simulator.load("main.asm", input).unwrap();
+            simulator.run().unwrap();
+
+            assert_eq!(simulator.register(RegisterKind::t0), t0);
+            assert_eq!(simulator.register(RegisterKind::t1), t1);
+            assert_eq!(simulator.register(RegisterKind::t2), t2);
+            assert_eq!(simulator.register(RegisterKind::t5), t5);
+        }
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_exception() {
+    use crate::{Simulator, Execution};
+    use crate::token::register::RegisterKind;
+    use crate::token::cp0::*;
+
+    let handler: Vec<u8> = [
+        0x401a7000u32,  // mfc0  $k0, $14       # EPC
+        0x275a0004,     // addiu $k0, $k0, 4
+        0x409a7000,     // mtc0  $k0, $14
+        0x40196800,     // mfc0  $t9, $13       # Cause
+        0x26100001,     // addiu $s0, $s0, 1
+        0x42000018,     // eret
+    ].iter().flat_map(|w| w.to_be_bytes()).collect();
+
+    let input = "\
+    li      $t0, 0x7fffffff
+    addi    $t1, $t0, 1
+    lw      $t2, 0($zero)
+    move    $t3, $t9
+    break
+    li      $t4, 1
+";
+
+    for execution in [Execution::Source, Execution::Machine] {
+        let mut simulator = Simulator::new();
+        simulator.set_execution(execution);
+        simulator.memory_mut().map(EXCEPTION_HANDLER, &handler).unwrap();
+        simulator.load("main.asm", input).unwrap();
+        simulator.run().unwrap();
+
+        assert_eq!(simulator.register(RegisterKind::s0), 3);
+        assert_eq!(simulator.register(RegisterKind::t1), 0);
+        assert_eq!(simulator.register(RegisterKind::t3), 4 << 2);  // AdEL
+        assert_eq!(simulator.register(RegisterKind::t9), 9 << 2);  // Bp
+        assert_eq!(simulator.register(RegisterKind::t4), 1);
+        assert_eq!(simulator.memory().cp0[STATUS] & STATUS_EXL, 0);
+        assert_eq!(simulator.memory().cp0[BADVADDR], 0);
+    }
+
+    // No handler
+    let mut simulator = Simulator::new();
+    simulator.load("main.asm", input).unwrap();
+    assert_eq!(simulator.run().unwrap_err().to_string(), "main.asm:2: panicked at 'arithmetic operation overflowed'");
+}
+
+#[test]
+#[cfg(test)]
+fn test_kernel() {
+    use crate::{Simulator, Execution};
+    use crate::token::register::RegisterKind;
+    use crate::token::memory::*;
+    use crate::token::cp0::*;
+
+    // Trap handler file like SPIM's exceptions.s
+    let handler = "\
+    .kdata
+count:  .word 0
+    .ktext 0x80000180
+    lw      $k0, count
+    addi    $k0, $k0, 1
+    sw      $k0, count
+    move    $s1, $k0
+    mfc0    $k1, $14
+    addiu   $k1, $k1, 4
+    mtc0    $k1, $14
+    eret
+";
+
+    let input = "\
+main:
+    la      $t0, count
+    lw      $t1, 0($t0)
+    sw      $t0, 0($t0)
+    li      $t2, 1
+";
+
+    for execution in [Execution::Source, Execution::Machine] {
+        let mut simulator = Simulator::new();
+        simulator.set_execution(execution);
+        simulator.load("exceptions.s", handler).unwrap();
+        simulator.load("main.asm", input).unwrap();
+        simulator.run().unwrap();
+
+        assert_eq!(simulator.program().symbols().get("main").unwrap().address, TEXT_SEGMENT);
+        assert_eq!(simulator.register(RegisterKind::t0), KDATA_SEGMENT as i32);
+        assert_eq!(simulator.register(RegisterKind::t1), 0);
+        assert_eq!(simulator.register(RegisterKind::t2), 1);
+        assert_eq!(simulator.register(RegisterKind::s1), 2);
+        assert_eq!(simulator.memory().cp0[BADVADDR], KDATA_SEGMENT);
+        assert_eq!(simulator.memory().load_u32(KDATA_SEGMENT), Err(Fault::Kernel(KDATA_SEGMENT)));
+    }
+
+    // User mode cannot jump into the kernel text
+    let mut simulator = Simulator::new();
+    simulator.load("kernel.s", ".ktext\nkernel:\n    nop").unwrap();
+    simulator.load("main.asm", "    j kernel").unwrap();
+    assert_eq!(simulator.run().unwrap_err().to_string(), "kernel.s:3: kernel address in user mode: 0x80000000");
+}
+
+#[test]
+#[cfg(test)]
+fn test_privileged() {
+    use crate::{Simulator, Execution};
+    use crate::token::register::RegisterKind;
+    use crate::token::cp0::*;
+
+    let handler = "\
+    .ktext 0x80000180
+    mfc0    $k0, $13        # Cause
+    srl     $k0, $k0, 2
+    add     $s0, $s0, $k0
+    mfc0    $k0, $14
+    addiu   $k0, $k0, 4
+    mtc0    $k0, $14
+    eret
+";
+
+    // User mode cannot leave user mode or jump into the kernel by CP0
+    let input = "\
+    mtc0    $zero, $12
+    la      $t0, 0x80000000
+    mtc0    $t0, $14
+    eret
+    mfc0    $t1, $12
+    li      $t2, 1
+";
+
+    for execution in [Execution::Source, Execution::Machine] {
+        let mut simulator = Simulator::new();
+        simulator.set_execution(execution);
+        simulator.load("exceptions.s", handler).unwrap();
+        simulator.load("main.asm", input).unwrap();
+        simulator.run().unwrap();
+
+        assert_eq!(simulator.register(RegisterKind::s0), 4 * 11);  // CpU
+        assert_eq!(simulator.register(RegisterKind::t1), 0);
+        assert_eq!(simulator.register(RegisterKind::t2), 1);
+        assert_ne!(simulator.memory().cp0[STATUS] & STATUS_UM, 0);
+    }
+
+    let mut simulator = Simulator::new();
+    simulator.load("main.asm", input).unwrap();
+    assert_eq!(simulator.run().unwrap_err().to_string(), "main.asm:1: coprocessor 0 is unusable in user mode");
+}
+
+#[test]
+#[cfg(test)]
+fn test_interrupt() {
+    use crate::{Simulator, Execution};
+    use crate::token::register::RegisterKind;
+    use crate::token::cp0::*;
+
+    let handler = "\
+    .ktext 0x80000180
+    mfc0    $s1, $13        # Cause
+    addi    $s0, $s0, 1
+    mfc0    $k0, $9         # Count
+    addiu   $k0, $k0, 10
+    mtc0    $k0, $11        # Compare (acknowledge)
+    eret
+";
+
+    let input = "\
+main:
+    li      $t0, 0
+    li      $t1, 100
+loop:
+    addi    $t0, $t0, 1
+    blt     $t0, $t1, loop
+";
+
+    for execution in [Execution::Source, Execution::Machine] {
+        for enabled in [true, false] {
+            let mut simulator = Simulator::new();
+            simulator.set_execution(execution);
+            simulator.load("exceptions.s", handler).unwrap();
+            simulator.load("main.asm", input).unwrap();
+            simulator.set_timer(10);
+            if !enabled {
+                simulator.memory_mut().cp0[STATUS] &= !STATUS_IE;
+            }
+            simulator.run().unwrap();
+
+            // Interrupted instructions are not skipped
+            assert_eq!(simulator.register(RegisterKind::t0), 100);
+            assert_eq!(simulator.register(RegisterKind::s0) > 10, enabled);
+            if enabled {
+                let cause = simulator.register(RegisterKind::s1) as u32;
+                assert_eq!(cause & CAUSE_EXC_CODE, 0);
+                assert_eq!(cause & CAUSE_IP, hardware_interrupt(TIMER_INTERRUPT));
+            }
+        }
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_interrupt_count() {
+    use crate::{Simulator, Execution};
+    use crate::token::register::RegisterKind;
+
+    let handler = "\
+    .ktext 0x80000180
+    addi    $s0, $s0, 1
+    mfc0    $k0, $9         # Count
+    addiu   $k0, $k0, 7
+    mtc0    $k0, $11        # Compare (acknowledge)
+    eret
+";
+
+    // Pseudo instructions of 2-3 words
+    let input = "\
+main:
+    li      $t0, 0
+loop:
+    li      $t1, 0x12345678
+    addi    $t0, $t0, 1
+    blt     $t0, 200, loop
+";
+
+    let counts: Vec<i32> = [Execution::Source, Execution::Machine].iter().map(|&execution| {
+        let mut simulator = Simulator::new();
+        simulator.set_execution(execution);
+        simulator.load("exceptions.s", handler).unwrap();
+        simulator.load("main.asm", input).unwrap();
+        simulator.set_timer(7);
+        simulator.run().unwrap();
+        assert_eq!(simulator.register(RegisterKind::t0), 200);
+        simulator.register(RegisterKind::s0)
+    }).collect();
+    assert!(counts[0] > 100);
+    assert_eq!(counts[0], counts[1]);
+}
+
+#[test]
+#[cfg(test)]
+fn test_exception_in_pseudo() {
+    use crate::{Simulator, Execution};
+    use crate::token::register::RegisterKind;
+    use crate::token::memory::TEXT_SEGMENT;
+
+    let handler = "\
+    .ktext 0x80000180
+    mfc0    $k0, $14        # EPC
+    move    $s1, $k0
+    mfc0    $s2, $9         # Count
+    addiu   $k0, $k0, 4
+    mtc0    $k0, $14
+    eret
+";
+
+    // bne $t2, $zero, 1; break; div $t1, $t2; mflo $t0
+    let input = "\
+    li      $t1, 7
+    div     $t0, $t1, $t2
+    li      $s0, 1
+";
+
+    // lui $at, upper; sw $t1, lower($at) (AdES after $at is written)
+    let store = "\
+    .data
+value:  .word 0
+    .text
+    li      $t1, 7
+    sw      $t1, value+2
+    lw      $s0, value
+";
+
+    for execution in [Execution::Source, Execution::Machine] {
+        let mut simulator = Simulator::new();
+        simulator.set_execution(execution);
+        simulator.load("exceptions.s", handler).unwrap();
+        simulator.load("main.asm", input).unwrap();
+        simulator.run().unwrap();
+
+        // `break` is the 2nd word of `div`
+        assert_eq!(simulator.register(RegisterKind::s1) as u32, TEXT_SEGMENT + 8);
+        assert_eq!(simulator.register(RegisterKind::s2), 1 + 2 + 3);  // li, 2 words of `div` and the handler
+        assert_eq!(simulator.register(RegisterKind::s0), 1);
+
+        let mut simulator = Simulator::new();
+        simulator.set_execution(execution);
+        simulator.load("exceptions.s", handler).unwrap();
+        simulator.load("main.asm", store).unwrap();
+        simulator.run().unwrap();
+
+        // `sw` is the 2nd word and stores nothing
+        assert_eq!(simulator.register(RegisterKind::s1) as u32, TEXT_SEGMENT + 8);
+        assert_eq!(simulator.register(RegisterKind::s2), 1 + 2 + 3);
+        assert_eq!(simulator.register(RegisterKind::s0), 0);
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_trap() {
+    use crate::{Simulator, Execution};
+    use crate::token::register::RegisterKind;
+    use crate::token::cp0::*;
+
+    let handler = "\
+    .ktext 0x80000180
+    mfc0    $k0, $14
+    addiu   $k0, $k0, 4
+    mtc0    $k0, $14
+    addi    $s0, $s0, 1
+    mfc0    $s1, $13
+    eret
+";
+
+    let input = "\
+    li      $t0, -1
+    li      $t1, 1
+    teq     $t0, $t1
+    tne     $t0, $t1        # trap
+    tge     $t0, $t1
+    tgeu    $t0, $t1        # trap
+    tlt     $t1, $t0
+    tltu    $t0, $t1
+    teqi    $t0, -1         # trap
+    tnei    $t0, -1
+    tgei    $t0, 0
+    tgeiu   $t0, 0          # trap
+    tlti    $t0, 0          # trap
+    tltiu   $t1, -1         # trap
+    tnei    $t1, 0x12345    # trap
+";
+
+    for execution in [Execution::Source, Execution::Machine] {
+        let mut simulator = Simulator::new();
+        simulator.set_execution(execution);
+        simulator.load("exceptions.s", handler).unwrap();
+        simulator.load("main.asm", input).unwrap();
+        simulator.run().unwrap();
+
+        assert_eq!(simulator.register(RegisterKind::s0), 7);
+        assert_eq!(simulator.register(RegisterKind::s1) as u32 & CAUSE_EXC_CODE, 13 << 2);
+    }
+
+    // No handler
+    let mut simulator = Simulator::new();
+    simulator.load("main.asm", "    li $t0, 1\n    teqi $t0, 1").unwrap();
+    assert_eq!(simulator.run().unwrap_err().to_string(), "main.asm:2: trap: condition is true");
+}
+
+#[test]
+#[cfg(test)]
+fn test_integer() {
+    use crate::{Simulator, Execution};
+    use crate::token::register::RegisterKind;
+
+    let handler = "\
+    .ktext 0x80000180
+    mfc0    $k0, $14
+    addiu   $k0, $k0, 4
+    mtc0    $k0, $14
+    addi    $s0, $s0, 1
+    eret
+";
+
+    let input = "\
+    li      $t0, -1
+    mtlo    $t0
+    li      $t1, 1
+    maddu   $t1, $t1            # hi:lo = 0x1_00000000
+    mfhi    $s1
+    msub    $t1, $t1            # hi:lo = 0x0_ffffffff
+    mfhi    $s2
+    mflo    $s3
+    lui     $t0, 0x8000
+    div     $s4, $t0, -1        # overflow wraps around
+    rem     $s5, $t0, -1
+    li      $t2, 7
+    div     $t2, $zero          # hi and lo are unchanged
+    mflo    $s6
+    div     $t3, $t2, $zero     # break
+    remu    $t3, $t2, $zero     # break
+    mulo    $t3, $t0, $t0       # break
+    mulou   $t3, $t2, $t0       # break
+    mulo    $s7, $t2, $t2
+    li      $t5, 33
+    sllv    $t6, $t2, $t5       # shift by 1
+    srav    $t7, $t0, $t5
+";
+
+    for execution in [Execution::Source, Execution::Machine] {
+        let mut simulator = Simulator::new();
+        simulator.set_execution(execution);
+        simulator.load("exceptions.s", handler).unwrap();
+        simulator.load("main.asm", input).unwrap();
+        simulator.run().unwrap();
+
+        let register = |kind| simulator.register(kind);
+        assert_eq!(register(RegisterKind::s0), 4);
+        assert_eq!(register(RegisterKind::s1), 1);
+        assert_eq!((register(RegisterKind::s2), register(RegisterKind::s3)), (0, -1));
+        assert_eq!((register(RegisterKind::s4), register(RegisterKind::s5)), (i32::MIN, 0));
+        assert_eq!(register(RegisterKind::s6), i32::MIN);
+        assert_eq!(register(RegisterKind::s7), 49);
+        assert_eq!((register(RegisterKind::t6), register(RegisterKind::t7)), (14, i32::MIN >> 1));
+    }
+
+    // No handler
+    let mut simulator = Simulator::new();
+    simulator.load("main.asm", "    li $t0, 1\n    div $t0, $t0, $zero").unwrap();
+    assert_eq!(simulator.run().unwrap_err().to_string(), "main.asm:2: break: 0");
+}
+
+#[test]
+#[cfg(test)]
+fn test_unsigned() {
+    use crate::{Simulator, Execution};
+    use crate::token::register::RegisterKind;
+
+    // Set instructions into $s0-$s7, and branches not taken into the bits of $v1
+    let sets = ["slt", "sltu", "sge", "sgeu", "sgt", "sgtu", "sle", "sleu"];
+    let branches = ["blt", "bltu", "bge", "bgeu", "bgt", "bgtu", "ble", "bleu"];
+    let values = [0, 1, -1, i32::MAX, i32::MIN];
+
+    for &x in &values {
+        for &y in &values {
+            let mut input = format!("    li $t0, {}\n    li $t1, {}\n", x, y);
+            for (i, set) in sets.iter().enumerate() {
+                input += &format!("    {} $s{}, $t0, $t1\n", set, i);
+            }
+            for (i, branch) in branches.iter().enumerate() {
+                input += &format!("    {} $t0, $t1, l{}\n    ori $v1, $v1, {}\nl{}:\n", branch, i, 1 << i, i);
+            }
+            input += &format!("    sltiu $t2, $t0, {}\n    bltu $t0, {}, end\n    li $t3, 1\nend:\n", y, y);
+
+            let (ux, uy) = (x as u32, y as u32);
+            let expected = [x < y, ux < uy, x >= y, ux >= uy, x > y, ux > uy, x <= y, ux <= uy];
+            let not_taken = expected.iter().enumerate()
+                .filter(|(_, &taken)| !taken)
+                .fold(0, |bits, (i, _)| bits | 1 << i);
+
+            for execution in [Execution::Source, Execution::Machine] {
+                let mut simulator = Simulator::new();
+                simulator.set_execution(execution);
+                simulator.load("main.asm", &input).unwrap();
+                simulator.run().unwrap();
+
+                let registers = simulator.registers();
+                let results: Vec<bool> = (0..8).map(|i| registers[RegisterKind::s0 as usize + i] == 1).collect();
+                assert_eq!(results, expected, "{} {}", x, y);
+                assert_eq!(simulator.register(RegisterKind::v1), not_taken, "{} {}", x, y);
+                assert_eq!(simulator.register(RegisterKind::t2), (ux < uy) as i32, "{} {}", x, y);
+                assert_eq!(simulator.register(RegisterKind::t3), (ux >= uy) as i32, "{} {}", x, y);
+            }
+        }
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_double() {
+    use crate::{Simulator, Execution, Endian};
+
+    let input = "\
+.data
+b:  .byte 1
+x:  .double 2.0, 0.1
+y:  .double 0.0
+w:  .word 7
+.text
+    l.d     $f0, x
+    la      $t0, x
+    ldc1    $f2, 8($t0)
+    add.d   $f4, $f0, $f2       # 2.1
+    sub.d   $f6, $f0, $f2       # 1.9
+    mul.d   $f8, $f0, $f2       # 0.2
+    div.d   $f10, $f2, $f0      # 0.05
+    sqrt.d  $f12, $f0
+    neg.d   $f14, $f0
+    abs.d   $f14, $f14
+    mov.d   $f16, $f14
+    s.d     $f4, y
+    c.lt.d  $f2, $f0
+    bc1f    end
+    lwc1    $f18, w
+    cvt.d.w $f18, $f18          # 7.0
+    cvt.s.d $f20, $f2
+    cvt.d.s $f22, $f20
+    cvt.w.d $f24, $f4           # 2
+end:
+";
+
+    for execution in [Execution::Source, Execution::Machine] {
+        for endian in [Endian::Big, Endian::Little] {
+            let mut simulator = Simulator::new();
+            simulator.set_execution(execution);
+            simulator.set_endian(endian);
+            simulator.load("main.asm", input).unwrap();
+            simulator.run().unwrap();
+
+            let registers = simulator.registers();
+            let f = |n: usize| registers.double(32 + n);
+            assert_eq!(f(0), 2.0);
+            assert_eq!(f(2), 0.1);
+            assert_eq!(f(4), 2.0 + 0.1);
+            assert_eq!(f(6), 2.0 - 0.1);
+            assert_eq!(f(8), 2.0 * 0.1);
+            assert_eq!(f(10), 0.1 / 2.0);
+            assert_eq!(f(12), 2.0f64.sqrt());
+            assert_eq!(f(16), 2.0);
+            assert_eq!(f(18), 7.0);
+            assert_eq!(f(22), 0.1f32 as f64);
+            assert_eq!(registers[32 + 24], 2);
+
+            // Aligned to 8 bytes, stored in the byte order
+            let address = crate::token::memory::STATIC_DATA + 24;
+            assert_eq!(simulator.memory().load_u64(address), Ok((2.0f64 + 0.1).to_bits()));
+        }
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_fpu() {
+    use crate::{Simulator, Execution};
+
+    let input = "\
+.data
+x:  .float 2.5
+y:  .float -1.5
+z:  .float 4.0
+.text
+    li      $t0, 0x40490fdb     # pi
+    mtc1    $t0, $f0
+    mfc1    $t1, $f0
+    mov.s   $f1, $f0
+    lwc1    $f2, x
+    lwc1    $f3, y
+    lwc1    $f4, z
+    sqrt.s  $f5, $f4            # 2
+    recip.s $f6, $f4            # 0.25
+    rsqrt.s $f7, $f4            # 0.5
+    madd.s  $f8, $f2, $f3, $f4  # 2.5 + -1.5 * 4
+    round.w.s $f10, $f2         # 2
+    trunc.w.s $f11, $f3         # -1
+    ceil.w.s  $f12, $f3         # -1
+    floor.w.s $f13, $f3         # -2
+    round.w.s $f14, $f3         # -2
+    c.lt.s  3, $f3, $f2
+    c.lt.s  $f2, $f3
+    li      $t2, 0
+    bc1t    3, taken
+    li      $t2, 1
+taken:
+    li      $t3, 5
+    movt    $t4, $t3, 3
+    movt    $t5, $t3
+    movn.s  $f15, $f4, $t3
+    movz.s  $f16, $f4, $t3
+";
+
+    for execution in [Execution::Source, Execution::Machine] {
+        let mut simulator = Simulator::new();
+        simulator.set_execution(execution);
+        simulator.load("main.asm", input).unwrap();
+        simulator.run().unwrap();
+
+        let registers = simulator.registers();
+        let f = |n: usize| f32::from_bits(registers[32 + n] as u32);
+        assert_eq!(registers[32], 0x40490fdb);
+        assert_eq!(registers[9], 0x40490fdb);
+        assert_eq!(registers[33], 0x40490fdb);
+        assert_eq!(f(5), 2.0);
+        assert_eq!(f(6), 0.25);
+        assert_eq!(f(7), 0.5);
+        assert_eq!(f(8), -3.5);
+        let words: Vec<i32> = (10..=14).map(|n| registers[32 + n]).collect();
+        assert_eq!(words, [2, -1, -1, -2, -2]);
+        assert!(registers.fcc(3));
+        assert!(!registers.fcc(0));
+        assert_eq!(registers[10], 0);
+        assert_eq!(registers[12], 5);
+        assert_eq!(registers[13], 0);
+        assert_eq!(f(15), 4.0);
+        assert_eq!(f(16), 0.0);
+    }
+}
+
+#[test]
+#[cfg(test)]
+fn test_fcsr() {
+    use crate::{Simulator, Execution};
+    use crate::token::register::RegisterKind;
+
+    let input = "\
+.data
+one:    .float 1.0
+three:  .float 3.0
+big:    .float 3e38
+half:   .float 2.5
+.text
+    lwc1    $f1, one
+    lwc1    $f3, three
+    lwc1    $f4, big
+    lwc1    $f5, half
+    div.s   $f6, $f1, $f3       # nearest
+    li      $t0, 1              # toward zero
+    ctc1    $t0, $31
+    div.s   $f7, $f1, $f3
+    cfc1    $s0, $31            # cause and flags: inexact
+    mul.s   $f8, $f4, $f4       # overflow: max
+    li      $t0, 2              # toward +infinity
+    ctc1    $t0, $31
+    cvt.w.s $f9, $f5            # 3
+    round.w.s $f10, $f5         # 2
+    mtc1    $zero, $f0
+    div.s   $f11, $f1, $f0      # division by zero
+    neg.s   $f12, $f1
+    sqrt.s  $f12, $f12          # invalid
+    cfc1    $s1, $31
+    mul.s   $f13, $f1, $f3      # exact
+    cfc1    $s2, $31
+";
+
+    for execution in [Execution::Source, Execution::Machine] {
+        let mut simulator = Simulator::new();
+        simulator.set_execution(execution);
+        simulator.load("main.asm", input).unwrap();
+        simulator.run().unwrap();
+
+        let registers = simulator.registers();
+        let f = |n: usize| f32::from_bits(registers[32 + n] as u32);
+        assert_eq!(f(6), 1.0 / 3.0);
+        assert_eq!(f(7), f32::from_bits((1.0f32 / 3.0).to_bits() - 1));
+        assert_eq!(simulator.register(RegisterKind::s0), 0x1 << 12 | 0x1 << 2 | 1);
+        assert_eq!(f(8), f32::MAX);
+        assert_eq!(registers[32 + 9], 3);
+        assert_eq!(registers[32 + 10], 2);
+        assert_eq!(f(11), f32::INFINITY);
+        assert!(f(12).is_nan());
+        assert_eq!(simulator.register(RegisterKind::s1), 0x10 << 12 | 0x19 << 2 | 2);
+        assert_eq!(simulator.register(RegisterKind::s2), 0x19 << 2 | 2);
+    }
+
+    // Enabled exception traps, and the destination is not written
+    let input = "\
+    li      $t0, 0x400          # enable division by zero
+    ctc1    $t0, $31
+    li.s    $f1, 1.0
+    mtc1    $zero, $f0
+    div.s   $f2, $f1, $f0
+";
+    let mut simulator = Simulator::new();
+    simulator.load("main.asm", input).unwrap();
+    assert_eq!(simulator.run().unwrap_err().to_string(), "main.asm:5: floating point exception: division by zero");
+    assert_eq!(simulator.registers()[32 + 2], 0);
+}
+
+#[test]
+#[cfg(test)]
+fn test_file() {
+    use crate::Simulator;
+    use crate::token::register::RegisterKind;
+
+    let root = std::env::temp_dir().join(format!("mipsi-test-file-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("input.txt"), "hello").unwrap();
+
+    let input = "\
+.data
+input:  .asciiz \"input.txt\"
+output: .asciiz \"./output.txt\"
+outside: .asciiz \"../input.txt\"
+buf:    .space 16
+.text
+    li      $v0, 13
+    la      $a0, input
+    li      $a1, 0
+    syscall
+    move    $s0, $v0            # 3
+    li      $v0, 14
+    move    $a0, $s0
+    la      $a1, buf
+    li      $a2, 16
+    syscall
+    move    $s1, $v0            # 5
+    li      $v0, 13
+    la      $a0, output
+    li      $a1, 1
+    syscall
+    move    $s2, $v0            # 4
+    li      $v0, 15
+    move    $a0, $s2
+    la      $a1, buf
+    move    $a2, $s1
+    syscall
+    li      $v0, 16
+    move    $a0, $s2
+    syscall
+    li      $v0, 13
+    la      $a0, output
+    li      $a1, 9
+    syscall
+    move    $s3, $v0            # 4 (reused)
+    li      $v0, 15
+    move    $a0, $s3
+    la      $a1, buf
+    li      $a2, 1
+    syscall
+    li      $v0, 13
+    la      $a0, outside
+    li      $a1, 0
+    syscall
+    move    $s4, $v0            # -1
+";
+
+    let mut simulator = Simulator::new();
+    simulator.set_fs_root(&root).unwrap();
+    simulator.load("main.asm", input).unwrap();
+    simulator.run().unwrap();
+    assert_eq!(simulator.register(RegisterKind::s0), 3);
+    assert_eq!(simulator.register(RegisterKind::s1), 5);
+    assert_eq!(simulator.register(RegisterKind::s2), 4);
+    assert_eq!(simulator.register(RegisterKind::s3), 4);
+    assert_eq!(simulator.register(RegisterKind::s4), -1);
+    drop(simulator);
+    assert_eq!(std::fs::read_to_string(root.join("output.txt")).unwrap(), "helloh");
+
+    // No access without the root
+    let mut simulator = Simulator::new();
+    simulator.load("main.asm", input).unwrap();
+    simulator.run().unwrap();
+    assert_eq!(simulator.register(RegisterKind::s0), -1);
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+#[cfg(test)]
+fn test_service() {
+    use crate::Simulator;
+    use crate::token::register::RegisterKind;
+
+    let input = "\
+    li      $v0, 30
+    syscall
+    move    $s0, $a0
+    li      $v0, 32
+    li      $a0, 250
+    syscall
+    li      $v0, 30
+    syscall
+    move    $s1, $a0
+    li      $v0, 40
+    li      $a0, 1
+    li      $a1, 42
+    syscall
+    li      $v0, 41
+    li      $a0, 1
+    syscall
+    move    $s2, $a0
+    li      $v0, 42
+    li      $a0, 1
+    li      $a1, 10
+    syscall
+    move    $s3, $a0
+    li      $v0, 41
+    li      $a0, 2              # unseeded
+    syscall
+    move    $s4, $a0
+    li      $v0, 44
+    li      $a0, 1
+    syscall
+";
+
+    let run = |seed: u64| {
+        let mut simulator = Simulator::new();
+        simulator.set_virtual_clock(1000);
+        simulator.set_seed(seed);
+        simulator.load("main.asm", input).unwrap();
+        simulator.run().unwrap();
+        assert_eq!(simulator.register(RegisterKind::s0), 1000);
+        assert_eq!(simulator.register(RegisterKind::s1), 1250);
+        assert!((0..10).contains(&simulator.register(RegisterKind::s3)));
+        assert!((0.0..1.0).contains(&simulator.registers().double(32)));
+        [RegisterKind::s2, RegisterKind::s3, RegisterKind::s4].map(|r| simulator.register(r))
+    };
+
+    let [s2, s3, s4] = run(1);
+    assert_eq!(run(1), [s2, s3, s4]);
+    // Seeded by the program
+    let [t2, t3, t4] = run(2);
+    assert_eq!([t2, t3], [s2, s3]);
+    assert_ne!(t4, s4);
+}
+
+#[test]
+#[cfg(test)]
+fn test_console() {
+    use crate::Simulator;
+    use crate::token::console::BufferConsole;
+
+    let input = "\
+.data
+msg:    .asciiz \"n=\"
+buf:    .space 8
+.text
+    li      $v0, 5
+    syscall
+    move    $s0, $v0
+    li      $v0, 4
+    la      $a0, msg
+    syscall
+    li      $v0, 1
+    move    $a0, $s0
+    syscall
+    prtn
+    li      $v0, 34
+    li      $a0, -1
+    syscall
+    li      $v0, 11
+    li      $a0, 32
+    syscall
+    li      $v0, 35
+    li      $a0, 5
+    syscall
+    li      $v0, 11
+    li      $a0, 32
+    syscall
+    li      $v0, 36
+    li      $a0, -1
+    syscall
+    prtn
+    li      $v0, 14             # read stdin
+    li      $a0, 0
+    la      $a1, buf
+    li      $a2, 3
+    syscall
+    li      $v0, 15             # write stdout
+    li      $a0, 1
+    la      $a1, buf
+    li      $a2, 3
+    syscall
+    prti    $s0
+";
+
+    let console = BufferConsole::new("12\nabc");
+    let mut simulator = Simulator::new();
+    simulator.set_console(Box::new(console.clone()));
+    simulator.load("main.asm", input).unwrap();
+    simulator.run().unwrap();
+    assert_eq!(console.output(), "n=12\n0xffffffff 00000000000000000000000000000101 4294967295\nabc12");
+}
@@ -1,14 +1,15 @@
 use std::io::{stdin, stdout, Write};
 
-use super::token::Tokens;
-use super::token::memory::Memory;
-use super::lexer::tokenize;
-use super::parser::{parse, display::*};
-
-pub fn run() {
-    let mut tokens: Tokens = Tokens::new();
+use mipsi::{Simulator, Execution, Endian};
+use mipsi::parser::display::*;
+
+pub fn run(machine: bool, endian: Endian) {
+    let mut simulator = Simulator::new();
+    simulator.set_endian(endian);
+    if machine {
+        simulator.set_execution(Execution::Machine);
+    }
     let mut number_of_lines: u32 = 0;
-    let mut memory = Memory::default();
 
     println!("Welcome mipsi REPL!");
     println!("Type `exit` or ^C to exit");
@@ -26,43 +27,31 @@ pub fn run() {
 
         match &*input {
             "exit"  => break,
-            "help"  => { display_help();                              continue; },
-            "dispt" => { println!("{:?}", tokens.token);              continue; }, // TODO provisional
-            "dispd" => { display_data_per_4byte(&memory.static_data); continue; },
-            "disps" => { display_stack(&memory.stack);                continue; },
-            "dispr" => { display_register(&memory.registers);         continue; },
-            "dispf" => { display_fp_register(&memory.registers);      continue; },
+            "help"  => { display_help();                                          continue; },
+            "dispt" => { println!("{:?}", simulator.tokens().token);              continue; }, // TODO provisional
+            "dispd" => { display_data_per_4byte(simulator.memory());              continue; },
+            "disps" => { display_stack(simulator.memory());                       continue; },
+            "dispr" => { display_register(simulator.registers());                 continue; },
+            "dispf" => { display_fp_register(simulator.registers());              continue; },
             "" => continue,
             _ => (),
         }
 
         number_of_lines += 1;
-        let old_tokens_len = tokens.len();
+        let checkpoint = simulator.checkpoint();
 
-        if let Err(e) = tokenize(number_of_lines, 0, &input, &mut tokens) {
+        if let Err(e) = simulator.load_line(number_of_lines, &input) {
             eprintln!("tokenize failed: {}\n", e);
-            rollback(&mut tokens, old_tokens_len);
+            simulator.rollback(checkpoint);
             continue;
         }
 
-        if 0 < tokens.len() {
-            let result = parse(&mut tokens, &mut memory);
-            if let Err(e) = result {
-                eprintln!("{}\n", e);
-                rollback(&mut tokens, old_tokens_len);
-                continue;
-            }
+        if let Err(e) = simulator.run() {
+            eprintln!("{}\n", e);
+            simulator.rollback(checkpoint);
+            continue;
         }
 
         println!();
     }
 }
-
-fn rollback(tokens: &mut Tokens, old_tokens_len: usize) {
-    let rollback_len = tokens.len() - old_tokens_len;
-    for _ in 0..rollback_len {
-        tokens.pop();
-    }
-    tokens.back_idx();
-}
-
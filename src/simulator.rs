@@ -0,0 +1,285 @@
+use std::error::Error;
+use std::path::Path;
+
+use super::assembler::{self, Program};
+use super::assembler::symbol::{Segment, Symbol};
+use super::elf;
+use super::lexer::tokenize;
+use super::parser::{self, Status};
+use super::token::{Tokens, TokenKind, IndicateKind};
+use super::token::memory::{Memory, Endian, STACK_SEGMENT, USER_END};
+use super::token::register::{Registers, RegisterKind};
+use super::token::cp0::{COUNT, COMPARE};
+use super::token::clock::Clock;
+use super::token::console::Console;
+
+/// Default $gp of executables without `_gp`
+const GP: u32 = 0x10008000;
+
+/// How to execute the program
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Execution {
+    Source,   // Assembled instructions (pseudo instructions as is)
+    Machine,  // Fetch, decode and execute the machine code in memory
+}
+
+/// Wraps `Tokens` and `Memory`.
+/// Load source text, then `step()` or `run()` it.
+pub struct Simulator {
+    tokens: Tokens,
+    program: Program,
+    memory: Memory,
+    execution: Execution,
+}
+
+/// State to roll back to (Use only in REPL)
+#[derive(Clone, Copy, Debug)]
+pub struct Checkpoint {
+    tokens_len: usize,
+    program: assembler::Checkpoint,
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Simulator {
+    pub fn new() -> Self {
+        Simulator {
+            tokens: Tokens::new(),
+            program: Program::new(),
+            memory: Memory::default(),
+            execution: Execution::Source,
+        }
+    }
+
+    /// Both give the same results except $at, hi and lo
+    /// which are used by the expansion of pseudo instructions.
+    pub fn set_execution(&mut self, execution: Execution) {
+        self.execution = execution;
+    }
+
+    /// Byte order of data, machine code and loads/stores (Big Endian by default).
+    /// Set before loading. ELF executables use their own.
+    pub fn set_endian(&mut self, endian: Endian) {
+        self.memory.endian = endian;
+    }
+
+    /// Request the timer interrupt (hardware interrupt 5) after `n` more instructions.
+    /// Same as setting CP0 Compare to Count + `n`.
+    pub fn set_timer(&mut self, n: u32) {
+        let count = self.memory.cp0[COUNT];
+        self.memory.cp0.write(COMPARE, count.wrapping_add(n));
+    }
+
+    /// Allow the file syscalls to access files under the directory `root` (no files by default)
+    pub fn set_fs_root(&mut self, root: &Path) -> Result<(), String> {
+        self.memory.files.set_root(root).map_err(|e| format!("{}: {}", root.display(), e))
+    }
+
+    /// Console of the program (stdin and stdout of the process by default).  Kept by `reset()`.
+    pub fn set_console(&mut self, console: Box<dyn Console>) {
+        self.memory.console = console;
+    }
+
+    /// Time and sleep syscalls use a virtual clock from `start` (milliseconds since the epoch).
+    /// Only sleep advances it.
+    pub fn set_virtual_clock(&mut self, start: u64) {
+        self.memory.clock = Clock::Virtual(start);
+    }
+
+    /// Random generators which are not seeded by the program (syscall 40) are reproducible from `seed`
+    pub fn set_seed(&mut self, seed: u64) {
+        self.memory.random.set_seed(seed);
+    }
+
+    /// Tokenize `source` as a file named `filename`.
+    /// Labels of all loaded files are global.  Each file starts in .text segment.
+    pub fn load(&mut self, filename: &str, source: &str) -> Result<(), String> {
+        let filename_idx = self.tokens.add_file(filename);
+        self.tokens.push(TokenKind::INDICATE(IndicateKind::text), 0, filename_idx);
+        self.tokens.push(TokenKind::EOL, 0, filename_idx);
+        for (i, line) in source.lines().enumerate() {
+            let number_of_lines = i as u32 + 1;
+            if let Err(e) = tokenize(number_of_lines, filename_idx, line, &mut self.tokens) {
+                return Err(format!("{}:{}: {}", filename, number_of_lines, e));
+            }
+        }
+        Ok(())
+    }
+
+    /// Load source text, or an ELF executable
+    pub fn load_file(&mut self, path: &Path) -> Result<(), String> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        if elf::Elf::is_elf(&bytes) {
+            return self.load_elf(&path.display().to_string(), &bytes);
+        }
+        let source = String::from_utf8(bytes)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        self.load(&path.display().to_string(), &source)
+    }
+
+    /// Load a statically linked ELF32 MIPS executable named `filename`.
+    /// Maps PT_LOAD segments, starts at e_entry and executes the machine code.
+    /// Function symbols become .text labels (for traces and error messages).
+    pub fn load_elf(&mut self, filename: &str, bytes: &[u8]) -> Result<(), String> {
+        let elf = elf::parse(bytes).map_err(|e| format!("{}: {}", filename, e))?;
+        let filename_idx = self.tokens.add_file(filename);
+
+        self.memory.endian = elf.endian;
+        for segment in &elf.segments {
+            self.memory.map(segment.address, &segment.bytes)
+                .map_err(|e| format!("{}: {}", filename, e))?;
+        }
+
+        for symbol in elf.symbols.iter().filter(|s| s.function) {
+            let label = Symbol { segment: Segment::Text, address: symbol.address, filename_idx, line: 0 };
+            // Local symbols may have the same name: the first one wins
+            let _ = self.program.symbols_mut().insert(&symbol.name, label);
+        }
+
+        // $sp: argc, argv and envp (all 0) are on the stack
+        let sp = STACK_SEGMENT & !0xf;
+        for offset in [0, 4, 8] {
+            self.memory.store_u32(sp + offset, 0).map_err(|e| e.to_string())?;
+        }
+        self.memory.registers[RegisterKind::sp] = sp as i32;
+        self.memory.registers[RegisterKind::gp] = elf.symbol("_gp").map_or(GP, |s| s.address) as i32;
+        self.memory.pc = elf.entry;
+        self.execution = Execution::Machine;
+        Ok(())
+    }
+
+    /// Tokenize 1 line (Use only in REPL)
+    pub fn load_line(&mut self, number_of_lines: u32, line: &str) -> Result<(), String> {
+        tokenize(number_of_lines, 0, line, &mut self.tokens)
+    }
+
+    /// Assemble loaded tokens which are not assembled yet
+    pub fn assemble(&mut self) -> Result<(), String> {
+        if self.program.cursor() < self.tokens.len() {
+            let len = self.program.len();
+            assembler::assemble(&mut self.tokens, &mut self.program, &mut self.memory)?;
+
+            // Every instruction must be encoded
+            if self.execution == Execution::Machine {
+                for instruction in &self.program.instructions[len..] {
+                    assembler::encode::encode(instruction).map_err(|e| {
+                        format!("{}:{}: {}", self.tokens.filename_of(instruction.filename_idx), instruction.line, e)
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Machine code words of the .text segment
+    pub fn encode(&mut self) -> Result<Vec<u32>, String> {
+        self.assemble()?;
+        let mut words = Vec::new();
+        for instruction in self.program.instructions.iter().filter(|i| i.address < USER_END) {
+            let code = assembler::encode::encode(instruction).map_err(|e| {
+                format!("{}:{}: {}", self.tokens.filename_of(instruction.filename_idx), instruction.line, e)
+            })?;
+            words.extend(code);
+        }
+        Ok(words)
+    }
+
+    /// Execute one instruction
+    pub fn step(&mut self) -> Result<Status, Box<dyn Error>> {
+        self.assemble()?;
+        match self.execution {
+            Execution::Source  => parser::step(&mut self.tokens, &mut self.program, &mut self.memory),
+            Execution::Machine => parser::step_machine(&mut self.tokens, &mut self.program, &mut self.memory),
+        }
+    }
+
+    /// Execute until exit or the end of instructions
+    pub fn run(&mut self) -> Result<Status, Box<dyn Error>> {
+        loop {
+            match self.step()? {
+                Status::Running => (),
+                status => return Ok(status),
+            }
+        }
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            tokens_len: self.tokens.len(),
+            program: self.program.checkpoint(),
+        }
+    }
+
+    /// Discard tokens loaded after `checkpoint`
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        while checkpoint.tokens_len < self.tokens.len() {
+            self.tokens.pop();
+        }
+        self.tokens.back_idx();
+        self.program.rollback(checkpoint.program);
+        self.memory.pc = self.memory.pc.min(self.program.end_address());
+        self.memory.truncate_text(self.program.end_address());
+    }
+
+    pub fn reset(&mut self) {
+        self.memory.clear();
+        self.tokens.init();
+        self.program = Program::new();
+    }
+
+    pub fn tokens(&self) -> &Tokens {
+        &self.tokens
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.memory.registers
+    }
+
+    pub fn registers_mut(&mut self) -> &mut Registers {
+        &mut self.memory.registers
+    }
+
+    pub fn register(&self, register: RegisterKind) -> i32 {
+        self.memory.registers[register]
+    }
+
+    /// Address of the next instruction
+    pub fn pc(&self) -> u32 {
+        self.memory.pc
+    }
+
+    pub fn hi(&self) -> u32 {
+        self.memory.hi
+    }
+
+    pub fn lo(&self) -> u32 {
+        self.memory.lo
+    }
+
+    /// Read `byte` bytes from `address`
+    pub fn read_int(&self, address: u32, byte: usize) -> Result<i32, String> {
+        self.memory.load(address, byte).map(|int| int as i32).map_err(|e| e.to_string())
+    }
+
+    /// Read a null-terminated string from `address`
+    pub fn read_string(&self, address: u32) -> Result<String, String> {
+        self.memory.load_string(address).map_err(|e| e.to_string())
+    }
+}
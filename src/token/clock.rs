@@ -0,0 +1,26 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Clock of the time (30) and sleep (32) syscalls
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Clock {
+    #[default]
+    Real,          // System time, sleep blocks
+    Virtual(u64),  // Milliseconds since the epoch, advanced only by sleep
+}
+
+impl Clock {
+    /// Milliseconds since the epoch
+    pub fn now(&self) -> u64 {
+        match self {
+            Clock::Real => SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64),
+            Clock::Virtual(now) => *now,
+        }
+    }
+
+    pub fn sleep(&mut self, ms: u64) {
+        match self {
+            Clock::Real => std::thread::sleep(Duration::from_millis(ms)),
+            Clock::Virtual(now) => *now = now.saturating_add(ms),
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Input and output of the program: console syscalls, `PRT*` instructions and file descriptors 0-2
+pub trait Console: Send {
+    /// Read a line including the newline ("" at the end of input)
+    fn read_line(&mut self) -> io::Result<String>;
+
+    /// Read up to `buf.len()` bytes (0 at the end of input)
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Write to the standard output
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()>;
+
+    /// Write to the standard error
+    fn write_error(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.write(bytes)
+    }
+
+    fn print(&mut self, s: &str) -> io::Result<()> {
+        self.write(s.as_bytes())
+    }
+}
+
+impl fmt::Debug for dyn Console {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Console")
+    }
+}
+
+/// stdin, stdout and stderr of the process
+#[derive(Debug, Default)]
+pub struct StdConsole;
+
+impl Console for StdConsole {
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        io::stdin().read_line(&mut line)?;
+        Ok(line)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        io::stdin().read(buf)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(bytes)?;
+        stdout.flush()
+    }
+
+    fn write_error(&mut self, bytes: &[u8]) -> io::Result<()> {
+        io::stderr().write_all(bytes)
+    }
+}
+
+/// Input from a buffer, output (and error) to a buffer shared by the clones
+///
+/// ```
+/// use mipsi::Simulator;
+/// use mipsi::token::console::BufferConsole;
+///
+/// let console = BufferConsole::new("41\n");
+/// let mut simulator = Simulator::new();
+/// simulator.set_console(Box::new(console.clone()));
+/// simulator.load("main.asm", "li $v0, 5\nsyscall\naddi $a0, $v0, 1\nli $v0, 1\nsyscall\n").unwrap();
+/// simulator.run().unwrap();
+/// assert_eq!(console.output(), "42");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct BufferConsole {
+    input: Cursor<Vec<u8>>,
+    output: Arc<Mutex<Vec<u8>>>,
+}
+
+impl BufferConsole {
+    pub fn new(input: &str) -> Self {
+        BufferConsole {
+            input: Cursor::new(input.as_bytes().to_vec()),
+            output: Arc::default(),
+        }
+    }
+
+    /// Output so far (invalid UTF-8 is replaced)
+    pub fn output(&self) -> String {
+        String::from_utf8_lossy(&self.output.lock().unwrap()).into_owned()
+    }
+
+    pub fn output_bytes(&self) -> Vec<u8> {
+        self.output.lock().unwrap().clone()
+    }
+}
+
+impl Console for BufferConsole {
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        self.input.read_line(&mut line)?;
+        Ok(line)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.input.read(buf)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.output.lock().unwrap().extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Input from a file, output (and error) to a file
+#[derive(Debug)]
+pub struct FileConsole {
+    input: BufReader<File>,
+    output: File,
+}
+
+impl FileConsole {
+    /// Open `input` and create (or truncate) `output`
+    pub fn new(input: &Path, output: &Path) -> io::Result<Self> {
+        Ok(FileConsole {
+            input: BufReader::new(File::open(input)?),
+            output: File::create(output)?,
+        })
+    }
+}
+
+impl Console for FileConsole {
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        self.input.read_line(&mut line)?;
+        Ok(line)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.input.read(buf)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.output.write_all(bytes)
+    }
+}
@@ -0,0 +1,150 @@
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+use super::memory::Fault;
+use super::fpu;
+
+// Register numbers
+pub const BADVADDR: usize =  8;  // Address of the last address error
+pub const COUNT:    usize =  9;  // Incremented every instruction
+pub const COMPARE:  usize = 11;  // Timer interrupt when Count reaches it
+pub const STATUS:   usize = 12;  // Interrupt mask and enable, exception level
+pub const CAUSE:    usize = 13;  // Exception code
+pub const EPC:      usize = 14;  // Address of the instruction which caused the exception
+
+// Status bits
+pub const STATUS_IE:  u32 = 1 << 0;  // Interrupt enable
+pub const STATUS_EXL: u32 = 1 << 1;  // Exception level
+pub const STATUS_UM:  u32 = 1 << 4;  // User mode
+pub const STATUS_IM:  u32 = 0xff << 8;  // Interrupt mask
+
+// Cause bits
+pub const CAUSE_EXC_CODE: u32 = 0x1f << 2;
+pub const CAUSE_IP:       u32 = 0xff << 8;  // Pending interrupts (same bits as Status.IM)
+
+/// Hardware interrupt of the timer (IP7)
+pub const TIMER_INTERRUPT: u32 = 5;
+
+/// Entry point of the exception handler
+pub const EXCEPTION_HANDLER: u32 = 0x80000180;
+
+/// Coprocessor 0 registers (System Control Coprocessor)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Cp0 {
+    regs: [u32; 32],
+}
+
+impl Default for Cp0 {
+    fn default() -> Self {
+        let mut regs = [0; 32];
+        regs[STATUS] = 0x0000ff00 | STATUS_UM | STATUS_IE;  // Same as MARS
+        Self {
+            regs,
+        }
+    }
+}
+
+impl Cp0 {
+    /// Count an executed instruction.  The timer interrupt is requested when Count reaches Compare.
+    pub fn tick(&mut self) {
+        self.regs[COUNT] = self.regs[COUNT].wrapping_add(1);
+        if self.regs[COUNT] == self.regs[COMPARE] {
+            self.regs[CAUSE] |= hardware_interrupt(TIMER_INTERRUPT);
+        }
+    }
+
+    /// Write the register by `mtc0`.  Writing Compare acknowledges the timer interrupt.
+    pub fn write(&mut self, idx: usize, value: u32) {
+        self.regs[idx] = value;
+        if idx == COMPARE {
+            self.regs[CAUSE] &= !hardware_interrupt(TIMER_INTERRUPT);
+        }
+    }
+
+    /// Interrupt is pending, unmasked and enabled (and not in the handler)
+    pub fn interrupted(&self) -> bool {
+        let status = self.regs[STATUS];
+        status & STATUS_IE != 0 && status & STATUS_EXL == 0 &&
+            self.regs[CAUSE] & status & CAUSE_IP != 0
+    }
+}
+
+/// Cause.IP (and Status.IM) bit of the hardware interrupt `n` (0-5)
+pub fn hardware_interrupt(n: u32) -> u32 {
+    1 << (10 + n)
+}
+
+impl Index<usize> for Cp0 {
+    type Output = u32;
+    fn index(&self, idx: usize) -> &Self::Output {
+        &self.regs[idx]
+    }
+}
+
+impl IndexMut<usize> for Cp0 {
+    fn index_mut(&mut self, idx: usize) -> &mut Self::Output {
+        &mut self.regs[idx]
+    }
+}
+
+/// Architectural exceptions
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Exception {
+    Interrupt,                 //  0: Int
+    AddressLoad(Fault),        //  4: AdEL (load or instruction fetch)
+    AddressStore(Fault),       //  5: AdES
+    Syscall(i32),              //  8: Sys (Unknown service number)
+    Breakpoint(u32),           //  9: Bp (code)
+    ReservedInstruction(u32),  // 10: RI (instruction word)
+    CoprocessorUnusable,       // 11: CpU (coprocessor 0 in user mode)
+    Overflow,                  // 12: Ov
+    Trap,                      // 13: Tr
+    FloatingPoint(u32),        // 15: FPE (enabled IEEE exceptions)
+}
+
+impl Exception {
+    /// ExcCode of the Cause register
+    pub fn code(&self) -> u32 {
+        match self {
+            Exception::Interrupt              =>  0,
+            Exception::AddressLoad(_)         =>  4,
+            Exception::AddressStore(_)        =>  5,
+            Exception::Syscall(_)             =>  8,
+            Exception::Breakpoint(_)          =>  9,
+            Exception::ReservedInstruction(_) => 10,
+            Exception::CoprocessorUnusable    => 11,
+            Exception::Overflow               => 12,
+            Exception::Trap                   => 13,
+            Exception::FloatingPoint(_)       => 15,
+        }
+    }
+
+    /// Address for BadVAddr
+    pub fn bad_address(&self) -> Option<u32> {
+        match self {
+            Exception::AddressLoad(fault) |
+            Exception::AddressStore(fault) => Some(fault.address()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Exception::Interrupt                   => write!(f, "interrupt"),
+            Exception::AddressLoad(fault) |
+            Exception::AddressStore(fault)         => write!(f, "{}", fault),
+            Exception::Syscall(code)               => write!(f, "SYSCALL: invalid code: {}", code),
+            Exception::Breakpoint(code)            => write!(f, "break: {}", code),
+            Exception::ReservedInstruction(word)   => write!(f, "invalid instruction: 0x{:08x}", word),
+            Exception::CoprocessorUnusable         => write!(f, "coprocessor 0 is unusable in user mode"),
+            Exception::Overflow                    => write!(f, "panicked at 'arithmetic operation overflowed'"),
+            Exception::Trap                        => write!(f, "trap: condition is true"),
+            Exception::FloatingPoint(exceptions)   =>
+                write!(f, "floating point exception: {}", fpu::exception_names(*exceptions)),
+        }
+    }
+}
+
+impl std::error::Error for Exception {}
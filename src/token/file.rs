@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+// File descriptors of the console
+pub const STDIN:  i32 = 0;
+pub const STDOUT: i32 = 1;
+pub const STDERR: i32 = 2;
+
+// Flags of open (MARS)
+pub const O_RDONLY: i32 = 0;
+pub const O_WRONLY: i32 = 1;  // Create or truncate
+pub const O_APPEND: i32 = 9;  // Create or append (write-only)
+
+/// File descriptor table of the file syscalls (13-16).  0-2 are the console, not in the table.
+/// Files can be opened only under the root directory (none without it).
+#[derive(Debug, Default)]
+pub struct Files {
+    root: Option<PathBuf>,      // Canonical path of the sandbox
+    table: BTreeMap<i32, File>,
+}
+
+impl Files {
+    /// Allow access to files under `root`
+    pub fn set_root(&mut self, root: &Path) -> std::io::Result<()> {
+        self.root = Some(root.canonicalize()?);
+        Ok(())
+    }
+
+    /// Close all files (the root is kept)
+    pub fn clear(&mut self) {
+        self.table.clear();
+    }
+
+    /// open: file descriptor (the lowest unused from 3)
+    pub fn open(&mut self, name: &str, flags: i32) -> Option<i32> {
+        let path = self.resolve(name)?;
+        let file = match flags {
+            O_RDONLY => File::open(path),
+            O_WRONLY => File::create(path),
+            O_APPEND => OpenOptions::new().append(true).create(true).open(path),
+            _ => return None,
+        }.ok()?;
+        if !file.metadata().ok()?.is_file() {
+            return None;
+        }
+
+        let fd = (STDERR + 1..).find(|fd| !self.table.contains_key(fd))?;
+        self.table.insert(fd, file);
+        Some(fd)
+    }
+
+    /// read: number of bytes read (0 at the end of file)
+    pub fn read(&mut self, fd: i32, buf: &mut [u8]) -> Option<usize> {
+        self.table.get_mut(&fd)?.read(buf).ok()
+    }
+
+    /// write: number of bytes written
+    pub fn write(&mut self, fd: i32, bytes: &[u8]) -> Option<usize> {
+        self.table.get_mut(&fd)?.write_all(bytes).ok().map(|_| bytes.len())
+    }
+
+    /// close: false if `fd` is not open
+    pub fn close(&mut self, fd: i32) -> bool {
+        self.table.remove(&fd).is_some()
+    }
+
+    /// Path of `name` under the root.  Absolute paths, `..` and symbolic links out of the root are denied.
+    fn resolve(&self, name: &str) -> Option<PathBuf> {
+        let root = self.root.as_ref()?;
+        let relative = Path::new(name);
+        if name.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+            return None;
+        }
+
+        let path = root.join(relative);
+        let real = match path.canonicalize() {
+            Ok(real) => real,
+            // New file in an existing directory (not a dangling link)
+            Err(_) if path.symlink_metadata().is_err() =>
+                path.parent()?.canonicalize().ok()?.join(path.file_name()?),
+            Err(_) => return None,
+        };
+        if real.starts_with(root) {
+            Some(real)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use std::cmp::Ordering;
+use std::ops::{Add, Sub, Mul, Div, Neg};
+
+use super::register::{Registers, RegisterKind};
+use super::cp0::Exception;
+
+// Control registers of `cfc1` and `ctc1`
+pub const FIR:  usize =  0;  // Implementation (read only)
+pub const FCSR: usize = 31;  // Control and status
+
+/// FIR: single, double and word formats are implemented
+pub const FIR_VALUE: u32 = 1 << 16 | 1 << 17 | 1 << 20;
+
+// FCSR fields
+pub const FCSR_RM:     u32 = 0x3;       // Rounding mode
+pub const FCSR_FLAGS:  u32 = 0x1f << 2;   // Accumulated exceptions
+pub const FCSR_ENABLE: u32 = 0x1f << 7;   // Exceptions which trap
+pub const FCSR_CAUSE:  u32 = 0x3f << 12;  // Exceptions of the last operation
+pub const FCSR_WRITABLE: u32 = 0xff83_ffff;
+
+// IEEE-754 exceptions (bit 0 of each field)
+pub const INEXACT:          u32 = 1 << 0;
+pub const UNDERFLOW:        u32 = 1 << 1;
+pub const OVERFLOW:         u32 = 1 << 2;
+pub const DIVIDE_BY_ZERO:   u32 = 1 << 3;
+pub const INVALID:          u32 = 1 << 4;
+pub const UNIMPLEMENTED:    u32 = 1 << 5;  // Cause only, always enabled
+
+/// Rounding mode (FCSR.RM)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rounding {
+    Nearest,  // 0: to nearest, ties to even
+    Zero,     // 1: toward zero
+    Up,       // 2: toward +infinity
+    Down,     // 3: toward -infinity
+}
+
+impl Rounding {
+    pub fn of(registers: &Registers) -> Self {
+        match registers[RegisterKind::fcsr] as u32 & FCSR_RM {
+            0 => Rounding::Nearest,
+            1 => Rounding::Zero,
+            2 => Rounding::Up,
+            _ => Rounding::Down,
+        }
+    }
+}
+
+/// Set FCSR.Cause to the exceptions of an FP operation.
+/// Raise the floating point exception if any is enabled, otherwise accumulate them in FCSR.Flags.
+pub fn signal(registers: &mut Registers, cause: u32) -> Result<(), Exception> {
+    let fcsr = registers[RegisterKind::fcsr] as u32 & !FCSR_CAUSE | cause << 12;
+    registers[RegisterKind::fcsr] = fcsr as i32;
+    check(registers)?;
+    registers[RegisterKind::fcsr] = (fcsr | cause << 2 & FCSR_FLAGS) as i32;
+    Ok(())
+}
+
+/// Floating point exception if FCSR.Cause is enabled (or unimplemented)
+pub fn check(registers: &Registers) -> Result<(), Exception> {
+    let fcsr = registers[RegisterKind::fcsr] as u32;
+    let cause = (fcsr & FCSR_CAUSE) >> 12;
+    let enabled = (fcsr & FCSR_ENABLE) >> 7 | UNIMPLEMENTED;
+    if cause & enabled != 0 {
+        Err(Exception::FloatingPoint(cause & enabled))
+    } else {
+        Ok(())
+    }
+}
+
+/// Names of the exceptions, e.g. "overflow, inexact"
+pub fn exception_names(exceptions: u32) -> String {
+    let names = [
+        (INVALID, "invalid operation"),
+        (DIVIDE_BY_ZERO, "division by zero"),
+        (OVERFLOW, "overflow"),
+        (UNDERFLOW, "underflow"),
+        (INEXACT, "inexact"),
+        (UNIMPLEMENTED, "unimplemented operation"),
+    ];
+    names.iter()
+        .filter(|(bit, _)| exceptions & bit != 0)
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// f32 and f64
+pub trait Float: Copy + PartialOrd
+    + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self> + Neg<Output = Self>
+{
+    const ZERO: Self;
+    const MIN_POSITIVE: Self;
+    fn is_nan(self) -> bool;
+    fn is_infinite(self) -> bool;
+    fn abs(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn mul_add(self, a: Self, b: Self) -> Self;
+    fn next_up(self) -> Self;
+    fn next_down(self) -> Self;
+}
+
+macro_rules! impl_float {
+    ($t:ident, $bits:ident) => {
+        impl Float for $t {
+            const ZERO: Self = 0.0;
+            const MIN_POSITIVE: Self = $t::MIN_POSITIVE;
+            fn is_nan(self) -> bool { $t::is_nan(self) }
+            fn is_infinite(self) -> bool { $t::is_infinite(self) }
+            fn abs(self) -> Self { $t::abs(self) }
+            fn sqrt(self) -> Self { $t::sqrt(self) }
+            fn mul_add(self, a: Self, b: Self) -> Self { $t::mul_add(self, a, b) }
+            // Least value greater than `self` (by the bits, for older toolchains)
+            fn next_up(self) -> Self {
+                if self.is_nan() || self == $t::INFINITY {
+                    return self;
+                }
+                let bits = self.to_bits();
+                let abs = bits & !(1 << ($bits::BITS - 1));
+                $t::from_bits(if abs == 0 { 1 } else if bits == abs { bits + 1 } else { bits - 1 })
+            }
+            fn next_down(self) -> Self { -Float::next_up(-self) }
+        }
+    };
+}
+
+impl_float!(f32, u32);
+impl_float!(f64, u64);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operation {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Sqrt,  // of `x`
+}
+
+/// `x op y` rounded in `rounding` mode, and the IEEE exceptions
+pub fn arithmetic<T: Float>(operation: Operation, x: T, y: T, rounding: Rounding) -> (T, u32) {
+    let y = match operation {
+        Operation::Sqrt => T::ZERO,
+        Operation::Sub => -y,
+        _ => y,
+    };
+
+    // Result rounded to nearest, and the error of it (exact result - nearest)
+    let (nearest, error) = match operation {
+        Operation::Add | Operation::Sub => {
+            let sum = x + y;
+            let z = sum - x;
+            (sum, (x - (sum - z)) + (y - z))
+        },
+        Operation::Mul => {
+            let product = x * y;
+            (product, x.mul_add(y, -product))
+        },
+        Operation::Div => {
+            let quotient = x / y;
+            let remainder = (-quotient).mul_add(y, x);
+            (quotient, if y < T::ZERO { -remainder } else { remainder })
+        },
+        Operation::Sqrt => {
+            let root = x.sqrt();
+            (root, (-root).mul_add(root, x))
+        },
+    };
+
+    if x.is_nan() || y.is_nan() {
+        (nearest, 0)
+    } else if nearest.is_nan() {
+        (nearest, INVALID)
+    } else if operation == Operation::Div && y == T::ZERO {
+        (nearest, if x.is_infinite() { 0 } else { DIVIDE_BY_ZERO })
+    } else if x.is_infinite() || y.is_infinite() {
+        (nearest, 0)
+    } else if nearest == T::ZERO && error == T::ZERO && x != T::ZERO
+        && matches!(operation, Operation::Add | Operation::Sub)
+    {
+        // x - x is -0 when rounding toward -infinity
+        (if rounding == Rounding::Down { -nearest.abs() } else { nearest.abs() }, 0)
+    } else {
+        let error = if nearest.is_infinite() {
+            if nearest > T::ZERO { Ordering::Less } else { Ordering::Greater }
+        } else {
+            error.partial_cmp(&T::ZERO).unwrap_or(Ordering::Equal)
+        };
+        let result = round(nearest, error, rounding);
+        (result, rounding_exceptions(nearest, result, error))
+    }
+}
+
+/// cvt.s.d
+pub fn to_single(value: f64, rounding: Rounding) -> (f32, u32) {
+    let nearest = value as f32;
+    if value.is_nan() || value.is_infinite() {
+        return (nearest, 0);
+    }
+    let error = if nearest.is_infinite() {
+        if nearest > 0.0 { Ordering::Less } else { Ordering::Greater }
+    } else {
+        value.partial_cmp(&(nearest as f64)).unwrap()
+    };
+    let result = round(nearest, error, rounding);
+    (result, rounding_exceptions(nearest, result, error))
+}
+
+/// cvt.s.w
+pub fn word_to_single(value: i32, rounding: Rounding) -> (f32, u32) {
+    let nearest = value as f32;
+    let error = (value as f64).partial_cmp(&(nearest as f64)).unwrap();
+    let result = round(nearest, error, rounding);
+    (result, rounding_exceptions(nearest, result, error))
+}
+
+/// cvt.w.fmt, round.w.fmt, ...  NaN and out of range are 2^31-1 (invalid operation)
+pub fn to_word(value: f64, rounding: Rounding) -> (i32, u32) {
+    let rounded = match rounding {
+        // Ties to even (f64::round_ties_even needs newer toolchains)
+        Rounding::Nearest if (value - value.trunc()).abs() == 0.5 => 2.0 * (value / 2.0).round(),
+        Rounding::Nearest => value.round(),
+        Rounding::Zero    => value.trunc(),
+        Rounding::Up      => value.ceil(),
+        Rounding::Down    => value.floor(),
+    };
+    if (i32::MIN as f64..=i32::MAX as f64).contains(&rounded) {
+        #[allow(clippy::float_cmp)]
+        let cause = if rounded == value { 0 } else { INEXACT };
+        (rounded as i32, cause)
+    } else {
+        (i32::MAX, INVALID)
+    }
+}
+
+/// `nearest` rounded in `rounding` mode, where `error` is the exact result compared to `nearest`
+fn round<T: Float>(nearest: T, error: Ordering, rounding: Rounding) -> T {
+    match (rounding, error) {
+        (Rounding::Zero, Ordering::Less) if nearest > T::ZERO => nearest.next_down(),
+        (Rounding::Zero, Ordering::Greater) if nearest < T::ZERO => nearest.next_up(),
+        (Rounding::Up, Ordering::Greater) => nearest.next_up(),
+        (Rounding::Down, Ordering::Less) => nearest.next_down(),
+        _ => nearest,
+    }
+}
+
+/// Overflow, underflow (tiny after rounding) and inexact
+fn rounding_exceptions<T: Float>(nearest: T, result: T, error: Ordering) -> u32 {
+    if nearest.is_infinite() || result.is_infinite() {
+        OVERFLOW | INEXACT
+    } else if error == Ordering::Equal {
+        0
+    } else if result.abs() < T::MIN_POSITIVE {
+        UNDERFLOW | INEXACT
+    } else {
+        INEXACT
+    }
+}
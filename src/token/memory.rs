@@ -1,42 +1,382 @@
+use std::collections::HashMap;
+use std::fmt;
+
 use super::register::Registers;
+use super::cp0::{Cp0, STATUS, STATUS_EXL, STATUS_UM};
+use super::file::Files;
+use super::clock::Clock;
+use super::random::Random;
+use super::console::{Console, StdConsole};
 
 // Memory layout
-//pub const TEXT_SEGMENT:  u32 = 0x04000000;
+pub const TEXT_SEGMENT:  u32 = 0x00400000;
 pub const STATIC_DATA:   u32 = 0x10000000;
-pub const DYNAMIC_DATA:  u32 = 0x20000000;  // tmp
-pub const DYNAMIC_DATA_EXIT: u32 = 0x30000000;  // tmp
-pub const STACK_SEGMENT: u32 = 0x7fffffff;
+pub const DYNAMIC_DATA:  u32 = 0x20000000;  // Heap (sbrk)
+pub const STACK_LIMIT:   u32 = 0x7f800000;  // Lowest address of the stack (8MB)
+pub const STACK_SEGMENT: u32 = 0x7ffffffc;  // Initial $sp
+pub const USER_END:      u32 = 0x80000000;  // End of the user space
+pub const KTEXT_SEGMENT: u32 = 0x80000000;  // Kernel text (exception handler)
+pub const KDATA_SEGMENT: u32 = 0x90000000;  // Kernel data
+
+const PAGE_BITS: u32 = 12;
+const PAGE_SIZE: usize = 1 << PAGE_BITS;
+const PAGE_MASK: u32 = PAGE_SIZE as u32 - 1;
+
+type Page = Box<[u8; PAGE_SIZE]>;
+
+/// Byte order of multi-byte values in memory
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Endian {
+    Big,
+    Little,
+}
+
+impl Endian {
+    /// Bit position of the `i`-th byte (in address order) of a `byte`-byte value
+    pub fn shift(self, byte: usize, i: usize) -> usize {
+        match self {
+            Endian::Big    => (byte-1-i) * 8,
+            Endian::Little => i * 8,
+        }
+    }
+
+    pub fn u16_bytes(self, value: u16) -> [u8; 2] {
+        match self {
+            Endian::Big    => value.to_be_bytes(),
+            Endian::Little => value.to_le_bytes(),
+        }
+    }
+
+    pub fn u32_bytes(self, value: u32) -> [u8; 4] {
+        match self {
+            Endian::Big    => value.to_be_bytes(),
+            Endian::Little => value.to_le_bytes(),
+        }
+    }
+
+    pub fn u64_bytes(self, value: u64) -> [u8; 8] {
+        match self {
+            Endian::Big    => value.to_be_bytes(),
+            Endian::Little => value.to_le_bytes(),
+        }
+    }
+
+    pub fn u16_from(self, bytes: [u8; 2]) -> u16 {
+        match self {
+            Endian::Big    => u16::from_be_bytes(bytes),
+            Endian::Little => u16::from_le_bytes(bytes),
+        }
+    }
+
+    pub fn u32_from(self, bytes: [u8; 4]) -> u32 {
+        match self {
+            Endian::Big    => u32::from_be_bytes(bytes),
+            Endian::Little => u32::from_le_bytes(bytes),
+        }
+    }
+
+    pub fn u64_from(self, bytes: [u8; 8]) -> u64 {
+        match self {
+            Endian::Big    => u64::from_be_bytes(bytes),
+            Endian::Little => u64::from_le_bytes(bytes),
+        }
+    }
+}
 
-#[derive(Clone, Debug, Default, PartialEq)]
+/// Invalid memory access
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fault {
+    Unaligned(u32),  // Address is not a multiple of the access size
+    Unmapped(u32),   // Address is out of the mapped segments
+    Kernel(u32),     // Kernel address accessed in user mode
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::Unaligned(address) => write!(f, "unaligned address: 0x{:08x}", address),
+            Fault::Unmapped(address)  => write!(f, "address out of range: 0x{:08x}", address),
+            Fault::Kernel(address)    => write!(f, "kernel address in user mode: 0x{:08x}", address),
+        }
+    }
+}
+
+impl Fault {
+    pub fn address(&self) -> u32 {
+        match *self {
+            Fault::Unaligned(address) | Fault::Unmapped(address) | Fault::Kernel(address) => address,
+        }
+    }
+}
+
+impl std::error::Error for Fault {}
+
+/// `len` bytes from `address` fit in .text, .data, .ktext or .kdata segment (see `Memory::map`)
+pub fn mappable(address: u32, len: u64) -> bool {
+    let end = address as u64 + len;
+    (TEXT_SEGMENT <= address && end <= STATIC_DATA as u64)   ||
+    (STATIC_DATA <= address && end <= DYNAMIC_DATA as u64)   ||
+    (KTEXT_SEGMENT <= address && end <= KDATA_SEGMENT as u64) ||
+    KDATA_SEGMENT <= address
+}
+
+/// Registers and the 32-bit address space.
+/// Mapped segments: .text, .data, heap (up to the break), stack (8MB), .ktext and .kdata.  Unwritten bytes are 0.
+/// Addresses above `USER_END` are accessible only in kernel mode.
+#[derive(Debug)]
 pub struct Memory {
     pub registers: Registers,
+    pub pc: u32,  // Address of the next instruction
     pub hi: u32,
     pub lo: u32,
-    pub base_address: i32,
-    pub static_data:  Vec<u8>,
-    pub dynamic_data: Vec<u8>,
-    pub stack:        Vec<u8>,
+    pub cp0: Cp0,
+    pub endian: Endian,
+    pub files: Files,  // Opened by syscalls
+    pub clock: Clock,
+    pub random: Random,
+    pub console: Box<dyn Console>,
+    pages: HashMap<u32, Page>,  // Page number => bytes (allocated on write)
+    text_end: u32,
+    ktext_end: u32,
+    data_end: u32,
+    kdata_end: u32,
+    heap_end: u32,
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Memory {
+            registers: Registers::default(),
+            pc:        TEXT_SEGMENT,
+            hi:        0,
+            lo:        0,
+            cp0:       Cp0::default(),
+            endian:    Endian::Big,
+            files:     Files::default(),
+            clock:     Clock::default(),
+            random:    Random::default(),
+            console:   Box::new(StdConsole),
+            pages:     HashMap::new(),
+            text_end:  TEXT_SEGMENT,
+            ktext_end: KTEXT_SEGMENT,
+            data_end:  STATIC_DATA,
+            kdata_end: KDATA_SEGMENT,
+            heap_end:  DYNAMIC_DATA,
+        }
+    }
 }
 
 impl Memory {
+    pub fn load_u8(&self, address: u32) -> Result<u8, Fault> {
+        Ok(self.read::<1>(address)?[0])
+    }
+
+    pub fn load_u16(&self, address: u32) -> Result<u16, Fault> {
+        Ok(self.endian.u16_from(self.read(address)?))
+    }
+
+    pub fn load_u32(&self, address: u32) -> Result<u32, Fault> {
+        Ok(self.endian.u32_from(self.read(address)?))
+    }
+
+    /// Doubleword (`ldc1`)
+    pub fn load_u64(&self, address: u32) -> Result<u64, Fault> {
+        Ok(self.endian.u64_from(self.read(address)?))
+    }
+
+    pub fn store_u8(&mut self, address: u32, value: u8) -> Result<(), Fault> {
+        self.write(address, [value])
+    }
+
+    pub fn store_u16(&mut self, address: u32, value: u16) -> Result<(), Fault> {
+        self.write(address, self.endian.u16_bytes(value))
+    }
+
+    pub fn store_u32(&mut self, address: u32, value: u32) -> Result<(), Fault> {
+        self.write(address, self.endian.u32_bytes(value))
+    }
+
+    /// Doubleword (`sdc1`)
+    pub fn store_u64(&mut self, address: u32, value: u64) -> Result<(), Fault> {
+        self.write(address, self.endian.u64_bytes(value))
+    }
+
+    /// Load `byte` (1, 2 or 4) bytes from `address`
+    pub fn load(&self, address: u32, byte: usize) -> Result<u32, Fault> {
+        match byte {
+            1 => self.load_u8(address).map(u32::from),
+            2 => self.load_u16(address).map(u32::from),
+            _ => self.load_u32(address),
+        }
+    }
+
+    /// Store the lower `byte` (1, 2 or 4) bytes of `value` to `address`
+    pub fn store(&mut self, address: u32, byte: usize, value: u32) -> Result<(), Fault> {
+        match byte {
+            1 => self.store_u8(address, value as u8),
+            2 => self.store_u16(address, value as u16),
+            _ => self.store_u32(address, value),
+        }
+    }
+
+    /// Null-terminated string at `address`
+    pub fn load_string(&self, address: u32) -> Result<String, Fault> {
+        let mut s = String::new();
+        let mut address = address;
+        loop {
+            match self.load_u8(address)? {
+                0 => return Ok(s),
+                ch => s.push(ch as char),
+            }
+            address = address.wrapping_add(1);
+        }
+    }
+
+    /// `len` bytes from `address`
+    pub fn load_bytes(&self, address: u32, len: usize) -> Result<Vec<u8>, Fault> {
+        (0..len).map(|i| self.load_u8(address.wrapping_add(i as u32))).collect()
+    }
+
+    /// Store `bytes` to `address`
+    pub fn store_bytes(&mut self, address: u32, bytes: &[u8]) -> Result<(), Fault> {
+        for (i, byte) in bytes.iter().enumerate() {
+            self.store_u8(address.wrapping_add(i as u32), *byte)?;
+        }
+        Ok(())
+    }
+
+    /// Copy `bytes` to `address` (.text, .data, .ktext or .kdata), extending the segment.
+    /// Kernel segments can be written in user mode.
+    pub fn map(&mut self, address: u32, bytes: &[u8]) -> Result<(), String> {
+        let end = address as u64 + bytes.len() as u64;
+        if !mappable(address, bytes.len() as u64) {
+            return Err(format!("cannot map 0x{:08x}-0x{:08x}: out of the text and data segments", address, end));
+        }
+        let end = end.min(u32::MAX as u64) as u32;
+        if address < STATIC_DATA {
+            self.text_end = self.text_end.max(end);
+        } else if address < DYNAMIC_DATA {
+            self.data_end = self.data_end.max(end);
+        } else if address < KDATA_SEGMENT {
+            self.ktext_end = self.ktext_end.max(end);
+        } else {
+            self.kdata_end = self.kdata_end.max(end);
+        }
+        self.fill(address, bytes);
+        Ok(())
+    }
+
+    /// Unmap .text after `end` (Use only in REPL)
+    pub fn truncate_text(&mut self, end: u32) {
+        self.text_end = end.max(TEXT_SEGMENT);
+    }
+
+    /// Append `bytes` to .data (or .kdata) segment.  Returns the first address
+    pub fn push_data(&mut self, kernel: bool, bytes: &[u8]) -> Result<u32, String> {
+        let address = self.reserve_data(kernel, bytes.len() as u32)?;
+        self.fill(address, bytes);
+        Ok(address)
+    }
+
+    /// Append `len` zero bytes to .data (or .kdata) segment.  Returns the first address.
+    /// .data cannot overflow into the heap.
+    pub fn reserve_data(&mut self, kernel: bool, len: u32) -> Result<u32, String> {
+        let (end, limit) = if kernel {
+            (&mut self.kdata_end, u32::MAX)
+        } else {
+            (&mut self.data_end, DYNAMIC_DATA)
+        };
+        let address = *end;
+        match address.checked_add(len).filter(|&e| e <= limit) {
+            Some(e) => {
+                *end = e;
+                Ok(address)
+            },
+            None => Err(format!("{} segment overflows: 0x{:08x} + {} bytes",
+                if kernel { ".kdata" } else { ".data" }, address, len)),
+        }
+    }
+
+    /// Address of the next .data (or .kdata) byte
+    pub fn data_end(&self, kernel: bool) -> u32 {
+        if kernel { self.kdata_end } else { self.data_end }
+    }
+
     // Memory allocation
     // Returns the first assigned address
     pub fn malloc(&mut self, size: i32) -> i32 {
-        let first = self.dynamic_data.len() as u32 + DYNAMIC_DATA;
+        let first = self.heap_end;
         if 0 < size {
-            self.dynamic_data.resize(size as usize + self.dynamic_data.len(), 0);
+            self.heap_end = self.heap_end.saturating_add(size as u32).min(STACK_LIMIT);
         }
         first as i32
     }
 
+    /// Reset except the byte order, the root of files, the clock, the seed and the console
     pub fn clear(&mut self) {
-        self.registers = Registers::default();
-        self.hi = 0;
-        self.lo = 0;
-        self.base_address = 0;
-        self.static_data.clear();
-        self.dynamic_data.clear();
-        self.stack.clear();
+        let endian = self.endian;
+        let clock = self.clock;
+        let mut files = std::mem::take(&mut self.files);
+        let mut random = std::mem::take(&mut self.random);
+        let console = std::mem::replace(&mut self.console, Box::new(StdConsole));
+        files.clear();
+        random.clear();
+        *self = Memory { endian, files, clock, random, console, ..Memory::default() };
+    }
+
+    pub fn is_mapped(&self, address: u32) -> bool {
+        (TEXT_SEGMENT..self.text_end).contains(&address) ||
+        (KTEXT_SEGMENT..self.ktext_end).contains(&address) ||
+        // Up to the end of the page of the last data (e.g. a string without the terminator)
+        (STATIC_DATA..(self.data_end + PAGE_MASK) & !PAGE_MASK).contains(&address) ||
+        (DYNAMIC_DATA..self.heap_end).contains(&address) ||
+        (STACK_LIMIT..USER_END).contains(&address)       ||
+        (KDATA_SEGMENT..=u32::MAX).contains(&address)
+    }
+
+    /// Status.UM is clear or in the exception handler
+    pub fn kernel_mode(&self) -> bool {
+        self.cp0[STATUS] & STATUS_UM == 0 || self.cp0[STATUS] & STATUS_EXL != 0
     }
-}
 
+    /// Offset in the page of aligned and mapped `len` bytes from `address`
+    fn check(&self, address: u32, len: usize) -> Result<usize, Fault> {
+        if address % len as u32 != 0 {
+            return Err(Fault::Unaligned(address));
+        }
+        if !self.is_mapped(address) || !self.is_mapped(address + (len as u32 - 1)) {
+            return Err(Fault::Unmapped(address));
+        }
+        if USER_END <= address && !self.kernel_mode() {
+            return Err(Fault::Kernel(address));
+        }
+        Ok((address & PAGE_MASK) as usize)
+    }
+
+    fn read<const N: usize>(&self, address: u32) -> Result<[u8; N], Fault> {
+        let offset = self.check(address, N)?;
+        let mut bytes = [0; N];
+        if let Some(page) = self.pages.get(&(address >> PAGE_BITS)) {
+            bytes.copy_from_slice(&page[offset..offset+N]);
+        }
+        Ok(bytes)
+    }
+
+    fn write<const N: usize>(&mut self, address: u32, bytes: [u8; N]) -> Result<(), Fault> {
+        let offset = self.check(address, N)?;
+        let page = self.pages.entry(address >> PAGE_BITS)
+            .or_insert_with(|| Box::new([0; PAGE_SIZE]));
+        page[offset..offset+N].copy_from_slice(&bytes);
+        Ok(())
+    }
+
+    /// Copy `bytes` to `address` regardless of the mapping and the mode
+    fn fill(&mut self, address: u32, bytes: &[u8]) {
+        for (i, byte) in bytes.iter().enumerate() {
+            let address = address.wrapping_add(i as u32);
+            let page = self.pages.entry(address >> PAGE_BITS)
+                .or_insert_with(|| Box::new([0; PAGE_SIZE]));
+            page[(address & PAGE_MASK) as usize] = *byte;
+        }
+    }
+}
@@ -1,5 +1,11 @@
 use super::super::token::*;
-use super::super::token::memory::*;
+use std::collections::BTreeMap;
+
+impl Default for Tokens {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Tokens {
     pub fn new() -> Self {
@@ -11,12 +17,13 @@ impl Tokens {
         let fp_register_trace = std::env::var("FP_REGISTER_TRACE").is_ok();
 
         Tokens { token,
-                 data_area_now: true,
+                 data_area_now: false,
+                 kernel_area_now: false,
                  idx: 0,
                  foremost: true,
                  length: 0,
-                 addresses: Vec::new(),
                  filenames: Vec::new(),
+                 constants: BTreeMap::new(),
                  token_trace,
                  data_trace,
                  stack_trace,
@@ -30,13 +37,19 @@ impl Tokens {
         self.idx = 0;
         self.foremost = true;
         self.length = 0;
-        self.data_area_now = true;
+        self.data_area_now = false;
+        self.kernel_area_now = false;
+        self.constants.clear();
     }
 
     pub fn len(&self) -> usize {
         self.length
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
     pub fn push(&mut self, kind: TokenKind, line: u32, filename_idx: usize) {
         self.length += 1;
         self.token.push(Token { kind, line, filename_idx });
@@ -69,12 +82,12 @@ impl Tokens {
         }
     }
 
-    pub fn add_address(&mut self, label: String, token_index: usize) {
-        self.addresses.push((label, token_index));
-    }
-
-    pub fn add_file(&mut self, file: &str) {
+    /// Return: filename index
+    /// Constants of the previous file are not visible
+    pub fn add_file(&mut self, file: &str) -> usize {
+        self.constants.clear();
         self.filenames.push(file.to_string());
+        self.filenames.len() - 1
     }
 
     #[allow(dead_code)]
@@ -83,7 +96,6 @@ impl Tokens {
         self.idx = 0;
     }
 
-    #[allow(dead_code)]
     pub fn token_trace(&self) -> bool {
         self.token_trace
     }
@@ -105,7 +117,9 @@ impl Tokens {
     }
 
     pub fn consume(&mut self) -> Option<&Token> {
-        if self.foremost {
+        if self.length == 0 {
+            None
+        } else if self.foremost {
             self.foremost = false;
 
             // `TOKEN_TRACE=1 cargo run`
@@ -161,11 +175,14 @@ impl Tokens {
     }
 
     pub fn filename(&self) -> String {
-        let f_idx = self.token[self.idx].filename_idx;
-        if self.filenames.len() <= f_idx {
+        self.filename_of(self.token[self.idx].filename_idx)
+    }
+
+    pub fn filename_of(&self, filename_idx: usize) -> String {
+        if self.filenames.len() <= filename_idx {
             "REPL".to_string()
         } else {
-            self.filenames[f_idx].clone()
+            self.filenames[filename_idx].clone()
         }
     }
 
@@ -180,118 +197,4 @@ impl Tokens {
     pub fn is_none(&self) -> bool {
         self.idx + 1 >= self.length
     }
-
-    /// Get data index of String same as TokenKind::ADDRESS() from TokenKind::LABEL()
-    pub fn expect_address(&self) -> Result<usize, String> {
-        if let TokenKind::ADDRESS(s) = &self.token[self.idx].kind {
-            let line = self.token[self.idx].line;
-            for t in &self.token {
-                if let TokenKind::LABEL(name, _, Some(idx)) = &t.kind {
-                    if *s == *name {
-                        return Ok(*idx);
-                    }
-                }
-            }
-            Err(format!("{}: invalid address: {}", line, s))
-        } else {
-            let t = &self.token[self.idx];
-            Err(format!("{}: expect TokenKind::ADDRESS(String). but got: {:?}", t.line, t.kind))
-        }
-    }
-
-    /// Get label index of String same as TokenKind::ADDRESS() from TokenKind::LABEL()
-    pub fn expect_label(&self) -> Result<usize, String> {
-        if let TokenKind::ADDRESS(s) = &self.token[self.idx].kind {
-            for a in &self.addresses {
-                if *s == *a.0 {
-                    return Ok(a.1);
-                }
-            }
-            let line = self.token[self.idx].line;
-            Err(format!("{}: invalid address: {}", line, s))
-        } else {
-            let t = &self.token[self.idx];
-            Err(format!("{}: expect TokenKind::ADDRESS(String). but got: {:?}", t.line, t.kind))
-        }
-    }
-
-    pub fn expect_instruction(&self) -> Result<InstructionKind, String> {
-        if let TokenKind::INSTRUCTION(k) = self.token[self.idx].kind {
-            Ok(k)
-        } else {
-            let t = &self.token[self.idx];
-            Err(format!("{}: expect TokenKind::INSTRUCTION(InstructionKind). but got: {:?}", t.line, t.kind))
-        }
-    }
-
-    pub fn expect_register(&self) -> Result<usize, String> {
-        if let TokenKind::REGISTER(_, i) = self.token[self.idx].kind {
-            Ok(i)
-        } else {
-            let t = &self.token[self.idx];
-            Err(format!("{}: expect TokenKind::REGISTER(RegisterKind, usize). but got: {:?}", t.line, t.kind))
-        }
-    }
-
-    /// Return: Ok((register_idx, append idx))
-    pub fn expect_memory(&self) -> Result<(usize, u32), String> {
-        if let TokenKind::MEMORY(_, i, j) = self.token[self.idx].kind {
-            Ok((i, j))
-        } else {
-            let t = &self.token[self.idx];
-            Err(format!("{}: expect TokenKind::MEMORY(RegisterKind, usize, i32). but got: {:?}", t.line, t.kind))
-        }
-    }
-
-    /// Return: Ok((register_idx, data index))
-    pub fn expect_data(&self) -> Result<(usize, usize), String> {
-        if let TokenKind::DATA(_, r_i, s) = &self.token[self.idx].kind {
-            for t in &self.token {
-                if let TokenKind::LABEL(name, _, Some(d_i)) = &t.kind {
-                    if *s == *name {
-                        return Ok((*r_i, *d_i + STATIC_DATA as usize));
-                    }
-                }
-            }
-        }
-        let t = &self.token[self.idx];
-        Err(format!("{}: expect TokenKind::DATA(RegisterKind, usize, String). but got: {:?}", t.line, t.kind))
-    }
-
-    pub fn expect_integer(&self) -> Result<i32, String> {
-        if let TokenKind::INTEGER(i) = self.token[self.idx].kind {
-            Ok(i)
-        } else {
-            let t = &self.token[self.idx];
-            Err(format!("{}: expect TokenKind::INTEGER(i32). but got: {:?}", t.line, t.kind))
-        }
-    }
-
-    pub fn expect_floating(&self) -> Result<f32, String> {
-        if let TokenKind::FLOATING(f) = self.token[self.idx].kind {
-            Ok(f)
-        } else {
-            let t = &self.token[self.idx];
-            Err(format!("{}: expect TokenKind::FLOATING(f32). but got: {:?}", t.line, t.kind))
-        }
-    }
-
-    pub fn expect_literal(&self) -> Result<String, String> {
-        if let TokenKind::LITERAL(l) = &self.token[self.idx].kind {
-            Ok(l.to_string())
-        } else {
-            let t = &self.token[self.idx];
-            Err(format!("{}: expect TokenKind::LITERAL(String). but got: {:?}", t.line, t.kind))
-        }
-    }
-
-    pub fn expect_eol(&self) -> Result<(), String> {
-        if let TokenKind::EOL = self.token[self.idx].kind {
-            Ok(())
-        } else {
-            let t = &self.token[self.idx];
-            Err(format!("{}: expect TokenKind::EOL. but got: {:?}", t.line, t.kind))
-        }
-    }
 }
-
@@ -1,8 +1,15 @@
 pub mod method;
 pub mod register;
 pub mod memory;
+pub mod cp0;
+pub mod fpu;
+pub mod file;
+pub mod clock;
+pub mod random;
+pub mod console;
 
 use super::token::register::RegisterKind;
+use std::collections::BTreeMap;
 
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -63,10 +70,15 @@ pub enum InstructionKind {
     /// Comparison
     SLT,      // Rd, Rs, Rt    | Rd = if Rs < Rt  then 1 else 0
     SLTI,     // Rd, Rs, Imm   | Rd = if Rs < Imm then 1 else 0
+    SLTU,     // Rd, Rs, Rt    | Rd = if Rs < Rt  then 1 else 0 (unsigned)
+    SLTIU,    // Rd, Rs, Imm   | Rd = if Rs < Imm then 1 else 0 (unsigned)
     SEQ,      // Rd, Rs, Rt    | Rd = if Rs == Rt then 1 else 0
     SGE,      // Rd, Rs, Rt    | Rd = if Rs >= Rt then 1 else 0
-    SGT,      // Rd, Rs, Rt    | Rd = if Rs = Rt  then 1 else 0
+    SGEU,     // Rd, Rs, Rt    | Rd = if Rs >= Rt then 1 else 0 (unsigned)
+    SGT,      // Rd, Rs, Rt    | Rd = if Rs > Rt  then 1 else 0
+    SGTU,     // Rd, Rs, Rt    | Rd = if Rs > Rt  then 1 else 0 (unsigned)
     SLE,      // Rd, Rs, Rt    | Rd = if Rs <= Rt then 1 else 0
+    SLEU,     // Rd, Rs, Rt    | Rd = if Rs <= Rt then 1 else 0 (unsigned)
     SNE,      // Rd, Rs, Rt    | Rd = if Rs != Rt then 1 else 0
 
     /// Branch
@@ -74,22 +86,26 @@ pub enum InstructionKind {
     BEQ,      // Rs, Rt, label | goto label if Rs == Rt
     BNE,      // Rs, Rt, label | goto label if Rs != Rt
     BGE,      // Rs, Rt, label | goto label if Rs >= Rt
+    BGEU,     // Rs, Rt, label | goto label if Rs >= Rt (unsigned)
     BGT,      // Rs, Rt, label | goto label if Rs > Rt
+    BGTU,     // Rs, Rt, label | goto label if Rs > Rt (unsigned)
     BLE,      // Rs, Rt, label | goto label if Rs <= Rt
+    BLEU,     // Rs, Rt, label | goto label if Rs <= Rt (unsigned)
     BLT,      // Rs, Rt, label | goto label if Rs < Rt
+    BLTU,     // Rs, Rt, label | goto label if Rs < Rt (unsigned)
     BEQZ,     // Rs, label     | goto label if Rs == 0
     BGEZ,     // Rs, label     | goto label if Rs >= 0
     BGTZ,     // Rs, label     | goto label if Rs > 0
     BLEZ,     // Rs, label     | goto label if Rs <= 0
     BLTZ,     // Rs, label     | goto label if Rs < 0
     BNEZ,     // Rs, label     | goto label if Rs != 0
-    BGEZAL,   // Rs, label     | $ra = next idx; goto label if Rs >= 0
-    BLTZAL,   // Rs, label     | $ra = next idx; goto label if Rs < 0
+    BGEZAL,   // Rs, label     | $ra = next address; goto label if Rs >= 0
+    BLTZAL,   // Rs, label     | $ra = next address; goto label if Rs < 0
 
     /// Jump
     J,        // Target        | goto Target
-    JAL,      // Target        | $ra = next idx; goto Target
-    JR,       // Rs, Rd        | Rd = next idx; goto Rs
+    JAL,      // Target        | $ra = next address; goto Target
+    JR,       // Rs, Rd        | Rd = next address; goto Rs
     JALR,     // Rs            | goto Rs
 
     /// Load
@@ -116,6 +132,22 @@ pub enum InstructionKind {
 
     /// Exception, Interrupt
     SYSCALL,  //
+    BREAK,    // [code]        | Breakpoint exception
+    TEQ,      // Rs, Rt        | Trap if Rs == Rt
+    TNE,      // Rs, Rt        | Trap if Rs != Rt
+    TGE,      // Rs, Rt        | Trap if Rs >= Rt
+    TGEU,     // Rs, Rt        | Trap if Rs >= Rt (unsigned)
+    TLT,      // Rs, Rt        | Trap if Rs < Rt
+    TLTU,     // Rs, Rt        | Trap if Rs < Rt (unsigned)
+    TEQI,     // Rs, Imm       | Trap if Rs == Imm
+    TNEI,     // Rs, Imm       | Trap if Rs != Imm
+    TGEI,     // Rs, Imm       | Trap if Rs >= Imm
+    TGEIU,    // Rs, Imm       | Trap if Rs >= Imm (unsigned)
+    TLTI,     // Rs, Imm       | Trap if Rs < Imm
+    TLTIU,    // Rs, Imm       | Trap if Rs < Imm (unsigned)
+    ERET,     //               | PC = EPC, Status.EXL = 0
+    MFC0,     // Rt, Rd        | Rt = CP0[Rd]
+    MTC0,     // Rt, Rd        | CP0[Rd] = Rt
     NOP,      // Do nothing
 
     /// My own
@@ -129,31 +161,43 @@ pub enum InstructionKind {
 
     /// # FPU Instructions
     /// Loads and Stores Using Register+Offset Address Mode
-    //LDC1,
+    LDC1,     // Ft, address   | Ft:Ft+1 = double at address (64bit)
     //LWC1,
-    //SDC1,
+    SDC1,     // Ft, address   | double at address = Ft:Ft+1 (64bit)
     //SWC1,
 
     /// Move To and From Instructions
-    //CFC1,
-    //CTC1,
+    CFC1,     // Rt, Fs        | Rt = FCR[Fs] (FIR or FCSR)
+    CTC1,     // Rt, Fs        | FCR[Fs] = Rt
     //MFC1,
-    MTC1,
+    MTC1,     // Rt, Fs        | Fs = Rt (raw bits)
+    MOVF,     // Rd, Rs, [cc]  | Rd = Rs if FCC(cc) is false
+    MOVT,     // Rd, Rs, [cc]  | Rd = Rs if FCC(cc) is true
+    MOVN_S,   // Fd, Fs, Rt    | Fd = Fs if Rt != 0
+    MOVZ_S,   // Fd, Fs, Rt    | Fd = Fs if Rt == 0
 
     /// Arithmetic Instructions
     ABS_S,
     ADD_S,
     DIV_S,
-    //MADD_S,
+    MADD_S,   // Fd, Fr, Fs, Ft | Fd = Fs * Ft + Fr
     //MSUB_S,
     MUL_S,
     NEG_S,
     //NMADD_S,
     //NMSUB_S,
-    //RECIP_S,
-    //RSQRT_S,
-    //SQRT,
+    RECIP_S,
+    RSQRT_S,
+    SQRT_S,
     SUB_S,
+    ABS_D,
+    ADD_D,
+    DIV_D,
+    MOV_D,
+    MUL_D,
+    NEG_D,
+    SQRT_D,
+    SUB_D,
 
     /// Branch Instructions
     BC1F,
@@ -163,10 +207,21 @@ pub enum InstructionKind {
     C_EQ_S,
     C_LE_S,
     C_LT_S,
+    C_EQ_D,
+    C_LE_D,
+    C_LT_D,
 
     /// Convert Instructions
     CVT_S_W,
     CVT_W_S,
+    ROUND_W_S,
+    TRUNC_W_S,
+    CEIL_W_S,
+    FLOOR_W_S,
+    CVT_D_S,
+    CVT_D_W,
+    CVT_S_D,
+    CVT_W_D,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -174,17 +229,38 @@ pub enum InstructionKind {
 pub enum IndicateKind {
     text,            // Text space start
     data,            // Data space start
+    ktext(Option<u32>),  // Kernel text space start (Address)
+    kdata(Option<u32>),  // Kernel data space start (Address)
     globl(String),   // TODO
     word(u32),       // Number(32-bit)
     half(u16),       // (16-bit)
     byte(u8),        // 1 char(8-bit)
     float(f32),      // single floating point (32-bit)
+    double(f64),     // double floating point (64-bit)
     space(u32),      // n byte
     ascii(String),   // String
     asciiz(String),  // String
     align(u8),       // Align
 }
 
+/// Relocation operator of the address of a label
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum Relocation {
+    hi,  // %hi(address): upper 16 bits (adjusted for the sign of %lo)
+    lo,  // %lo(address): lower 16 bits (sign-extended)
+}
+
+impl Relocation {
+    /// `lui $t0, %hi(x)` and `addiu $t0, $t0, %lo(x)` load the address of x
+    pub fn apply(self, address: u32) -> i32 {
+        match self {
+            Relocation::hi => (address.wrapping_add(0x8000) >> 16) as i32,
+            Relocation::lo => address as i16 as i32,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 #[allow(clippy::upper_case_acronyms)]
 pub enum TokenKind {
@@ -193,9 +269,10 @@ pub enum TokenKind {
     INTEGER(i32),                         // integer immediate
     FLOATING(f32),                        // floating point immediate
     REGISTER(RegisterKind, usize),        // (_, Register index)
-    MEMORY(RegisterKind, usize, u32),     // (_, Register index, Append index) for data,stack
-    DATA(RegisterKind, usize, String),    // (_, Label name)
-    LABEL(String, usize, Option<usize>),  // (Literal, Token index, Data index)
+    MEMORY(RegisterKind, usize, i32),     // (_, Register index, Offset) for data,stack
+    DATA(RegisterKind, usize, String, i32),  // (_, Register index, Label name, Offset)
+    RELOCATION(Relocation, String, i32, Option<usize>),  // (_, Label name, Offset, Register index) e.g. %lo(x+4)($t0)
+    LABEL(String, usize, Option<usize>),  // (Literal, Token index, Data address)
     ADDRESS(String),                      // Label
     LITERAL(String),                      // Literal
     EOL,                                  // End of Line
@@ -205,19 +282,20 @@ pub enum TokenKind {
 pub struct Token {
     pub kind: TokenKind,  // Token kind
     pub line: u32,        // Number of lines
-    filename_idx: usize,  // File name index
+    pub filename_idx: usize,  // File name index
 }
 
 #[derive(Debug)]
 pub struct Tokens {
     pub token: Vec<Token>,            // Token's vector
     pub data_area_now: bool,          // for data_analysis() in REPL
+    pub kernel_area_now: bool,        // .ktext or .kdata
 
     idx: usize,                       // Current index
     foremost: bool,                   // Foremost
     length: usize,                    // Token length
-    addresses: Vec<(String, usize)>,  // (label name, token index)
     filenames: Vec<String>,           // filenames
+    pub constants: BTreeMap<String, String>,  // .eqv and .set of the current file (Name => Text)
 
     token_trace: bool,                // Environment variable 'TOKEN_TRACE'
     data_trace: bool,                 // Environment variable 'DATA_TRACE'
@@ -226,5 +304,3 @@ pub struct Tokens {
     fp_register_trace: bool,          // Environment variable 'FP_REGISTER_TRACE'
 }
 
-pub static CONSUME_ERR: &str = "token.consume(): none";
-
@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+/// Pseudorandom number generators of the random syscalls (40-44), selected by id
+#[derive(Debug, Default)]
+pub struct Random {
+    seed: Option<u64>,  // Seed of the generators which are not seeded by syscall 40 (entropy if None)
+    streams: HashMap<i32, StdRng>,
+}
+
+impl Random {
+    /// Make unseeded generators reproducible
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        self.streams.clear();
+    }
+
+    /// Remove all generators (the seed is kept)
+    pub fn clear(&mut self) {
+        self.streams.clear();
+    }
+
+    /// set seed (40)
+    pub fn seed(&mut self, id: i32, seed: u64) {
+        self.streams.insert(id, StdRng::seed_from_u64(seed));
+    }
+
+    /// Generator `id`, created on first use
+    pub fn stream(&mut self, id: i32) -> &mut StdRng {
+        let seed = self.seed;
+        self.streams.entry(id).or_insert_with(|| match seed {
+            Some(seed) => StdRng::seed_from_u64(seed ^ (id as u32 as u64) << 32),
+            None => StdRng::from_entropy(),
+        })
+    }
+}
@@ -18,14 +18,33 @@ pub enum RegisterKind {
     ra,                              //    31: Return Address
 
     // Floating-Point registers
-    f0, f2,                                    // Hold results of floating-point type function
-    f1, f3, f4, f5, f6, f7, f8, f9, f10, f11,  // Temporary registers
+    f0, f1, f2, f3,                            // Hold results of floating-point type function ($f0, $f2)
+    f4, f5, f6, f7, f8, f9, f10, f11,          // Temporary registers
     f12, f13, f14, f15, f16, f17, f18, f19,    // Pass single or double precision actual arguments
     f20, f21, f22, f23,                        // Temporary registers
     f24, f25, f26, f27, f28, f29, f30, f31,    // Saved registers
     fcsr,                                      // FPU control and status register
 }
 
+impl RegisterKind {
+    /// Register of the `Registers` index
+    pub fn from_index(idx: usize) -> Option<Self> {
+        use RegisterKind::*;
+        const KINDS: [RegisterKind; 32+32+1] = [
+            zero, at, v0, v1, a0, a1, a2, a3,
+            t0, t1, t2, t3, t4, t5, t6, t7,
+            s0, s1, s2, s3, s4, s5, s6, s7,
+            t8, t9, k0, k1, gp, sp, fp, ra,
+            f0,  f1,  f2,  f3,  f4,  f5,  f6,  f7,
+            f8,  f9,  f10, f11, f12, f13, f14, f15,
+            f16, f17, f18, f19, f20, f21, f22, f23,
+            f24, f25, f26, f27, f28, f29, f30, f31,
+            fcsr,
+        ];
+        KINDS.get(idx).cloned()
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Registers {
     regs: [i32; 32+32+1],
@@ -41,6 +60,41 @@ impl Default for Registers {
     }
 }
 
+impl Registers {
+    /// Double of the FP register pair (`idx`: even, lower word. `idx`+1: upper word)
+    pub fn double(&self, idx: usize) -> f64 {
+        f64::from_bits((self.regs[idx+1] as u32 as u64) << 32 | self.regs[idx] as u32 as u64)
+    }
+
+    pub fn set_double(&mut self, idx: usize, value: f64) {
+        let bits = value.to_bits();
+        self.regs[idx]   = bits as u32 as i32;
+        self.regs[idx+1] = (bits >> 32) as u32 as i32;
+    }
+
+    /// Floating point condition code `cc` (0-7) in FCSR
+    pub fn fcc(&self, cc: usize) -> bool {
+        self.regs[RegisterKind::fcsr as usize] as u32 & fcc_bit(cc) != 0
+    }
+
+    pub fn set_fcc(&mut self, cc: usize, value: bool) {
+        let fcsr = &mut self.regs[RegisterKind::fcsr as usize];
+        if value {
+            *fcsr |= fcc_bit(cc) as i32;
+        } else {
+            *fcsr &= !fcc_bit(cc) as i32;
+        }
+    }
+}
+
+/// FCC(0) is bit 23, FCC(1)-FCC(7) are bits 25-31
+fn fcc_bit(cc: usize) -> u32 {
+    match cc {
+        0 => 1 << 23,
+        _ => 1 << (24 + cc),
+    }
+}
+
 impl Index<usize> for Registers {
     type Output = i32;
 